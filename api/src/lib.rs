@@ -4,8 +4,36 @@ pub mod routes {
     pub const ROOT: &str = "/";
     pub const GET_INFO: &str = "/v1/getinfo";
     pub const GET_BALANCE: &str = "/v1/getbalance";
+    pub const NEW_ADDR: &str = "/v1/newaddr";
+    pub const WITHDRAW: &str = "/v1/withdraw";
+    pub const LIST_UTXOS: &str = "/v1/listUtxos";
+    pub const LIST_TRANSACTIONS: &str = "/v1/listTransactions";
     pub const LIST_CHANNELS: &str = "/v1/channel/listChannels";
+    pub const GET_CHANNEL: &str = "/v1/channel/:id";
+    pub const LIST_FORWARDS: &str = "/v1/channel/listForwards";
+    pub const SET_CHANNEL_FEE: &str = "/v1/channel/setChannelFee";
     pub const OPEN_CHANNEL: &str = "/v1/channel/openChannel";
+    pub const CLOSE_CHANNEL: &str = "/v1/channel/closeChannel";
+    pub const GENERATE_INVOICE: &str = "/v1/invoice/genInvoice";
+    pub const LIST_INVOICES: &str = "/v1/invoice/listInvoices";
+    pub const PAY_INVOICE: &str = "/v1/pay";
+    pub const KEYSEND: &str = "/v1/pay/keysend";
+    pub const LIST_PAYMENTS: &str = "/v1/pay/listPayments";
+    pub const GET_PAYMENT: &str = "/v1/pay/payment/:payment_hash";
+    pub const PROBE: &str = "/v1/pay/probe";
+    pub const CONNECT_PEER: &str = "/v1/peer/connect";
+    pub const LIST_PEERS: &str = "/v1/peer/listPeers";
+    pub const DISCONNECT_PEER: &str = "/v1/peer/disconnect/:id";
+    pub const LIST_NODES: &str = "/v1/network/listNodes";
+    pub const GET_NODE: &str = "/v1/network/getNode/:id";
+    pub const LIST_NETWORK_CHANNELS: &str = "/v1/network/listChannels";
+    pub const GET_NETWORK_CHANNEL: &str = "/v1/network/listChannel/:short_channel_id";
+    pub const GET_ROUTE: &str = "/v1/network/getRoute/:id/:msatoshi";
+    pub const INTERCEPT_SCID: &str = "/v1/lsp/interceptScid";
+    pub const SCORER_CHANNEL: &str = "/v1/scorer/channel/:short_channel_id";
+    pub const BAN_NODE: &str = "/v1/scorer/banNode/:id";
+    pub const UNBAN_NODE: &str = "/v1/scorer/unbanNode/:id";
+    pub const RESET_SCORER: &str = "/v1/scorer/reset";
 }
 
 #[derive(Serialize, Deserialize)]
@@ -41,6 +69,65 @@ pub struct Balance {
     pub unconf_balance: u64,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct NewAddressResponse {
+    /// Bech32 address
+    pub address: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Withdraw {
+    /// Any bitcoin accepted type, including bech32
+    pub address: String,
+    /// Amount to be withdrawn in satoshis or "all" to sweep the wallet
+    pub satoshis: String,
+    /// urgent/normal/slow/<sats>perkw/<sats>perkb
+    /// At least the minimum relay fee of 250perkw or 1000perkb
+    /// Default: 'normal'
+    #[serde(rename = "feeRate", default)]
+    pub fee_rate: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WithdrawResponse {
+    /// Transaction
+    pub tx: String,
+    /// Transaction ID
+    pub txid: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Utxo {
+    /// Transaction ID
+    pub txid: String,
+    /// Index of the output in the transaction
+    pub output: u32,
+    /// Amount in satoshis
+    pub value: u64,
+    /// Address the output pays to
+    pub address: Option<String>,
+    /// confirmed/unconfirmed
+    pub status: String,
+    /// Height of the block the transaction confirmed in
+    pub blockheight: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WalletTransaction {
+    /// Transaction ID
+    pub txid: String,
+    /// Satoshis received by the wallet
+    pub received: u64,
+    /// Satoshis spent from the wallet
+    pub sent: u64,
+    /// Fee in satoshis, if known
+    pub fee: Option<u64>,
+    /// Height of the block the transaction confirmed in
+    pub blockheight: Option<u32>,
+    /// UNIX timestamp of the block the transaction confirmed in
+    pub timestamp: Option<u64>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Channel {
     /// Pub key
@@ -61,7 +148,7 @@ pub struct Channel {
     pub msatoshi_to_us: String,
     /// Total msats in the channel
     pub msatoshi_total: String,
-    /// Number of msats to push to their side
+    /// Number of msats on their side, the receivable msats plus their channel reserve
     pub msatoshi_to_them: String,
     /// Minimum number of msats on their side
     pub their_channel_reserve_satoshis: String,
@@ -69,6 +156,8 @@ pub struct Channel {
     pub our_channel_reserve_satoshis: String,
     /// Spendable msats
     pub spendable_msatoshi: String,
+    /// Msats we can receive
+    pub receivable_msatoshi: String,
     ///
     /// pub funding_allocation_msat: String,
     /// Flag indicating if this peer initiated the channel (0,1)
@@ -77,6 +166,111 @@ pub struct Channel {
     pub alias: String,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct ChannelDetail {
+    #[serde(flatten)]
+    pub channel: Channel,
+    /// Base fee we charge for forwarding over this channel
+    pub fee_base_msat: Option<u32>,
+    /// Proportional fee we charge for forwarding over this channel
+    pub fee_proportional_millionths: Option<u32>,
+    /// Blocks we require between an incoming and outgoing htlc
+    pub cltv_expiry_delta: Option<u16>,
+    /// Confirmations of the funding transaction
+    pub confirmations: Option<u32>,
+    /// Confirmations needed before the channel is ready
+    pub confirmations_required: Option<u32>,
+    /// Funding transaction output (txid:vout)
+    pub funding_outpoint: Option<String>,
+    /// Features of the peer (hex)
+    pub features: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SetChannelFee {
+    /// Channel ID (hex), a list of them or "all"
+    pub id: ChannelIds,
+    /// Base fee in msats, unchanged if missing
+    pub base: Option<u32>,
+    /// Proportional fee in millionths, unchanged if missing
+    pub ppm: Option<u32>,
+    /// Blocks required between an incoming and outgoing htlc, unchanged if missing
+    pub cltv_expiry_delta: Option<u16>,
+    /// Limit on our exposure to dust htlcs in msats, unchanged if missing
+    pub max_dust_htlc_exposure_msat: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ChannelIds {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ChannelFee {
+    /// Channel ID (hex)
+    pub channel_id: String,
+    pub base: u32,
+    pub ppm: u32,
+    pub cltv_expiry_delta: u16,
+    pub max_dust_htlc_exposure_msat: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ListForwards {
+    /// Only forwards at or after this time (unix seconds)
+    pub start: Option<u64>,
+    /// Only forwards before this time (unix seconds)
+    pub end: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ListForwardsResponse {
+    /// Newest first
+    pub forwards: Vec<Forward>,
+    /// Totals per channel over the same period, highest fees first
+    pub fee_totals: Vec<ChannelFees>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Forward {
+    /// Channel the payment came in on (hex)
+    pub in_channel: Option<String>,
+    /// Channel the payment went out on (hex)
+    pub out_channel: Option<String>,
+    /// Amount received on the incoming channel, not reported yet
+    pub amount_in_msat: Option<u64>,
+    /// Amount sent on the outgoing channel, not reported yet
+    pub amount_out_msat: Option<u64>,
+    /// Fee earned, unknown while an on-chain claim is pending
+    pub fee_msat: Option<u64>,
+    /// The payment was claimed by the next node on-chain
+    pub claim_from_onchain_tx: bool,
+    /// Unix timestamp
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ChannelFees {
+    /// Channel ID (hex)
+    pub channel_id: String,
+    /// Forwards that came in over the channel
+    pub incoming: ForwardTotals,
+    /// Forwards that went out over the channel
+    pub outgoing: ForwardTotals,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ForwardTotals {
+    /// Number of forwards
+    pub forwards: usize,
+    /// Total amount of the forwards whose amount is known
+    pub amount_msat: u64,
+    /// Total fees earned by the forwards
+    pub fee_msat: u64,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct FundChannel {
     /// Pub key of the peer
@@ -84,6 +278,7 @@ pub struct FundChannel {
     /// Amount in satoshis
     pub satoshis: String,
     /// urgent/normal/slow/<sats>perkw/<sats>perkb
+    /// At least the minimum relay fee of 250perkw or 1000perkb
     #[serde(rename = "feeRate")]
     pub fee_rate: String,
     /// Flag to announce the channel (true, false)
@@ -113,3 +308,328 @@ pub struct FundChannelResponse {
     /// channel_id of the newly created channel (hex)
     pub channel_id: String,
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct ChannelClose {
+    /// Channel ID (hex)
+    pub channel_id: String,
+    /// Broadcast our latest commitment transaction instead of negotiating with the peer
+    /// Default: 'false'
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ChannelCloseResponse {
+    /// Channel ID (hex)
+    pub channel_id: String,
+    /// Closing transaction, empty while the close is pending
+    pub tx: Option<String>,
+    /// Closing transaction ID, empty while the close is pending
+    pub txid: Option<String>,
+    /// mutual/unilateral
+    #[serde(rename = "type")]
+    pub close_type: String,
+    /// broadcast/pending
+    pub status: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GenerateInvoice {
+    /// Amount in millisatoshis, leave empty to accept any amount
+    #[serde(default)]
+    pub amount: Option<u64>,
+    /// Description embedded in the invoice
+    pub description: String,
+    /// Number of seconds the invoice is valid for
+    /// Default: 3600
+    #[serde(default)]
+    pub expiry: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GenerateInvoiceResponse {
+    /// Payment hash (hex)
+    pub payment_hash: String,
+    /// UNIX timestamp of when the invoice expires
+    pub expires_at: u64,
+    /// BOLT11 invoice
+    pub bolt11: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ListInvoices {
+    /// Only return the invoice with this payment hash (hex)
+    pub payment_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Invoice {
+    /// Payment hash (hex)
+    pub payment_hash: String,
+    /// BOLT11 invoice
+    pub bolt11: String,
+    /// Amount requested in millisatoshis, missing if any amount is accepted
+    pub msatoshi: Option<u64>,
+    /// Description embedded in the invoice
+    pub description: String,
+    /// unpaid/paid/expired
+    pub status: String,
+    /// UNIX timestamp of when the invoice expires
+    pub expires_at: u64,
+    /// Amount received in millisatoshis
+    pub msatoshi_received: Option<u64>,
+    /// UNIX timestamp of when the invoice was paid
+    pub paid_at: Option<u64>,
+    /// Proof of payment (hex)
+    pub payment_preimage: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PayInvoice {
+    /// BOLT11 invoice
+    pub invoice: String,
+    /// Amount in millisatoshis, only for invoices that do not specify one
+    #[serde(default)]
+    pub amount: Option<u64>,
+}
+
+/// Custom TLV records are not supported yet. Requests with fields other than these are rejected.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keysend {
+    /// Public key of the destination node (hex)
+    pub pubkey: String,
+    /// Amount in millisatoshis
+    pub amount: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PaymentResponse {
+    /// Payment hash (hex)
+    pub payment_hash: String,
+    /// succeeded/failed/pending, a pending payment can be looked up later
+    pub status: String,
+    /// Proof of payment (hex)
+    pub payment_preimage: Option<String>,
+    /// Routing fees paid in millisatoshis
+    pub fee_msat: Option<u64>,
+    /// Number of hops in the longest path of the payment
+    pub hops: Option<usize>,
+    /// Why the payment failed
+    pub failure: Option<PaymentFailure>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PaymentFailure {
+    /// Description of the failure
+    pub reason: String,
+    /// The recipient rejected the payment so retrying will not help
+    pub permanent: bool,
+    /// Channel responsible for the last failed attempt
+    pub short_channel_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ListPayments {
+    /// inbound/outbound
+    #[serde(default)]
+    pub direction: Option<String>,
+    /// pending/succeeded/failed
+    #[serde(default)]
+    pub status: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Payment {
+    /// Payment hash (hex)
+    pub payment_hash: String,
+    /// inbound/outbound
+    pub direction: String,
+    /// pending/succeeded/failed
+    pub status: String,
+    /// Amount in millisatoshis
+    pub msatoshi: Option<u64>,
+    /// Routing fees paid in millisatoshis
+    pub fee_msat: Option<u64>,
+    /// Proof of payment (hex)
+    pub payment_preimage: Option<String>,
+    /// UNIX timestamp when the payment was initiated or received
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ConnectPeer {
+    /// Pubkey@host:port
+    pub id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ConnectPeerResponse {
+    /// Pubkey of the connected node
+    pub id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Peer {
+    /// Pubkey of the node
+    pub id: String,
+    pub connected: bool,
+    /// The stored host:port we connect to
+    pub netaddr: Option<String>,
+    /// Features from its node announcement (hex)
+    pub features: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DisconnectPeer {
+    /// Also delete the stored address so we do not reconnect
+    #[serde(default)]
+    pub forget: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ListGraph {
+    /// Start after this node id or short channel id, from the beginning if missing
+    pub after: Option<String>,
+    /// Maximum number of entries to return
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NetworkNode {
+    /// Pub key (hex)
+    pub id: String,
+    pub alias: Option<String>,
+    /// Colour (hex rgb)
+    pub color: Option<String>,
+    /// Timestamp of the last node announcement
+    pub last_update: Option<u32>,
+    /// Addresses (host:port)
+    pub addresses: Vec<String>,
+    /// Features from its node announcement (hex)
+    pub features: Option<String>,
+    /// Short channel ids of its announced channels
+    pub channels: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NetworkChannel {
+    pub short_channel_id: u64,
+    /// Pub key of the first node (hex)
+    pub node_one: String,
+    /// Pub key of the second node (hex)
+    pub node_two: String,
+    pub capacity_sats: Option<u64>,
+    /// Policy for payments from node one to node two
+    pub one_to_two: Option<ChannelPolicy>,
+    /// Policy for payments from node two to node one
+    pub two_to_one: Option<ChannelPolicy>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ChannelPolicy {
+    pub enabled: bool,
+    pub base_fee_msat: u32,
+    pub fee_proportional_millionths: u32,
+    pub cltv_expiry_delta: u16,
+    pub htlc_minimum_msat: u64,
+    pub htlc_maximum_msat: u64,
+    /// Timestamp of the last channel update
+    pub last_update: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetRoute {
+    /// CLTV delta the destination requires, 18 if missing
+    pub final_cltv: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Route {
+    /// One path per part of the payment
+    pub paths: Vec<RoutePath>,
+    /// Total fees over all paths
+    pub fee_msat: u64,
+    /// Total amount received by the destination
+    pub amount_msat: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RoutePath {
+    pub hops: Vec<RouteHop>,
+    /// Fees paid over this path
+    pub fee_msat: u64,
+    /// Sum of the cltv deltas over this path
+    pub cltv_expiry_delta: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RouteHop {
+    /// Pub key of the node we send to (hex)
+    pub id: String,
+    pub short_channel_id: u64,
+    /// Fee for this hop, or the amount received on the last hop
+    pub fee_msat: u64,
+    /// CLTV delta for this hop, or the final cltv on the last hop
+    pub cltv_expiry_delta: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct InterceptScid {
+    /// Pub key of the node we open a channel to when it is paid (hex)
+    pub id: String,
+}
+
+/// The hop from us to the node for its invoice route hints.
+#[derive(Serialize, Deserialize)]
+pub struct RouteHint {
+    /// Our pub key (hex)
+    pub node_id: String,
+    pub short_channel_id: u64,
+    pub fee_base_msat: u32,
+    pub fee_proportional_millionths: u32,
+    pub cltv_expiry_delta: u16,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Probe {
+    /// Public key of the destination node (hex). Either this or an invoice is required
+    #[serde(default)]
+    pub pubkey: Option<String>,
+    /// BOLT11 invoice to probe the route to its payee with its route hints
+    #[serde(default)]
+    pub invoice: Option<String>,
+    /// Amount in millisatoshis, required unless the invoice specifies one
+    #[serde(default)]
+    pub amount: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ProbeResponse {
+    /// The probe reached the destination
+    pub success: bool,
+    /// The path the probe took
+    pub hops: Vec<RouteHop>,
+    /// The hop whose channel failed the probe, if known
+    pub failed_hop: Option<RouteHop>,
+}
+
+/// What the scorer has learned about the liquidity of a channel in each direction.
+#[derive(Serialize, Deserialize)]
+pub struct ChannelLiquidity {
+    pub short_channel_id: u64,
+    /// Pub key of the first node (hex)
+    pub node_one: String,
+    /// Pub key of the second node (hex)
+    pub node_two: String,
+    /// None if nothing has been learned in this direction yet
+    pub one_to_two: Option<LiquidityRange>,
+    pub two_to_one: Option<LiquidityRange>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct LiquidityRange {
+    pub min_msat: u64,
+    pub max_msat: u64,
+}
@@ -1,5 +1,5 @@
 use crate::convert::{BlockchainInfo, FeeResponse, RawTx};
-use base64;
+use base64::{engine::general_purpose::STANDARD, Engine};
 use bitcoin::blockdata::transaction::Transaction;
 use bitcoin::consensus::encode;
 use bitcoin::hash_types::{BlockHash, Txid};
@@ -8,7 +8,6 @@ use lightning_block_sync::http::HttpEndpoint;
 use lightning_block_sync::rpc::RpcClient;
 use lightning_block_sync::{AsyncBlockSourceResult, BlockData, BlockHeaderData, BlockSource};
 use log::info;
-use serde_json;
 use settings::Settings;
 use std::collections::HashMap;
 use std::fs::File;
@@ -16,10 +15,12 @@ use std::io::Read;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::broadcast;
 
 pub struct Client {
     bitcoind_rpc_client: Arc<RpcClient>,
     fees: Arc<HashMap<Target, AtomicU32>>,
+    broadcasts: broadcast::Sender<Transaction>,
 }
 
 #[derive(Clone, Eq, Hash, PartialEq)]
@@ -49,7 +50,7 @@ impl BlockSource for &Client {
         Box::pin(async move { self.bitcoind_rpc_client.get_block(header_hash).await })
     }
 
-    fn get_best_block(&self) -> AsyncBlockSourceResult<(BlockHash, Option<u32>)> {
+    fn get_best_block(&self) -> AsyncBlockSourceResult<'_, (BlockHash, Option<u32>)> {
         Box::pin(async move { self.bitcoind_rpc_client.get_best_block().await })
     }
 }
@@ -71,9 +72,11 @@ impl Client {
         fees.insert(Target::Background, AtomicU32::new(MIN_FEERATE));
         fees.insert(Target::Normal, AtomicU32::new(2000));
         fees.insert(Target::HighPriority, AtomicU32::new(5000));
+        let (broadcasts, _) = broadcast::channel(100);
         let client = Self {
             bitcoind_rpc_client: Arc::new(bitcoind_rpc_client),
             fees: Arc::new(fees),
+            broadcasts,
         };
         Client::poll_for_fee_estimates(client.fees.clone(), client.bitcoind_rpc_client.clone());
         info!(
@@ -87,7 +90,7 @@ impl Client {
         let mut file = File::open(settings.bitcoin_cookie_path.clone())?;
         let mut cookie = String::new();
         file.read_to_string(&mut cookie)?;
-        let credentials = base64::encode(cookie.as_bytes());
+        let credentials = STANDARD.encode(cookie.as_bytes());
         let http_endpoint = HttpEndpoint::for_host(settings.bitcoind_rpc_host.clone())
             .with_port(settings.bitcoind_rpc_port);
         RpcClient::new(&credentials, http_endpoint)
@@ -167,6 +170,11 @@ impl Client {
             .unwrap();
     }
 
+    /// Receive every transaction LDK asks us to broadcast, e.g. to find out the closing transaction of a channel.
+    pub fn subscribe_broadcasts(&self) -> broadcast::Receiver<Transaction> {
+        self.broadcasts.subscribe()
+    }
+
     pub async fn get_blockchain_info(&self) -> BlockchainInfo {
         self.bitcoind_rpc_client
             .call_method::<BlockchainInfo>("getblockchaininfo", &[])
@@ -199,6 +207,8 @@ impl FeeEstimator for Client {
 
 impl BroadcasterInterface for Client {
     fn broadcast_transaction(&self, tx: &Transaction) {
+        // Only fails if there are no subscribers.
+        let _ = self.broadcasts.send(tx.clone());
         let bitcoind_rpc_client = self.bitcoind_rpc_client.clone();
         let tx_serialized = serde_json::json!(encode::serialize_hex(tx));
         tokio::spawn(async move {
@@ -43,6 +43,7 @@ impl TryInto<FeeResponse> for JsonResponse {
 
 pub struct BlockchainInfo {
     pub latest_height: usize,
+    pub latest_headers: usize,
     pub latest_blockhash: BlockHash,
     pub chain: String,
 }
@@ -52,6 +53,7 @@ impl TryInto<BlockchainInfo> for JsonResponse {
     fn try_into(self) -> std::io::Result<BlockchainInfo> {
         Ok(BlockchainInfo {
             latest_height: self.0["blocks"].as_u64().unwrap() as usize,
+            latest_headers: self.0["headers"].as_u64().unwrap() as usize,
             latest_blockhash: BlockHash::from_hex(self.0["bestblockhash"].as_str().unwrap())
                 .unwrap(),
             chain: self.0["chain"].as_str().unwrap().to_string(),
@@ -0,0 +1,19 @@
+-- The wallet columns that aren't looked up by value are encrypted as one blob per row. Rows
+-- from before keep the plaintext columns until they are encrypted on startup. Script pubkeys
+-- stay plaintext, they are looked up both by path and by script.
+ALTER TABLE wallet_utxos ADD COLUMN utxo BLOB;
+ALTER TABLE wallet_utxos ADD COLUMN key_version INT NOT NULL DEFAULT 0;
+ALTER TABLE wallet_transaction_details ADD COLUMN details BLOB;
+ALTER TABLE wallet_transaction_details ADD COLUMN key_version INT NOT NULL DEFAULT 0;
+ALTER TABLE wallet_last_derivation_indices ADD COLUMN last_index BLOB;
+ALTER TABLE wallet_last_derivation_indices ADD COLUMN key_version INT NOT NULL DEFAULT 0;
+ALTER TABLE wallet_checksums ADD COLUMN key_version INT NOT NULL DEFAULT 0;
+ALTER TABLE wallet_sync_time ADD COLUMN sync_time BLOB;
+ALTER TABLE wallet_sync_time ADD COLUMN key_version INT NOT NULL DEFAULT 0;
+
+-- Set once the rows written before encryption was added are encrypted. Plaintext rows are
+-- rejected from then on.
+CREATE TABLE plaintext_migration (
+    id              INT PRIMARY KEY,
+    timestamp       TIMESTAMP NOT NULL DEFAULT current_timestamp()
+);
@@ -0,0 +1,3 @@
+-- LDK doesn't report the amounts of a forward yet, they stay NULL until it does.
+ALTER TABLE forwards ADD COLUMN amount_in_msat INT;
+ALTER TABLE forwards ADD COLUMN amount_out_msat INT;
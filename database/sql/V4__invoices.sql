@@ -0,0 +1,14 @@
+CREATE TABLE invoices (
+    payment_hash            BYTES NOT NULL,
+    payment_secret          BYTES NOT NULL,
+    bolt11                  STRING NOT NULL,
+    amount_msat             INT,
+    description             STRING NOT NULL,
+    expires_at              TIMESTAMP NOT NULL,
+    status                  STRING NOT NULL,
+    preimage                BYTES,
+    amount_received_msat    INT,
+    paid_at                 TIMESTAMP,
+    timestamp               TIMESTAMP NOT NULL DEFAULT current_timestamp(),
+    PRIMARY KEY ( payment_hash )
+);
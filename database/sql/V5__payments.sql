@@ -0,0 +1,11 @@
+CREATE TABLE payments (
+    payment_hash            BYTES NOT NULL,
+    direction               STRING NOT NULL,
+    status                  STRING NOT NULL,
+    preimage                BYTES,
+    secret                  BYTES,
+    amount_msat             INT,
+    fee_msat                INT,
+    timestamp               TIMESTAMP NOT NULL,
+    PRIMARY KEY ( payment_hash, direction )
+);
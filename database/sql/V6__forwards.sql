@@ -0,0 +1,11 @@
+CREATE TABLE forwards (
+    id                      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    prev_channel_id         BYTES,
+    next_channel_id         BYTES,
+    fee_earned_msat         INT,
+    claim_from_onchain_tx   BOOL NOT NULL,
+    timestamp               TIMESTAMP NOT NULL,
+    INDEX ( timestamp )
+);
+
+GRANT SELECT ON TABLE forwards TO grafana;
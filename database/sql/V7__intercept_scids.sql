@@ -0,0 +1,5 @@
+CREATE TABLE intercept_scids (
+    short_channel_id        INT PRIMARY KEY,
+    node_id                 BYTES NOT NULL,
+    timestamp               TIMESTAMP NOT NULL
+);
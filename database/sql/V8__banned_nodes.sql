@@ -0,0 +1,4 @@
+CREATE TABLE banned_nodes (
+    node_id                 BYTES PRIMARY KEY,
+    timestamp               TIMESTAMP NOT NULL
+);
@@ -0,0 +1,7 @@
+-- The key each blob was encrypted with, 0 for plaintext rows written before encryption.
+ALTER TABLE channel_manager ADD COLUMN key_version INT NOT NULL DEFAULT 0;
+ALTER TABLE channel_monitors ADD COLUMN key_version INT NOT NULL DEFAULT 0;
+ALTER TABLE channel_monitor_updates ADD COLUMN key_version INT NOT NULL DEFAULT 0;
+ALTER TABLE network_graph ADD COLUMN key_version INT NOT NULL DEFAULT 0;
+ALTER TABLE scorer ADD COLUMN key_version INT NOT NULL DEFAULT 0;
+ALTER TABLE wallet_transactions ADD COLUMN key_version INT NOT NULL DEFAULT 0;
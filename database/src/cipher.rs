@@ -0,0 +1,152 @@
+use aes_gcm_siv::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm_siv::{Aes256GcmSiv, Nonce};
+use anyhow::{anyhow, bail, Result};
+
+/// Rows written before encryption was added. They are encrypted once on startup and can't be
+/// decrypted, so a plaintext row inserted later is never trusted.
+pub const PLAINTEXT: i64 = 0;
+
+/// Row key of the tables that only have one row.
+pub const SINGLETON_ROW: &[u8] = b"singleton";
+
+const KEY_VERSION: i64 = 1;
+const NONCE_SIZE: usize = 12;
+
+/// Authenticated encryption of the blobs we store in the database. Each row records the
+/// key_version it was written with so that the key can be changed later.
+#[derive(Clone)]
+pub struct Cipher {
+    cipher: Aes256GcmSiv,
+}
+
+impl Cipher {
+    pub fn new(key: &[u8; 32]) -> Cipher {
+        Cipher {
+            cipher: Aes256GcmSiv::new(key.into()),
+        }
+    }
+
+    pub fn key_version(&self) -> i64 {
+        KEY_VERSION
+    }
+
+    /// The table name and the primary key of the row are authenticated, so a blob can't be
+    /// moved to another table or row.
+    pub fn encrypt(&self, table: &str, row_key: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let nonce = Aes256GcmSiv::generate_nonce(&mut OsRng);
+        let mut ciphertext = nonce.to_vec();
+        ciphertext.extend(
+            self.cipher
+                .encrypt(
+                    &nonce,
+                    Payload {
+                        msg: plaintext,
+                        aad: &aad(table, row_key),
+                    },
+                )
+                .expect("Encryption failed"),
+        );
+        ciphertext
+    }
+
+    pub fn decrypt(
+        &self,
+        table: &str,
+        row_key: &[u8],
+        key_version: i64,
+        data: &[u8],
+    ) -> Result<Vec<u8>> {
+        match key_version {
+            PLAINTEXT => bail!("Unencrypted data in {}", table),
+            KEY_VERSION => {
+                if data.len() < NONCE_SIZE {
+                    bail!("Encrypted data in {} is too short", table);
+                }
+                let (nonce, ciphertext) = data.split_at(NONCE_SIZE);
+                self.cipher
+                    .decrypt(
+                        Nonce::from_slice(nonce),
+                        Payload {
+                            msg: ciphertext,
+                            aad: &aad(table, row_key),
+                        },
+                    )
+                    .map_err(|_| anyhow!("Failed to decrypt data in {}", table))
+            }
+            version => bail!("Unknown key version {} in {}", version, table),
+        }
+    }
+}
+
+// Table names don't contain a zero byte, so the boundary to the row key is unambiguous.
+fn aad(table: &str, row_key: &[u8]) -> Vec<u8> {
+    let mut aad = table.as_bytes().to_vec();
+    aad.push(0);
+    aad.extend_from_slice(row_key);
+    aad
+}
+
+#[test]
+fn test_cipher() {
+    let cipher = Cipher::new(&[1u8; 32]);
+    let ciphertext = cipher.encrypt("scorer", SINGLETON_ROW, b"secret");
+    assert_ne!(b"secret".to_vec(), ciphertext);
+    assert_ne!(
+        ciphertext,
+        cipher.encrypt("scorer", SINGLETON_ROW, b"secret")
+    );
+    assert_eq!(
+        b"secret".to_vec(),
+        cipher
+            .decrypt("scorer", SINGLETON_ROW, cipher.key_version(), &ciphertext)
+            .unwrap()
+    );
+    assert!(cipher
+        .decrypt("scorer", SINGLETON_ROW, PLAINTEXT, b"plain")
+        .is_err());
+
+    assert!(cipher
+        .decrypt(
+            "network_graph",
+            SINGLETON_ROW,
+            cipher.key_version(),
+            &ciphertext
+        )
+        .is_err());
+    assert!(Cipher::new(&[2u8; 32])
+        .decrypt("scorer", SINGLETON_ROW, cipher.key_version(), &ciphertext)
+        .is_err());
+    assert!(cipher
+        .decrypt("scorer", SINGLETON_ROW, 2, &ciphertext)
+        .is_err());
+}
+
+#[test]
+fn test_cipher_row_key() {
+    let cipher = Cipher::new(&[1u8; 32]);
+    let ciphertext = cipher.encrypt("channel_monitors", b"channel 1", b"monitor");
+    assert_eq!(
+        b"monitor".to_vec(),
+        cipher
+            .decrypt(
+                "channel_monitors",
+                b"channel 1",
+                cipher.key_version(),
+                &ciphertext
+            )
+            .unwrap()
+    );
+    assert!(cipher
+        .decrypt(
+            "channel_monitors",
+            b"channel 2",
+            cipher.key_version(),
+            &ciphertext
+        )
+        .is_err());
+    // The row key can't be shifted into the table name.
+    let ciphertext = cipher.encrypt("scorer", b"", b"scorer");
+    assert!(cipher
+        .decrypt("score", b"r", cipher.key_version(), &ciphertext)
+        .is_err());
+}
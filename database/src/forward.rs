@@ -0,0 +1,34 @@
+use std::time::SystemTime;
+
+/// A payment we routed from one of our channels to another.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Forward {
+    pub prev_channel_id: Option<[u8; 32]>,
+    pub next_channel_id: Option<[u8; 32]>,
+    // Not reported by LDK yet.
+    pub amount_in_msat: Option<u64>,
+    pub amount_out_msat: Option<u64>,
+    // Unknown until the on-chain claim has been spent if the inbound channel was force closed.
+    pub fee_earned_msat: Option<u64>,
+    pub claim_from_onchain_tx: bool,
+    pub timestamp: SystemTime,
+}
+
+impl Forward {
+    pub fn new(
+        prev_channel_id: Option<[u8; 32]>,
+        next_channel_id: Option<[u8; 32]>,
+        fee_earned_msat: Option<u64>,
+        claim_from_onchain_tx: bool,
+    ) -> Forward {
+        Forward {
+            prev_channel_id,
+            next_channel_id,
+            amount_in_msat: None,
+            amount_out_msat: None,
+            fee_earned_msat,
+            claim_from_onchain_tx,
+            timestamp: SystemTime::now(),
+        }
+    }
+}
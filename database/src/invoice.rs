@@ -0,0 +1,47 @@
+use std::time::SystemTime;
+
+use anyhow::{bail, Result};
+use lightning::ln::{PaymentHash, PaymentPreimage, PaymentSecret};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InvoiceStatus {
+    Unpaid,
+    Paid,
+}
+
+impl InvoiceStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InvoiceStatus::Unpaid => "unpaid",
+            InvoiceStatus::Paid => "paid",
+        }
+    }
+
+    pub fn parse(status: &str) -> Result<InvoiceStatus> {
+        match status {
+            "unpaid" => Ok(InvoiceStatus::Unpaid),
+            "paid" => Ok(InvoiceStatus::Paid),
+            _ => bail!("Unknown invoice status {}", status),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Invoice {
+    pub payment_hash: PaymentHash,
+    pub payment_secret: PaymentSecret,
+    pub bolt11: String,
+    pub amount_msat: Option<u64>,
+    pub description: String,
+    pub expires_at: SystemTime,
+    pub status: InvoiceStatus,
+    pub preimage: Option<PaymentPreimage>,
+    pub amount_received_msat: Option<u64>,
+    pub paid_at: Option<SystemTime>,
+}
+
+impl Invoice {
+    pub fn is_expired(&self) -> bool {
+        self.status == InvoiceStatus::Unpaid && self.expires_at < SystemTime::now()
+    }
+}
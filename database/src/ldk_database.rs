@@ -1,86 +1,97 @@
-use crate::{connection, to_i64, Client};
-use anyhow::{bail, Result};
+use crate::cipher::{Cipher, SINGLETON_ROW};
+use crate::{from_maybe_i64, to_i64, EncryptedColumn, KeyColumn, Plaintext};
+use anyhow::{anyhow, bail, Result};
 use bitcoin::hashes::Hash;
 use bitcoin::secp256k1::PublicKey;
 use bitcoin::{BlockHash, Txid};
+use deadpool_postgres::Pool;
 use lightning::chain::chaininterface::{BroadcasterInterface, FeeEstimator};
 use lightning::chain::chainmonitor::MonitorUpdateId;
-use lightning::chain::channelmonitor::{ChannelMonitor, ChannelMonitorUpdate};
+use lightning::chain::channelmonitor::{
+    ChannelMonitor, ChannelMonitorUpdate, CLOSED_CHANNEL_UPDATE_ID,
+};
 use lightning::chain::keysinterface::{KeysInterface, Sign};
 use lightning::chain::transaction::OutPoint;
 use lightning::chain::{self, ChannelMonitorUpdateStatus, Watch};
 use lightning::ln::channelmanager::{ChannelManager, ChannelManagerReadArgs};
+use lightning::ln::{PaymentHash, PaymentPreimage, PaymentSecret};
 use lightning::routing::gossip::NetworkGraph;
 use lightning::routing::scoring::{
     ProbabilisticScorer, ProbabilisticScoringParameters, WriteableScore,
 };
 use lightning::util::logger::Logger;
 use lightning::util::persist::Persister;
-use lightning::util::ser::ReadableArgs;
 use lightning::util::ser::Writeable;
-use log::{debug, info};
+use lightning::util::ser::{Readable, ReadableArgs};
+use log::{debug, error};
 use logger::KndLogger;
 use settings::Settings;
 use std::convert::TryInto;
 use std::io::Cursor;
 use std::ops::Deref;
 use std::sync::Arc;
+use std::time::SystemTime;
 use tokio::runtime::Handle;
-use tokio::sync::RwLock;
+use tokio_postgres::Row;
 
+use crate::forward::Forward;
+use crate::invoice::{Invoice, InvoiceStatus};
+use crate::monitor_writer::{MonitorWrite, MonitorWriter, FIRST_RETRY_DELAY, MAX_RETRY_DELAY};
+use crate::payment::{Payment, PaymentDirection, PaymentStatus};
 use crate::peer::Peer;
 
 // This gets called from a background thread in LDK so need a handle to the runtime.
+// Errors are returned as io::Error which LDK's Persister expects.
 macro_rules! block_in_place {
     ($statement: literal, $params: expr, $self: expr) => {
         tokio::task::block_in_place(move || {
             $self.runtime.block_on(async move {
                 $self
-                    .client
-                    .read()
+                    .pool
+                    .get()
                     .await
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
                     .execute($statement, $params)
                     .await
-                    .unwrap()
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
             })
         })
     };
 }
 
 pub struct LdkDatabase {
-    pub(crate) client: Arc<RwLock<Client>>,
+    pool: Pool,
     runtime: Handle,
+    cipher: Cipher,
+    monitor_writer: MonitorWriter,
+    monitor_compaction_interval: u64,
 }
 
 impl LdkDatabase {
-    pub async fn new(settings: &Settings) -> Result<LdkDatabase> {
-        info!(
-            "Connecting LDK to Cockroach database {} at {}:{}",
-            settings.database_name, settings.database_host, settings.database_port
-        );
-        let client = connection(&settings).await?;
-        let client = Arc::new(RwLock::new(client));
-
-        Ok(LdkDatabase {
-            client,
+    pub fn new(settings: &Settings, pool: Pool, cipher: Cipher) -> LdkDatabase {
+        LdkDatabase {
+            monitor_writer: MonitorWriter::new(pool.clone()),
+            pool,
             runtime: Handle::current(),
-        })
+            cipher,
+            monitor_compaction_interval: settings.database_monitor_compaction_interval,
+        }
     }
 
     pub async fn is_first_start(&self) -> Result<bool> {
         Ok(self
-            .client
-            .read()
-            .await
+            .pool
+            .get()
+            .await?
             .query_opt("SELECT true FROM channel_manager", &[])
             .await?
             .is_none())
     }
 
     pub async fn persist_peer(&self, peer: &Peer) -> Result<()> {
-        self.client
-            .read()
-            .await
+        self.pool
+            .get()
+            .await?
             .execute(
                 "UPSERT INTO peers (public_key, address) \
             VALUES ($1, $2)",
@@ -97,14 +108,14 @@ impl LdkDatabase {
         debug!("Fetching peers from database");
         let mut peers = Vec::new();
         for row in self
-            .client
-            .read()
-            .await
+            .pool
+            .get()
+            .await?
             .query("SELECT * FROM peers", &[])
             .await?
         {
-            let public_key: Vec<u8> = row.get(&"public_key");
-            let address: Vec<u8> = row.get(&"address");
+            let public_key: Vec<u8> = row.get("public_key");
+            let address: Vec<u8> = row.get("address");
             peers.push(Peer {
                 public_key: PublicKey::from_slice(&public_key).unwrap(),
                 socket_addr: String::from_utf8(address)?.parse().unwrap(),
@@ -114,10 +125,10 @@ impl LdkDatabase {
         Ok(peers)
     }
 
-    pub async fn delete_peer(&self, peer: &Peer) {
-        self.client
-            .read()
-            .await
+    pub async fn delete_peer(&self, peer: &Peer) -> Result<()> {
+        self.pool
+            .get()
+            .await?
             .execute(
                 "DELETE FROM peers \
             WHERE public_key = $1 AND address = $2",
@@ -126,32 +137,293 @@ impl LdkDatabase {
                     &peer.socket_addr.to_string().as_bytes(),
                 ],
             )
-            .await
-            .unwrap();
+            .await?;
+        Ok(())
+    }
+
+    pub async fn persist_invoice(&self, invoice: &Invoice) -> Result<()> {
+        self.pool.get().await?
+            .execute(
+                "UPSERT INTO invoices (payment_hash, payment_secret, bolt11, amount_msat, description, \
+            expires_at, status, preimage, amount_received_msat, paid_at) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+                &[
+                    &invoice.payment_hash.0.as_slice(),
+                    &invoice.payment_secret.0.as_slice(),
+                    &invoice.bolt11,
+                    &invoice.amount_msat.map(|x| to_i64!(x)),
+                    &invoice.description,
+                    &invoice.expires_at,
+                    &invoice.status.as_str(),
+                    &invoice.preimage.as_ref().map(|x| x.0.as_slice()),
+                    &invoice.amount_received_msat.map(|x| to_i64!(x)),
+                    &invoice.paid_at,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_invoice(&self, payment_hash: &PaymentHash) -> Result<Option<Invoice>> {
+        self.pool
+            .get()
+            .await?
+            .query_opt(
+                "SELECT * FROM invoices WHERE payment_hash = $1",
+                &[&payment_hash.0.as_slice()],
+            )
+            .await?
+            .map(|row| Self::invoice_from_row(&row))
+            .transpose()
+    }
+
+    pub async fn fetch_invoices(&self) -> Result<Vec<Invoice>> {
+        debug!("Fetching invoices from database");
+        self.pool
+            .get()
+            .await?
+            .query("SELECT * FROM invoices ORDER BY timestamp", &[])
+            .await?
+            .iter()
+            .map(Self::invoice_from_row)
+            .collect()
+    }
+
+    fn invoice_from_row(row: &Row) -> Result<Invoice> {
+        let payment_hash: Vec<u8> = row.get("payment_hash");
+        let payment_secret: Vec<u8> = row.get("payment_secret");
+        let preimage: Option<Vec<u8>> = row.get("preimage");
+        let status: String = row.get("status");
+        Ok(Invoice {
+            payment_hash: PaymentHash(payment_hash.as_slice().try_into()?),
+            payment_secret: PaymentSecret(payment_secret.as_slice().try_into()?),
+            bolt11: row.get("bolt11"),
+            amount_msat: from_maybe_i64!(row, "amount_msat"),
+            description: row.get("description"),
+            expires_at: row.get("expires_at"),
+            status: InvoiceStatus::parse(&status)?,
+            preimage: preimage
+                .map(|x| x.as_slice().try_into().map(PaymentPreimage))
+                .transpose()?,
+            amount_received_msat: from_maybe_i64!(row, "amount_received_msat"),
+            paid_at: row.get("paid_at"),
+        })
+    }
+
+    pub async fn persist_payment(&self, payment: &Payment) -> Result<()> {
+        self.pool
+            .get()
+            .await?
+            .execute(
+                "UPSERT INTO payments (payment_hash, direction, status, preimage, secret, \
+            amount_msat, fee_msat, timestamp) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                &[
+                    &payment.payment_hash.0.as_slice(),
+                    &payment.direction.as_str(),
+                    &payment.status.as_str(),
+                    &payment.preimage.as_ref().map(|x| x.0.as_slice()),
+                    &payment.secret.as_ref().map(|x| x.0.as_slice()),
+                    &payment.amount_msat.map(|x| to_i64!(x)),
+                    &payment.fee_msat.map(|x| to_i64!(x)),
+                    &payment.timestamp,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_payment(
+        &self,
+        payment_hash: &PaymentHash,
+        direction: PaymentDirection,
+    ) -> Result<Option<Payment>> {
+        self.pool
+            .get()
+            .await?
+            .query_opt(
+                "SELECT * FROM payments WHERE payment_hash = $1 AND direction = $2",
+                &[&payment_hash.0.as_slice(), &direction.as_str()],
+            )
+            .await?
+            .map(|row| Self::payment_from_row(&row))
+            .transpose()
+    }
+
+    /// Newest first. Each filter is ignored when None.
+    pub async fn fetch_payments(
+        &self,
+        payment_hash: Option<&PaymentHash>,
+        direction: Option<PaymentDirection>,
+        status: Option<PaymentStatus>,
+    ) -> Result<Vec<Payment>> {
+        debug!("Fetching payments from database");
+        self.pool
+            .get()
+            .await?
+            .query(
+                "SELECT * FROM payments \
+            WHERE ($1::BYTES IS NULL OR payment_hash = $1) \
+            AND ($2::STRING IS NULL OR direction = $2) \
+            AND ($3::STRING IS NULL OR status = $3) \
+            ORDER BY timestamp DESC",
+                &[
+                    &payment_hash.map(|x| x.0.as_slice()),
+                    &direction.map(|x| x.as_str()),
+                    &status.map(|x| x.as_str()),
+                ],
+            )
+            .await?
+            .iter()
+            .map(Self::payment_from_row)
+            .collect()
+    }
+
+    fn payment_from_row(row: &Row) -> Result<Payment> {
+        let payment_hash: Vec<u8> = row.get("payment_hash");
+        let direction: String = row.get("direction");
+        let status: String = row.get("status");
+        let preimage: Option<Vec<u8>> = row.get("preimage");
+        let secret: Option<Vec<u8>> = row.get("secret");
+        Ok(Payment {
+            payment_hash: PaymentHash(payment_hash.as_slice().try_into()?),
+            direction: PaymentDirection::parse(&direction)?,
+            status: PaymentStatus::parse(&status)?,
+            preimage: preimage
+                .map(|x| x.as_slice().try_into().map(PaymentPreimage))
+                .transpose()?,
+            secret: secret
+                .map(|x| x.as_slice().try_into().map(PaymentSecret))
+                .transpose()?,
+            amount_msat: from_maybe_i64!(row, "amount_msat"),
+            fee_msat: from_maybe_i64!(row, "fee_msat"),
+            timestamp: row.get("timestamp"),
+        })
+    }
+
+    pub async fn persist_forward(&self, forward: &Forward) -> Result<()> {
+        self.pool
+            .get()
+            .await?
+            .execute(
+                "INSERT INTO forwards (prev_channel_id, next_channel_id, amount_in_msat, \
+            amount_out_msat, fee_earned_msat, claim_from_onchain_tx, timestamp) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                &[
+                    &forward.prev_channel_id.as_ref().map(|x| x.as_slice()),
+                    &forward.next_channel_id.as_ref().map(|x| x.as_slice()),
+                    &forward.amount_in_msat.map(|x| to_i64!(x)),
+                    &forward.amount_out_msat.map(|x| to_i64!(x)),
+                    &forward.fee_earned_msat.map(|x| to_i64!(x)),
+                    &forward.claim_from_onchain_tx,
+                    &forward.timestamp,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Newest first, from `start` (inclusive) to `end` (exclusive). Each bound is ignored when None.
+    pub async fn fetch_forwards(
+        &self,
+        start: Option<SystemTime>,
+        end: Option<SystemTime>,
+    ) -> Result<Vec<Forward>> {
+        debug!("Fetching forwards from database");
+        self.pool
+            .get()
+            .await?
+            .query(
+                "SELECT * FROM forwards \
+            WHERE ($1::TIMESTAMP IS NULL OR timestamp >= $1) \
+            AND ($2::TIMESTAMP IS NULL OR timestamp < $2) \
+            ORDER BY timestamp DESC",
+                &[&start, &end],
+            )
+            .await?
+            .iter()
+            .map(Self::forward_from_row)
+            .collect()
+    }
+
+    /// Remember which node a short channel id handed out for intercepting HTLCs belongs to.
+    pub async fn persist_intercept_scid(
+        &self,
+        short_channel_id: u64,
+        node_id: &PublicKey,
+    ) -> Result<()> {
+        self.pool
+            .get()
+            .await?
+            .execute(
+                "INSERT INTO intercept_scids (short_channel_id, node_id, timestamp) \
+            VALUES ($1, $2, $3)",
+                &[
+                    &to_i64!(short_channel_id),
+                    &node_id.serialize().as_slice(),
+                    &SystemTime::now(),
+                ],
+            )
+            .await?;
+        Ok(())
     }
 
-    pub async fn fetch_channel_monitors<Signer: Sign, K: Deref>(
+    pub async fn fetch_intercept_scid(&self, short_channel_id: u64) -> Result<Option<PublicKey>> {
+        let row = self
+            .pool
+            .get()
+            .await?
+            .query_opt(
+                "SELECT node_id FROM intercept_scids WHERE short_channel_id = $1",
+                &[&to_i64!(short_channel_id)],
+            )
+            .await?;
+        Ok(match row {
+            Some(row) => Some(PublicKey::from_slice(row.get("node_id"))?),
+            None => None,
+        })
+    }
+
+    fn forward_from_row(row: &Row) -> Result<Forward> {
+        let prev_channel_id: Option<Vec<u8>> = row.get("prev_channel_id");
+        let next_channel_id: Option<Vec<u8>> = row.get("next_channel_id");
+        Ok(Forward {
+            prev_channel_id: prev_channel_id
+                .map(|x| x.as_slice().try_into())
+                .transpose()?,
+            next_channel_id: next_channel_id
+                .map(|x| x.as_slice().try_into())
+                .transpose()?,
+            amount_in_msat: from_maybe_i64!(row, "amount_in_msat"),
+            amount_out_msat: from_maybe_i64!(row, "amount_out_msat"),
+            fee_earned_msat: from_maybe_i64!(row, "fee_earned_msat"),
+            claim_from_onchain_tx: row.get("claim_from_onchain_tx"),
+            timestamp: row.get("timestamp"),
+        })
+    }
+
+    /// Monitors with the updates stored since they were last written replayed onto them.
+    pub async fn fetch_channel_monitors<Signer: Sign, K: Deref, B: Deref, F: Deref>(
         &self,
         keys_manager: K,
-        //		broadcaster: &B,
-        //		fee_estimator: &F,
+        broadcaster: &B,
+        fee_estimator: &F,
     ) -> Result<Vec<(BlockHash, ChannelMonitor<Signer>)>>
     where
         <K as Deref>::Target: KeysInterface<Signer = Signer> + Sized,
-        //      B::Target: BroadcasterInterface,
-        //		F::Target: FeeEstimator,
+        B::Target: BroadcasterInterface,
+        F::Target: FeeEstimator,
     {
         let rows = self
-            .client
-            .read()
-            .await
+            .pool
+            .get()
+            .await?
             .query(
-                "SELECT out_point, monitor \
+                "SELECT out_point, monitor, key_version \
             FROM channel_monitors",
                 &[],
             )
-            .await
-            .unwrap();
+            .await?;
         let mut monitors: Vec<(BlockHash, ChannelMonitor<Signer>)> = vec![];
         for row in rows {
             let out_point: Vec<u8> = row.get("out_point");
@@ -161,6 +433,12 @@ impl LdkDatabase {
             let index = u16::from_le_bytes(index_bytes.try_into().unwrap());
 
             let monitor: Vec<u8> = row.get("monitor");
+            let monitor = self.cipher.decrypt(
+                "channel_monitors",
+                &out_point,
+                row.get("key_version"),
+                &monitor,
+            )?;
             let mut buffer = Cursor::new(&monitor);
             match <(BlockHash, ChannelMonitor<Signer>)>::read(&mut buffer, &*keys_manager) {
                 Ok((blockhash, channel_monitor)) => {
@@ -169,34 +447,56 @@ impl LdkDatabase {
                     {
                         bail!("Unable to find ChannelMonitor for: {}:{}", txid, index);
                     }
-                    /*
-                                        let update_rows = self
-                                            .client
-                                            .read()
-                                            .await
-                                            .query(
-                                                "SELECT update \
-                                            FROM channel_monitor_updates \
-                                            WHERE out_point = $1 \
-                                            ORDER BY update_id ASC",
-                                                &[&out_point],
-                                            )
-                                            .await
-                                            .unwrap();
-
-                                        let updates: Vec<ChannelMonitorUpdate> = update_rows
-                                            .iter()
-                                            .map(|row| {
-                                                let ciphertext: Vec<u8> = row.get("update");
-                                                let update = self.cipher.decrypt(&ciphertext);
-                                                ChannelMonitorUpdate::read(&mut Cursor::new(&update)).unwrap()
-                                            })
-                                            .collect();
-                                        for update in updates {
-                                            channel_monitor
-                                                .update_monitor(&update, broadcaster, fee_estimator.clone(), &KndLogger::global()).unwrap();
-                                        }
-                    */
+                    let update_rows = self
+                        .pool
+                        .get()
+                        .await?
+                        .query(
+                            "SELECT update, update_id, key_version \
+                            FROM channel_monitor_updates \
+                            WHERE out_point = $1 AND update_id > $2 \
+                            ORDER BY update_id ASC",
+                            &[
+                                &out_point,
+                                &update_id_column(channel_monitor.get_latest_update_id()),
+                            ],
+                        )
+                        .await?;
+                    for row in &update_rows {
+                        let update: Vec<u8> = row.get("update");
+                        let update = self.cipher.decrypt(
+                            "channel_monitor_updates",
+                            &monitor_update_key(&out_point, row.get("update_id")),
+                            row.get("key_version"),
+                            &update,
+                        )?;
+                        let update = ChannelMonitorUpdate::read(&mut Cursor::new(&update))
+                            .map_err(|e| {
+                                anyhow!("Failed to deserialize ChannelMonitorUpdate: {}", e)
+                            })?;
+                        if channel_monitor
+                            .update_monitor(
+                                &update,
+                                broadcaster,
+                                &**fee_estimator,
+                                &KndLogger::global(),
+                            )
+                            .is_err()
+                        {
+                            bail!(
+                                "Failed to apply update {} to ChannelMonitor for: {}:{}",
+                                update.update_id,
+                                txid,
+                                index
+                            );
+                        }
+                    }
+                    debug!(
+                        "Fetched ChannelMonitor for {}:{} with {} updates",
+                        txid,
+                        index,
+                        update_rows.len()
+                    );
                     monitors.push((blockhash, channel_monitor));
                 }
                 Err(e) => bail!("Failed to deserialize ChannelMonitor: {}", e),
@@ -205,6 +505,29 @@ impl LdkDatabase {
         Ok(monitors)
     }
 
+    /// Called with each write that LDK is waiting for once it committed. Must be set before
+    /// the ChainMonitor persists anything.
+    pub fn on_monitor_persisted(
+        &self,
+        callback: impl Fn(OutPoint, MonitorUpdateId) + Send + Sync + 'static,
+    ) {
+        self.monitor_writer.on_persisted(Box::new(callback));
+    }
+
+    /// Channel monitor writes that have not committed yet.
+    pub fn pending_monitor_writes(&self) -> usize {
+        self.monitor_writer.pending()
+    }
+
+    /// Failed attempts to write a channel monitor since startup.
+    pub fn failed_monitor_writes(&self) -> u64 {
+        self.monitor_writer.failures()
+    }
+
+    pub async fn wait_for_monitor_writes(&self) {
+        self.monitor_writer.wait_until_idle().await
+    }
+
     pub async fn fetch_channel_manager<
         Signer: Sign,
         M: Deref,
@@ -224,16 +547,22 @@ impl LdkDatabase {
         <L as Deref>::Target: Logger,
     {
         let row = self
-            .client
-            .read()
-            .await
+            .pool
+            .get()
+            .await?
             .query_one(
-                "SELECT manager \
+                "SELECT manager, key_version \
             FROM channel_manager",
                 &[],
             )
             .await?;
         let manager: Vec<u8> = row.get("manager");
+        let manager = self.cipher.decrypt(
+            "channel_manager",
+            SINGLETON_ROW,
+            row.get("key_version"),
+            &manager,
+        )?;
         Ok(
             <(BlockHash, ChannelManager<M, T, K, F, L>)>::read(
                 &mut Cursor::new(manager),
@@ -245,19 +574,70 @@ impl LdkDatabase {
 
     pub async fn fetch_graph(&self) -> Result<Option<NetworkGraph<Arc<KndLogger>>>> {
         let graph = self
-            .client
-            .read()
-            .await
-            .query_opt("SELECT graph FROM network_graph", &[])
+            .pool
+            .get()
+            .await?
+            .query_opt("SELECT graph, key_version FROM network_graph", &[])
             .await?
             .map(|row| {
                 let bytes: Vec<u8> = row.get(0);
+                self.cipher
+                    .decrypt("network_graph", SINGLETON_ROW, row.get(1), &bytes)
+            })
+            .transpose()?
+            .map(|bytes| {
                 NetworkGraph::read(&mut Cursor::new(bytes), KndLogger::global())
                     .expect("Unable to deserialize network graph")
             });
         Ok(graph)
     }
 
+    /// Forget what the scorer has learned. It starts from scratch on the next start.
+    pub async fn delete_scorer(&self) -> Result<()> {
+        self.pool
+            .get()
+            .await?
+            .execute("DELETE FROM scorer", &[])
+            .await?;
+        Ok(())
+    }
+
+    /// Nodes banned from routing through at runtime.
+    pub async fn persist_banned_node(&self, node_id: &PublicKey) -> Result<()> {
+        self.pool
+            .get()
+            .await?
+            .execute(
+                "UPSERT INTO banned_nodes (node_id, timestamp) VALUES ($1, $2)",
+                &[&node_id.serialize().as_slice(), &SystemTime::now()],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_banned_node(&self, node_id: &PublicKey) -> Result<()> {
+        self.pool
+            .get()
+            .await?
+            .execute(
+                "DELETE FROM banned_nodes WHERE node_id = $1",
+                &[&node_id.serialize().as_slice()],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fetch_banned_nodes(&self) -> Result<Vec<PublicKey>> {
+        self.pool
+            .get()
+            .await?
+            .query("SELECT node_id FROM banned_nodes", &[])
+            .await?
+            .iter()
+            .map(|row| Ok(PublicKey::from_slice(row.get("node_id"))?))
+            .collect()
+    }
+
     pub async fn fetch_scorer(
         &self,
         params: ProbabilisticScoringParameters,
@@ -265,13 +645,18 @@ impl LdkDatabase {
     ) -> Result<Option<ProbabilisticScorer<Arc<NetworkGraph<Arc<KndLogger>>>, Arc<KndLogger>>>>
     {
         let scorer = self
-            .client
-            .read()
-            .await
-            .query_opt("SELECT scorer FROM scorer", &[])
+            .pool
+            .get()
+            .await?
+            .query_opt("SELECT scorer, key_version FROM scorer", &[])
             .await?
             .map(|row| {
                 let bytes: Vec<u8> = row.get(0);
+                self.cipher
+                    .decrypt("scorer", SINGLETON_ROW, row.get(1), &bytes)
+            })
+            .transpose()?
+            .map(|bytes| {
                 ProbabilisticScorer::read(
                     &mut Cursor::new(bytes),
                     (params.clone(), graph.clone(), KndLogger::global()),
@@ -292,147 +677,188 @@ where
     L::Target: 'static + Logger,
     S: 'static + WriteableScore<'a>,
 {
+    // The background processor stops when this fails, so wait for the database to come back.
     fn persist_manager(
         &self,
         channel_manager: &ChannelManager<M, T, K, F, L>,
     ) -> Result<(), std::io::Error> {
         let mut buf = vec![];
-        channel_manager.write(&mut buf).unwrap();
-        block_in_place!(
-            "UPSERT INTO channel_manager (manager, timestamp) \
-            VALUES ($1, CURRENT_TIMESTAMP)",
-            &[&buf],
+        channel_manager.write(&mut buf)?;
+        // LDK requires the monitors to be stored before the manager that refers to them. The
+        // monitor writes for this state were queued before it was serialized.
+        tokio::task::block_in_place(|| self.runtime.block_on(self.monitor_writer.flush()));
+        let ciphertext = &self.cipher.encrypt("channel_manager", SINGLETON_ROW, &buf);
+        let mut retry_delay = FIRST_RETRY_DELAY;
+        while let Err(e) = block_in_place!(
+            "UPSERT INTO channel_manager (manager, key_version, timestamp) \
+            VALUES ($1, $2, CURRENT_TIMESTAMP)",
+            &[&ciphertext, &self.cipher.key_version()],
             self
-        );
+        ) {
+            error!(
+                "Failed to persist channel manager, retrying in {}s: {}",
+                retry_delay.as_secs(),
+                e
+            );
+            std::thread::sleep(retry_delay);
+            retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+        }
         Ok(())
     }
 
+    // Unlike the manager, a failed graph or scorer write is only logged by LDK while running and
+    // tried again at its next interval. Both can be rebuilt from gossip and payments, so blocking the
+    // background processor on retries here isn't worth it.
     fn persist_graph(
         &self,
         network_graph: &lightning::routing::gossip::NetworkGraph<L>,
     ) -> Result<(), std::io::Error> {
         let mut buf = vec![];
-        network_graph.write(&mut buf).unwrap();
+        network_graph.write(&mut buf)?;
+        let ciphertext = self.cipher.encrypt("network_graph", SINGLETON_ROW, &buf);
         block_in_place!(
-            "UPSERT INTO network_graph (graph, timestamp)
-            VALUES ($1, CURRENT_TIMESTAMP)",
-            &[&buf],
+            "UPSERT INTO network_graph (graph, key_version, timestamp)
+            VALUES ($1, $2, CURRENT_TIMESTAMP)",
+            &[&ciphertext, &self.cipher.key_version()],
             self
-        );
+        )?;
         Ok(())
     }
 
     fn persist_scorer(&self, scorer: &S) -> Result<(), std::io::Error> {
         let mut buf = vec![];
-        scorer.write(&mut buf).unwrap();
+        scorer.write(&mut buf)?;
+        let ciphertext = self.cipher.encrypt("scorer", SINGLETON_ROW, &buf);
         block_in_place!(
-            "UPSERT INTO scorer (scorer, timestamp)
-            VALUES ($1, CURRENT_TIMESTAMP)",
-            &[&buf],
+            "UPSERT INTO scorer (scorer, key_version, timestamp)
+            VALUES ($1, $2, CURRENT_TIMESTAMP)",
+            &[&ciphertext, &self.cipher.key_version()],
             self
-        );
+        )?;
         Ok(())
     }
 }
 
+// Writes are queued and LDK is told through on_monitor_persisted once they are done.
 impl<ChannelSigner: Sign> chain::chainmonitor::Persist<ChannelSigner> for LdkDatabase {
-    // The CHANNEL_MONITORS table stores the latest monitor and its update_id.
+    // The CHANNEL_MONITORS table stores a full monitor and its update_id, the
+    // CHANNEL_MONITOR_UPDATES table the updates applied to it since.
     fn persist_new_channel(
         &self,
         funding_txo: OutPoint,
         monitor: &ChannelMonitor<ChannelSigner>,
-        _update_id: MonitorUpdateId,
+        update_id: MonitorUpdateId,
     ) -> ChannelMonitorUpdateStatus {
         debug!(
             "Persisting new channel: {:?}:{}",
             funding_txo,
             monitor.get_latest_update_id()
         );
-
-        let mut out_point_buf = vec![];
-        funding_txo.write(&mut out_point_buf).unwrap();
-
-        let mut monitor_buf = vec![];
-        monitor.write(&mut monitor_buf).unwrap();
-
-        block_in_place!(
-            "UPSERT INTO channel_monitors (out_point, monitor, update_id) \
-            VALUES ($1, $2, $3)",
-            &[
-                &out_point_buf,
-                &monitor_buf,
-                &to_i64!(monitor.get_latest_update_id())
-            ],
-            self
+        let out_point = funding_txo.encode();
+        self.monitor_writer.queue(
+            funding_txo,
+            update_id,
+            MonitorWrite::Monitor {
+                monitor: self
+                    .cipher
+                    .encrypt("channel_monitors", &out_point, &monitor.encode()),
+                out_point,
+                update_id: update_id_column(monitor.get_latest_update_id()),
+                key_version: self.cipher.key_version(),
+            },
         );
-        ChannelMonitorUpdateStatus::Completed
+        ChannelMonitorUpdateStatus::InProgress
     }
 
-    // Updates are applied to the monitor when fetched from database.
+    // Only the update is stored, except every monitor_compaction_interval updates, for updates
+    // after the channel closed and for chain sync which comes without an update.
     fn update_persisted_channel(
         &self,
         funding_txo: OutPoint,
-        _update: &Option<ChannelMonitorUpdate>,
+        update: &Option<ChannelMonitorUpdate>,
         monitor: &ChannelMonitor<ChannelSigner>,
-        update_id: MonitorUpdateId, // only need this if persisting async.
+        update_id: MonitorUpdateId,
     ) -> ChannelMonitorUpdateStatus {
-        debug!(
-            "Updating persisted channel: {:?}:{}",
-            funding_txo,
-            monitor.get_latest_update_id()
-        );
-        self.persist_new_channel(funding_txo, monitor, update_id)
-
-        // Hope we can enable this soon. Probably after https://github.com/lightningdevkit/rust-lightning/issues/1426
-        /*
-                let mut out_point_buf = vec![];
-                funding_txo.write(&mut out_point_buf).unwrap();
-
-                // If its the last update then store the last monitor and delete the updates.
-                if update.as_ref().map_or(true, |x| x.update_id == CLOSED_CHANNEL_UPDATE_ID) {
-                    let mut monitor_buf = vec![];
-                    monitor.write(&mut monitor_buf).unwrap();
-                    let ciphertext = self.cipher.encrypt(&monitor_buf);
-
-                    tokio::task::block_in_place(move || {
-                        Handle::current().block_on(async move {
-                            let mut lock = self.client.write().await;
-                            let tx = lock.transaction().await.unwrap();
-                            tx.execute(
-                                "UPSERT INTO channel_monitors (out_point, monitor, update_id) VALUES ($1, $2, $3)",
-                                &[
-                                    &out_point_buf,
-                                    &ciphertext,
-                                    &to_i64!(monitor.get_latest_update_id()),
-                                ],
-                            )
-                            .await
-                            .unwrap();
-                            let deleted = tx
-                                .execute(
-                                    "DELETE FROM channel_monitor_updates WHERE out_point = $1",
-                                    &[&out_point_buf],
-                                )
-                                .await
-                                .unwrap();
-                            tx.commit().await.unwrap();
-                            debug!("Stored latest monitor and deleted {} updates.", deleted);
-                        })
-                    })
-                } else {
-                    let update = update.as_ref().unwrap();
-                    let mut update_buf = vec![];
-                    update.write(&mut update_buf).unwrap();
-                    let ciphertext = self.cipher.encrypt(&update_buf);
-
-                    block_in_place!(
-                        "UPSERT INTO channel_monitor_updates (out_point, update, update_id) \
-                        VALUES ($1, $2, $3)",
-                        &[&out_point_buf, &ciphertext, &to_i64!(update.update_id)],
-                        self
-                    );
-                }
-                ChannelMonitorUpdateStatus::Completed
-        */
+        match update {
+            Some(update)
+                if update.update_id != CLOSED_CHANNEL_UPDATE_ID
+                    && update.update_id % self.monitor_compaction_interval != 0 =>
+            {
+                debug!(
+                    "Persisting channel monitor update: {:?}:{}",
+                    funding_txo, update.update_id
+                );
+                let out_point = funding_txo.encode();
+                let update_id_column = to_i64!(update.update_id);
+                self.monitor_writer.queue(
+                    funding_txo,
+                    update_id,
+                    MonitorWrite::Update {
+                        update: self.cipher.encrypt(
+                            "channel_monitor_updates",
+                            &monitor_update_key(&out_point, update_id_column),
+                            &update.encode(),
+                        ),
+                        out_point,
+                        update_id: update_id_column,
+                        key_version: self.cipher.key_version(),
+                    },
+                );
+                ChannelMonitorUpdateStatus::InProgress
+            }
+            _ => self.persist_new_channel(funding_txo, monitor, update_id),
+        }
     }
 }
+
+pub(crate) fn encrypted_columns() -> [EncryptedColumn; 5] {
+    [
+        EncryptedColumn {
+            table: "channel_manager",
+            column: "manager",
+            primary_key: &[KeyColumn::Int("rowid")],
+            row_key: |_| SINGLETON_ROW.to_vec(),
+            plaintext: Plaintext::InPlace,
+        },
+        EncryptedColumn {
+            table: "channel_monitors",
+            column: "monitor",
+            primary_key: &[KeyColumn::Bytes("out_point")],
+            row_key: |row| row.get("out_point"),
+            plaintext: Plaintext::InPlace,
+        },
+        EncryptedColumn {
+            table: "channel_monitor_updates",
+            column: "update",
+            primary_key: &[KeyColumn::Bytes("out_point"), KeyColumn::Int("update_id")],
+            row_key: |row| monitor_update_key(row.get("out_point"), row.get("update_id")),
+            plaintext: Plaintext::InPlace,
+        },
+        EncryptedColumn {
+            table: "network_graph",
+            column: "graph",
+            primary_key: &[KeyColumn::Int("rowid")],
+            row_key: |_| SINGLETON_ROW.to_vec(),
+            plaintext: Plaintext::InPlace,
+        },
+        EncryptedColumn {
+            table: "scorer",
+            column: "scorer",
+            primary_key: &[KeyColumn::Int("rowid")],
+            row_key: |_| SINGLETON_ROW.to_vec(),
+            plaintext: Plaintext::InPlace,
+        },
+    ]
+}
+
+// The primary key of a channel_monitor_updates row, which its update is encrypted with.
+fn monitor_update_key(out_point: &[u8], update_id: i64) -> Vec<u8> {
+    let mut key = out_point.to_vec();
+    key.extend_from_slice(&update_id.to_be_bytes());
+    key
+}
+
+// Channel monitors that saw an update after the channel closed have the largest update_id.
+fn update_id_column(update_id: u64) -> i64 {
+    i64::try_from(update_id).unwrap_or(i64::MAX)
+}
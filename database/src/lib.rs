@@ -1,11 +1,26 @@
+pub mod cipher;
+pub mod forward;
+pub mod invoice;
 pub mod ldk_database;
+mod monitor_writer;
+pub mod payment;
 pub mod peer;
+mod tls;
 pub mod wallet_database;
 
+use std::time::Duration;
+
 use anyhow::Result;
+use cipher::{Cipher, PLAINTEXT};
+use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod, Runtime};
 use log::{error, info};
 use settings::Settings;
+use tls::tls_connector;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_postgres::config::SslMode;
+use tokio_postgres::types::ToSql;
 pub use tokio_postgres::{Client, NoTls, Transaction};
+use tokio_postgres::{Connection, Row};
 
 #[macro_export]
 macro_rules! to_i64 {
@@ -29,25 +44,197 @@ macro_rules! from_maybe_i64 {
     };
 }
 
-async fn connection(settings: &Settings) -> Result<Client> {
-    let mut params = format!(
-        "host={} port={} user={} dbname={}",
-        settings.database_host,
-        settings.database_port,
-        settings.database_user,
-        settings.database_name
-    );
-    info!("Creating database connection with params: {}", params);
+fn postgres_config(settings: &Settings) -> Result<tokio_postgres::Config> {
+    let mut config = tokio_postgres::Config::new();
+    config
+        .host(&settings.database_host)
+        .port(settings.database_port.parse()?)
+        .user(&settings.database_user)
+        .dbname(&settings.database_name)
+        .connect_timeout(Duration::from_secs(settings.database_connect_timeout_secs));
     if !settings.database_password.is_empty() {
-        params = format!("{} password={}", params, settings.database_password);
+        config.password(&settings.database_password);
     }
-    let (client, connection) = tokio_postgres::connect(&params, NoTls).await?;
+    if settings.database_ssl_mode == "disable" {
+        config.ssl_mode(SslMode::Disable);
+    } else {
+        config.ssl_mode(SslMode::Require);
+    }
+    Ok(config)
+}
+
+async fn connection(settings: &Settings) -> Result<Client> {
+    info!(
+        "Creating database connection to {} at {}:{}",
+        settings.database_name, settings.database_host, settings.database_port
+    );
+    let config = postgres_config(settings)?;
+    Ok(match tls_connector(settings)? {
+        Some(tls) => spawn_connection(config.connect(tls).await?),
+        None => spawn_connection(config.connect(NoTls).await?),
+    })
+}
+
+fn spawn_connection<S, T>((client, connection): (Client, Connection<S, T>)) -> Client
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
     tokio::spawn(async move {
         if let Err(e) = connection.await {
             error!("Database connection error: {}", e);
         }
     });
-    Ok(client)
+    client
+}
+
+// The LDK and wallet databases share one pool, so the node opens at most
+// `database_pool_size` connections. Connections are checked with a query before they are
+// handed out again and replaced when they broke, so the node keeps running while the
+// database restarts.
+pub fn pool(settings: &Settings) -> Result<Pool> {
+    info!(
+        "Creating database pool for {} at {}:{}",
+        settings.database_name, settings.database_host, settings.database_port
+    );
+    let config = postgres_config(settings)?;
+    let manager_config = ManagerConfig {
+        recycling_method: RecyclingMethod::Verified,
+    };
+    let manager = match tls_connector(settings)? {
+        Some(tls) => Manager::from_config(config, tls, manager_config),
+        None => Manager::from_config(config, NoTls, manager_config),
+    };
+    let connect_timeout = Duration::from_secs(settings.database_connect_timeout_secs);
+    Ok(Pool::builder(manager)
+        .max_size(settings.database_pool_size.try_into()?)
+        .wait_timeout(Some(Duration::from_secs(
+            settings.database_pool_timeout_secs,
+        )))
+        .create_timeout(Some(connect_timeout))
+        .recycle_timeout(Some(connect_timeout))
+        .runtime(Runtime::Tokio1)
+        .build()?)
+}
+
+/// A column that is encrypted with the primary key of its row, see Cipher::encrypt.
+pub(crate) struct EncryptedColumn {
+    pub table: &'static str,
+    pub column: &'static str,
+    /// Identifies the row to encrypt. Tables without a primary key use their hidden rowid.
+    pub primary_key: &'static [KeyColumn],
+    pub row_key: fn(&Row) -> Vec<u8>,
+    pub plaintext: Plaintext,
+}
+
+pub(crate) enum KeyColumn {
+    Bytes(&'static str),
+    Int(&'static str),
+    Text(&'static str),
+}
+
+impl KeyColumn {
+    fn name(&self) -> &'static str {
+        match self {
+            KeyColumn::Bytes(name) | KeyColumn::Int(name) | KeyColumn::Text(name) => name,
+        }
+    }
+
+    fn value(&self, row: &Row) -> Box<dyn ToSql + Sync> {
+        match self {
+            KeyColumn::Bytes(name) => Box::new(row.get::<_, Vec<u8>>(name)),
+            KeyColumn::Int(name) => Box::new(row.get::<_, i64>(name)),
+            KeyColumn::Text(name) => Box::new(row.get::<_, String>(name)),
+        }
+    }
+}
+
+/// Where rows written before encryption was added kept their plaintext.
+pub(crate) enum Plaintext {
+    /// In the encrypted column itself.
+    InPlace,
+    /// In these columns, which are cleared once the function's result is encrypted.
+    Columns(&'static [&'static str], fn(&Row) -> Result<Vec<u8>>),
+}
+
+/// Encrypts the rows written before encryption was added. This runs once, Cipher::decrypt
+/// rejects plaintext rows so a row inserted as plaintext later is never trusted.
+pub async fn encrypt_plaintext_rows(pool: &Pool, cipher: &Cipher) -> Result<()> {
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+    if transaction
+        .query_opt("SELECT true FROM plaintext_migration", &[])
+        .await?
+        .is_some()
+    {
+        return Ok(());
+    }
+    let columns = ldk_database::encrypted_columns()
+        .into_iter()
+        .chain(wallet_database::encrypted_columns());
+    for column in columns {
+        encrypt_column(&transaction, cipher, &column).await?;
+    }
+    transaction
+        .execute("INSERT INTO plaintext_migration (id) VALUES (0)", &[])
+        .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+async fn encrypt_column(
+    transaction: &Transaction<'_>,
+    cipher: &Cipher,
+    column: &EncryptedColumn,
+) -> Result<()> {
+    let key_names: Vec<&str> = column.primary_key.iter().map(KeyColumn::name).collect();
+    let rows = transaction
+        .query(
+            &format!(
+                "SELECT {}, * FROM {} WHERE key_version = $1",
+                key_names.join(", "),
+                column.table
+            ),
+            &[&PLAINTEXT],
+        )
+        .await?;
+    let mut assignments = vec![format!("{} = $1", column.column)];
+    assignments.push("key_version = $2".to_string());
+    if let Plaintext::Columns(cleared, _) = column.plaintext {
+        assignments.extend(cleared.iter().map(|name| format!("{} = NULL", name)));
+    }
+    let conditions: Vec<String> = key_names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| format!("{} = ${}", name, i + 3))
+        .collect();
+    let update = format!(
+        "UPDATE {} SET {} WHERE {}",
+        column.table,
+        assignments.join(", "),
+        conditions.join(" AND ")
+    );
+    let key_version = cipher.key_version();
+    for row in &rows {
+        let plaintext = match column.plaintext {
+            Plaintext::InPlace => row.get::<_, Option<Vec<u8>>>(column.column),
+            Plaintext::Columns(_, plaintext) => Some(plaintext(row)?),
+        };
+        let ciphertext = plaintext
+            .map(|plaintext| cipher.encrypt(column.table, &(column.row_key)(row), &plaintext));
+        let key_values: Vec<Box<dyn ToSql + Sync>> = column
+            .primary_key
+            .iter()
+            .map(|key| key.value(row))
+            .collect();
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&ciphertext, &key_version];
+        params.extend(key_values.iter().map(|value| value.as_ref()));
+        transaction.execute(&update, &params).await?;
+    }
+    if !rows.is_empty() {
+        info!("Encrypted {} rows in {}", rows.len(), column.table);
+    }
+    Ok(())
 }
 
 mod embedded {
@@ -67,7 +254,7 @@ pub async fn migrate_database(settings: &Settings) -> Result<()> {
             )
             .await?;
     }
-    let mut client = connection(&settings).await?;
+    let mut client = connection(settings).await?;
     info!("Running database migrations");
     embedded::migrations::runner()
         .run_async(&mut client)
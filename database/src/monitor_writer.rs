@@ -0,0 +1,203 @@
+//! Writes channel monitors on a background task so that LDK never waits for the database.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use deadpool_postgres::Pool;
+use lightning::chain::chainmonitor::MonitorUpdateId;
+use lightning::chain::transaction::OutPoint;
+use log::{debug, error, warn};
+use once_cell::sync::OnceCell;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::Notify;
+
+pub(crate) const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+pub(crate) const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+pub(crate) enum MonitorWrite {
+    /// The whole monitor, which replaces the updates it already contains.
+    Monitor {
+        out_point: Vec<u8>,
+        monitor: Vec<u8>,
+        update_id: i64,
+        key_version: i64,
+    },
+    Update {
+        out_point: Vec<u8>,
+        update: Vec<u8>,
+        update_id: i64,
+        key_version: i64,
+    },
+}
+
+struct QueuedWrite {
+    funding_txo: OutPoint,
+    update_id: MonitorUpdateId,
+    write: MonitorWrite,
+}
+
+type MonitorPersisted = Box<dyn Fn(OutPoint, MonitorUpdateId) + Send + Sync>;
+
+#[derive(Default)]
+struct Status {
+    pending: AtomicUsize,
+    failures: AtomicU64,
+    // Counted before the write is sent to the writer, so when `written` reaches a value read
+    // from `queued` every write queued before the read has committed.
+    queued: AtomicU64,
+    written: AtomicU64,
+    progress: Notify,
+    persisted: OnceCell<MonitorPersisted>,
+}
+
+/// Writes are done one at a time in the order they were queued, so a monitor is never
+/// overwritten by an older one. Failed writes are retried until they succeed.
+pub(crate) struct MonitorWriter {
+    sender: UnboundedSender<QueuedWrite>,
+    status: Arc<Status>,
+}
+
+impl MonitorWriter {
+    pub fn new(pool: Pool) -> MonitorWriter {
+        let (sender, receiver) = unbounded_channel();
+        let status = Arc::new(Status::default());
+        tokio::spawn(write_monitors(pool, status.clone(), receiver));
+        MonitorWriter { sender, status }
+    }
+
+    pub fn queue(&self, funding_txo: OutPoint, update_id: MonitorUpdateId, write: MonitorWrite) {
+        self.status.pending.fetch_add(1, Ordering::AcqRel);
+        self.status.queued.fetch_add(1, Ordering::AcqRel);
+        let queued = QueuedWrite {
+            funding_txo,
+            update_id,
+            write,
+        };
+        if self.sender.send(queued).is_err() {
+            // Only happens once the runtime shut down.
+            self.status.pending.fetch_sub(1, Ordering::AcqRel);
+            self.status.failures.fetch_add(1, Ordering::AcqRel);
+            error!(
+                "Dropped channel monitor write for {:?}, the writer stopped",
+                funding_txo
+            );
+        }
+    }
+
+    pub fn on_persisted(&self, callback: MonitorPersisted) {
+        if self.status.persisted.set(callback).is_err() {
+            warn!("Channel monitor persisted callback was already set");
+        }
+    }
+
+    pub fn pending(&self) -> usize {
+        self.status.pending.load(Ordering::Acquire)
+    }
+
+    pub fn failures(&self) -> u64 {
+        self.status.failures.load(Ordering::Acquire)
+    }
+
+    pub async fn wait_until_idle(&self) {
+        loop {
+            let progress = self.status.progress.notified();
+            if self.pending() == 0 {
+                return;
+            }
+            progress.await;
+        }
+    }
+
+    /// Waits for the writes queued before this call, but not for the ones queued while it
+    /// waits. Returns early if the writer stopped.
+    pub async fn flush(&self) {
+        let queued = self.status.queued.load(Ordering::Acquire);
+        loop {
+            let progress = self.status.progress.notified();
+            if self.status.written.load(Ordering::Acquire) >= queued || self.sender.is_closed() {
+                return;
+            }
+            progress.await;
+        }
+    }
+}
+
+async fn write_monitors(
+    pool: Pool,
+    status: Arc<Status>,
+    mut receiver: UnboundedReceiver<QueuedWrite>,
+) {
+    while let Some(queued) = receiver.recv().await {
+        let mut retry_delay = FIRST_RETRY_DELAY;
+        while let Err(e) = queued.write.execute(&pool).await {
+            status.failures.fetch_add(1, Ordering::AcqRel);
+            error!(
+                "Failed to persist channel monitor {:?}, retrying in {}s: {}",
+                queued.funding_txo,
+                retry_delay.as_secs(),
+                e
+            );
+            tokio::time::sleep(retry_delay).await;
+            retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+        }
+        if let Some(persisted) = status.persisted.get() {
+            persisted(queued.funding_txo, queued.update_id);
+        }
+        status.written.fetch_add(1, Ordering::AcqRel);
+        status.pending.fetch_sub(1, Ordering::AcqRel);
+        status.progress.notify_waiters();
+    }
+}
+
+impl MonitorWrite {
+    async fn execute(&self, pool: &Pool) -> Result<()> {
+        match self {
+            MonitorWrite::Monitor {
+                out_point,
+                monitor,
+                update_id,
+                key_version,
+            } => {
+                let mut client = pool.get().await?;
+                let tx = client.transaction().await?;
+                tx.execute(
+                    "UPSERT INTO channel_monitors (out_point, monitor, update_id, key_version) \
+                    VALUES ($1, $2, $3, $4)",
+                    &[out_point, monitor, update_id, key_version],
+                )
+                .await?;
+                let deleted = tx
+                    .execute(
+                        "DELETE FROM channel_monitor_updates \
+                        WHERE out_point = $1 AND update_id <= $2",
+                        &[out_point, update_id],
+                    )
+                    .await?;
+                tx.commit().await?;
+                debug!(
+                    "Stored channel monitor {} and deleted {} updates",
+                    update_id, deleted
+                );
+            }
+            MonitorWrite::Update {
+                out_point,
+                update,
+                update_id,
+                key_version,
+            } => {
+                pool.get()
+                    .await?
+                    .execute(
+                        "UPSERT INTO channel_monitor_updates \
+                        (out_point, update, update_id, key_version) \
+                        VALUES ($1, $2, $3, $4)",
+                        &[out_point, update, update_id, key_version],
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
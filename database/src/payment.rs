@@ -0,0 +1,86 @@
+use std::time::SystemTime;
+
+use anyhow::{bail, Result};
+use lightning::ln::{PaymentHash, PaymentPreimage, PaymentSecret};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaymentDirection {
+    Inbound,
+    Outbound,
+}
+
+impl PaymentDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentDirection::Inbound => "inbound",
+            PaymentDirection::Outbound => "outbound",
+        }
+    }
+
+    pub fn parse(direction: &str) -> Result<PaymentDirection> {
+        match direction {
+            "inbound" => Ok(PaymentDirection::Inbound),
+            "outbound" => Ok(PaymentDirection::Outbound),
+            _ => bail!("Unknown payment direction {}", direction),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaymentStatus {
+    Pending,
+    Succeeded,
+    Failed,
+}
+
+impl PaymentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentStatus::Pending => "pending",
+            PaymentStatus::Succeeded => "succeeded",
+            PaymentStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(status: &str) -> Result<PaymentStatus> {
+        match status {
+            "pending" => Ok(PaymentStatus::Pending),
+            "succeeded" => Ok(PaymentStatus::Succeeded),
+            "failed" => Ok(PaymentStatus::Failed),
+            _ => bail!("Unknown payment status {}", status),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Payment {
+    pub payment_hash: PaymentHash,
+    pub direction: PaymentDirection,
+    pub status: PaymentStatus,
+    pub preimage: Option<PaymentPreimage>,
+    // Not known for keysend payments.
+    pub secret: Option<PaymentSecret>,
+    pub amount_msat: Option<u64>,
+    pub fee_msat: Option<u64>,
+    pub timestamp: SystemTime,
+}
+
+impl Payment {
+    pub fn new(
+        payment_hash: PaymentHash,
+        direction: PaymentDirection,
+        status: PaymentStatus,
+        amount_msat: Option<u64>,
+    ) -> Payment {
+        Payment {
+            payment_hash,
+            direction,
+            status,
+            preimage: None,
+            secret: None,
+            amount_msat,
+            fee_msat: None,
+            timestamp: SystemTime::now(),
+        }
+    }
+}
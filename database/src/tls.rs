@@ -0,0 +1,162 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{
+    ring, verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms,
+};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{
+    CertificateError, ClientConfig, DigitallySignedStruct, Error, RootCertStore, SignatureScheme,
+};
+use settings::Settings;
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+/// None when the connection is not encrypted.
+pub(crate) fn tls_connector(settings: &Settings) -> Result<Option<MakeRustlsConnect>> {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+    let builder = match settings.database_ssl_mode.as_str() {
+        "disable" => return Ok(None),
+        "require" => builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(ServerVerifier {
+                webpki: None,
+                algorithms: provider.signature_verification_algorithms,
+            })),
+        "verify-ca" => builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(ServerVerifier {
+                webpki: Some(
+                    WebPkiServerVerifier::builder_with_provider(
+                        Arc::new(root_certs(settings)?),
+                        provider.clone(),
+                    )
+                    .build()?,
+                ),
+                algorithms: provider.signature_verification_algorithms,
+            })),
+        "verify-full" => builder.with_root_certificates(root_certs(settings)?),
+        ssl_mode => bail!("Unknown database ssl mode {}", ssl_mode),
+    };
+    let config = match (
+        &settings.database_client_cert_path,
+        &settings.database_client_key_path,
+    ) {
+        (Some(cert_path), Some(key_path)) => {
+            builder.with_client_auth_cert(read_certs(cert_path)?, read_key(key_path)?)?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => bail!("Database client certificate and key must be set together"),
+    };
+    Ok(Some(MakeRustlsConnect::new(config)))
+}
+
+fn root_certs(settings: &Settings) -> Result<RootCertStore> {
+    let path = settings.database_ca_cert_path.as_ref().ok_or_else(|| {
+        anyhow!(
+            "Database ssl mode {} needs a CA certificate",
+            settings.database_ssl_mode
+        )
+    })?;
+    let mut roots = RootCertStore::empty();
+    for cert in read_certs(path)? {
+        roots.add(cert)?;
+    }
+    Ok(roots)
+}
+
+fn read_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file)).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        bail!("No certificates in {}", path);
+    }
+    Ok(certs)
+}
+
+fn read_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))?
+        .ok_or_else(|| anyhow!("No private key in {}", path))
+}
+
+// For the ssl modes that don't check the whole certificate. The handshake signatures are
+// always checked.
+#[derive(Debug)]
+struct ServerVerifier {
+    // Checks the certificate chain but not the host name. None accepts any certificate.
+    webpki: Option<Arc<WebPkiServerVerifier>>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ServerCertVerifier for ServerVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let webpki = match &self.webpki {
+            Some(webpki) => webpki,
+            None => return Ok(ServerCertVerified::assertion()),
+        };
+        match webpki.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+        {
+            Err(Error::InvalidCertificate(
+                CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. },
+            )) => Ok(ServerCertVerified::assertion()),
+            result => result,
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+#[test]
+fn test_tls_connector() {
+    let certs_dir = format!("{}/../test-utils/certs", env!("CARGO_MANIFEST_DIR"));
+    let mut settings = test_utils::test_settings();
+    assert!(tls_connector(&settings).unwrap().is_none());
+
+    settings.database_ssl_mode = "require".to_string();
+    assert!(tls_connector(&settings).unwrap().is_some());
+
+    settings.database_ssl_mode = "verify-full".to_string();
+    assert!(tls_connector(&settings).is_err());
+    settings.database_ca_cert_path = Some(format!("{}/knd.crt", certs_dir));
+    assert!(tls_connector(&settings).unwrap().is_some());
+    settings.database_ssl_mode = "verify-ca".to_string();
+    assert!(tls_connector(&settings).unwrap().is_some());
+
+    settings.database_client_cert_path = Some(format!("{}/knd.crt", certs_dir));
+    assert!(tls_connector(&settings).is_err());
+    settings.database_client_key_path = Some(format!("{}/knd.key", certs_dir));
+    assert!(tls_connector(&settings).unwrap().is_some());
+}
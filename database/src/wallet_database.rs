@@ -1,6 +1,8 @@
+use std::ops::Deref;
 use std::sync::Arc;
 
-use crate::{connection, to_i64, Client};
+use crate::cipher::{Cipher, SINGLETON_ROW};
+use crate::{from_i64, from_maybe_i64, to_i64, EncryptedColumn, KeyColumn, Plaintext};
 use anyhow::Result;
 use bdk::{
     database::{BatchDatabase, BatchOperations, Database, SyncTime},
@@ -8,16 +10,18 @@ use bdk::{
 };
 use bitcoin::consensus::encode::{deserialize, serialize};
 use bitcoin::{OutPoint, Script, Transaction, TxOut, Txid};
-use log::info;
-use settings::Settings;
+use deadpool_postgres::{Object, Pool};
+use log::error;
 use tokio::runtime::Handle;
+use tokio_postgres::Row;
 
 macro_rules! execute_blocking {
     ($statement: literal, $params: expr, $self: expr) => {
         tokio::task::block_in_place(move || {
             Handle::current().block_on(async move {
                 $self
-                    .client
+                    .client()
+                    .await?
                     .execute($statement, $params)
                     .await
                     .map_err(|e| Error::Generic(e.to_string()))
@@ -31,7 +35,8 @@ macro_rules! query_blocking {
         tokio::task::block_in_place(move || {
             Handle::current().block_on(async move {
                 $self
-                    .client
+                    .client()
+                    .await?
                     .query($statement, $params)
                     .await
                     .map_err(|e| Error::Generic(e.to_string()))
@@ -40,21 +45,106 @@ macro_rules! query_blocking {
     };
 }
 
+// Every column that isn't looked up by value is encrypted. Script pubkeys stay plaintext, they
+// are looked up both by path and by script.
 #[derive(Clone)]
 pub struct WalletDatabase {
-    client: Arc<Client>,
+    pool: Pool,
+    // Only set for a batch, which runs in a transaction on a connection of its own.
+    batch: Option<Arc<BatchConnection>>,
+    cipher: Cipher,
+}
+
+// A connection is only given back to the pool once its transaction committed. Otherwise it is
+// closed, which rolls the transaction back.
+struct BatchConnection(Option<Object>);
+
+impl Drop for BatchConnection {
+    fn drop(&mut self) {
+        if let Some(client) = self.0.take() {
+            drop(Object::take(client));
+        }
+    }
+}
+
+enum Connection<'a> {
+    Batch(&'a Object),
+    Pooled(Object),
+}
+
+impl Deref for Connection<'_> {
+    type Target = Object;
+
+    fn deref(&self) -> &Object {
+        match self {
+            Connection::Batch(client) => client,
+            Connection::Pooled(client) => client,
+        }
+    }
 }
 
 impl WalletDatabase {
-    pub async fn new(settings: &Settings) -> Result<WalletDatabase> {
-        info!(
-            "Connecting wallet to Cockroach database at {}:{}",
-            settings.database_host, settings.database_port
-        );
-        let client = connection(settings).await?;
-        Ok(WalletDatabase {
-            client: Arc::new(client),
-        })
+    pub fn new(pool: Pool, cipher: Cipher) -> WalletDatabase {
+        WalletDatabase {
+            pool,
+            batch: None,
+            cipher,
+        }
+    }
+
+    async fn client(&self) -> Result<Connection<'_>, Error> {
+        match &self.batch {
+            Some(batch) => match &batch.0 {
+                Some(client) => Ok(Connection::Batch(client)),
+                None => Err(Error::Generic("Wallet batch failed to begin".to_string())),
+            },
+            None => Ok(Connection::Pooled(
+                self.pool
+                    .get()
+                    .await
+                    .map_err(|e| Error::Generic(e.to_string()))?,
+            )),
+        }
+    }
+
+    fn decrypt(
+        &self,
+        table: &str,
+        row_key: &[u8],
+        key_version: i64,
+        data: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        self.cipher
+            .decrypt(table, row_key, key_version, data)
+            .map_err(|e| Error::Generic(e.to_string()))
+    }
+
+    fn decrypt_raw_tx(
+        &self,
+        txid: &[u8],
+        key_version: i64,
+        raw_tx: &[u8],
+    ) -> Result<Transaction, Error> {
+        Ok(deserialize(&self.decrypt(
+            "wallet_transactions",
+            txid,
+            key_version,
+            raw_tx,
+        )?)?)
+    }
+
+    fn decrypt_details(
+        &self,
+        txid: &[u8],
+        key_version: i64,
+        details: &[u8],
+    ) -> Result<TransactionDetails, Error> {
+        Ok(serde_json::from_slice(&self.decrypt(
+            "wallet_transaction_details",
+            txid,
+            key_version,
+            details,
+        )?)?)
     }
 
     fn insert_script_pubkey(
@@ -71,127 +161,136 @@ impl WalletDatabase {
         .map(|_| 0)
     }
 
-    fn insert_utxo(
-        &self,
-        value: u64,
-        keychain: String,
-        vout: u32,
-        txid: &[u8],
-        script: &[u8],
-        is_spent: bool,
-    ) -> Result<i64, Error> {
+    fn insert_utxo(&self, utxo: &LocalUtxo) -> Result<i64, Error> {
+        let txid: &[u8] = &utxo.outpoint.txid;
+        let data = self.cipher.encrypt(
+            "wallet_utxos",
+            &utxo_key(txid, utxo.outpoint.vout),
+            &serde_json::to_vec(utxo)?,
+        );
         execute_blocking!(
-			"UPSERT INTO wallet_utxos (value, keychain, vout, txid, script, is_spent) VALUES ($1, $2, $3, $4, $5, $6)",
-			&[&to_i64!(value), &keychain, &to_i64!(vout), &txid, &script, &is_spent],
-			self
-		)
-		.map(|_| 0)
+            "UPSERT INTO wallet_utxos (txid, vout, utxo, key_version) VALUES ($1, $2, $3, $4)",
+            &[
+                &txid,
+                &to_i64!(utxo.outpoint.vout),
+                &data,
+                &self.cipher.key_version()
+            ],
+            self
+        )
+        .map(|_| 0)
     }
 
     fn insert_transaction(&self, txid: &[u8], raw_tx: &[u8]) -> Result<i64, Error> {
+        let raw_tx = self.cipher.encrypt("wallet_transactions", txid, raw_tx);
         execute_blocking!(
-            "INSERT INTO wallet_transactions (txid, raw_tx) VALUES ($1, $2)",
-            &[&txid, &raw_tx],
+            "INSERT INTO wallet_transactions (txid, raw_tx, key_version) VALUES ($1, $2, $3)",
+            &[&txid, &raw_tx, &self.cipher.key_version()],
             self
         )
         .map(|_| 0)
     }
 
     fn update_transaction(&self, txid: &[u8], raw_tx: &[u8]) -> Result<(), Error> {
+        let raw_tx = self.cipher.encrypt("wallet_transactions", txid, raw_tx);
         execute_blocking!(
-            "UPDATE wallet_transactions SET raw_tx=$1 WHERE txid=$2",
-            &[&txid, &raw_tx],
+            "UPDATE wallet_transactions SET raw_tx=$1, key_version=$2 WHERE txid=$3",
+            &[&raw_tx, &self.cipher.key_version(), &txid],
             self
         )
         .map(|_| ())
     }
 
-    fn insert_transaction_details(&self, transaction: &TransactionDetails) -> Result<i64, Error> {
-        let (timestamp, height) = match &transaction.confirmation_time {
-            Some(confirmation_time) => (
-                Some(confirmation_time.timestamp),
-                Some(confirmation_time.height),
-            ),
-            None => (None, None),
+    // The raw transaction is stored in wallet_transactions.
+    fn encrypt_details(&self, transaction: &TransactionDetails) -> Result<Vec<u8>, Error> {
+        let details = TransactionDetails {
+            transaction: None,
+            ..transaction.clone()
         };
+        Ok(self.cipher.encrypt(
+            "wallet_transaction_details",
+            &transaction.txid,
+            &serde_json::to_vec(&details)?,
+        ))
+    }
 
+    fn insert_transaction_details(&self, transaction: &TransactionDetails) -> Result<i64, Error> {
         let txid: &[u8] = &transaction.txid;
-
         execute_blocking!(
-			"INSERT INTO wallet_transaction_details (txid, timestamp, received, sent, fee, height) VALUES ($1, $2, $3, $4, $5, $6)",
-			&[
-				&txid,
-				&timestamp.map(|x| to_i64!(x)),
-				&to_i64!(transaction.received),
-				&to_i64!(transaction.sent),
-				&transaction.fee.map(|x| to_i64!(x)),
-				&height.map(|x| to_i64!(x))
-			],
-			self
-		)
-		.map(|_| 0)
+            "INSERT INTO wallet_transaction_details (txid, details, key_version) VALUES ($1, $2, $3)",
+            &[
+                &txid,
+                &self.encrypt_details(transaction)?,
+                &self.cipher.key_version()
+            ],
+            self
+        )
+        .map(|_| 0)
     }
 
     fn update_transaction_details(&self, transaction: &TransactionDetails) -> Result<(), Error> {
-        let (timestamp, height) = match &transaction.confirmation_time {
-            Some(confirmation_time) => (
-                Some(confirmation_time.timestamp),
-                Some(confirmation_time.height),
-            ),
-            None => (None, None),
-        };
-
         let txid: &[u8] = &transaction.txid;
-
         execute_blocking!(
-			"UPDATE wallet_transaction_details SET timestamp=$1, received=$2, sent=$3, fee=$4, height=$5 WHERE txid=$6",
-			&[
-				&timestamp.map(|x| to_i64!(x)),
-				&to_i64!(transaction.received),
-				&to_i64!(transaction.sent),
-				&transaction.fee.map(|x| to_i64!(x)),
-				&height.map(|x| to_i64!(x)),
-				&txid,
-			],
-			self
-		)
-		.map(|_| ())
+            "UPDATE wallet_transaction_details SET details=$1, key_version=$2 WHERE txid=$3",
+            &[
+                &self.encrypt_details(transaction)?,
+                &self.cipher.key_version(),
+                &txid
+            ],
+            self
+        )
+        .map(|_| ())
     }
 
     fn insert_last_derivation_index(&self, keychain: String, value: u32) -> Result<i64, Error> {
+        let last_index = self.cipher.encrypt(
+            "wallet_last_derivation_indices",
+            keychain.as_bytes(),
+            &serde_json::to_vec(&value)?,
+        );
         execute_blocking!(
-            "INSERT INTO wallet_last_derivation_indices (keychain, value) VALUES ($1, $2)",
-            &[&keychain, &to_i64!(value)],
+            "INSERT INTO wallet_last_derivation_indices (keychain, last_index, key_version) VALUES ($1, $2, $3)",
+            &[&keychain, &last_index, &self.cipher.key_version()],
             self
         )
         .map(|_| 0)
     }
 
     fn insert_checksum(&self, keychain: String, checksum: &[u8]) -> Result<i64, Error> {
+        let checksum = self
+            .cipher
+            .encrypt("wallet_checksums", keychain.as_bytes(), checksum);
         execute_blocking!(
-            "INSERT INTO wallet_checksums (keychain, checksum) VALUES ($1, $2)",
-            &[&keychain, &checksum],
+            "INSERT INTO wallet_checksums (keychain, checksum, key_version) VALUES ($1, $2, $3)",
+            &[&keychain, &checksum, &self.cipher.key_version()],
             self
         )
         .map(|_| 0)
     }
 
     fn update_last_derivation_index(&self, keychain: String, value: u32) -> Result<(), Error> {
+        let last_index = self.cipher.encrypt(
+            "wallet_last_derivation_indices",
+            keychain.as_bytes(),
+            &serde_json::to_vec(&value)?,
+        );
         execute_blocking!(
-            "UPSERT INTO wallet_last_derivation_indices (keychain, value) VALUES ($1, $2)",
-            &[&keychain, &to_i64!(value)],
+            "UPSERT INTO wallet_last_derivation_indices (keychain, last_index, key_version) VALUES ($1, $2, $3)",
+            &[&keychain, &last_index, &self.cipher.key_version()],
             self
         )
         .map(|_| ())
     }
 
     fn update_sync_time(&self, data: SyncTime) -> Result<i64, Error> {
+        let sync_time = self.cipher.encrypt(
+            "wallet_sync_time",
+            SINGLETON_ROW,
+            &serde_json::to_vec(&data)?,
+        );
         execute_blocking!(
-            "UPSERT INTO wallet_sync_time (id, height, timestamp) VALUES (0, $1, $2)",
-            &[
-                &to_i64!(data.block_time.height),
-                &to_i64!(data.block_time.timestamp)
-            ],
+            "UPSERT INTO wallet_sync_time (id, sync_time, key_version) VALUES (0, $1, $2)",
+            &[&sync_time, &self.cipher.key_version()],
             self
         )
         .map(|_| 0)
@@ -232,7 +331,7 @@ impl WalletDatabase {
             self
         )?;
 
-        match rows.get(0) {
+        match rows.first() {
             Some(row) => {
                 let script: Vec<u8> = row.get(0);
                 let script: Script = script.into();
@@ -251,7 +350,7 @@ impl WalletDatabase {
             &[&script],
             self
         )?;
-        match rows.get(0) {
+        match rows.first() {
             Some(row) => {
                 let keychain: String = row.get(0);
                 let keychain: KeychainKind = serde_json::from_str(&keychain)?;
@@ -264,168 +363,96 @@ impl WalletDatabase {
 
     fn select_utxos(&self) -> Result<Vec<LocalUtxo>, Error> {
         let rows = query_blocking!(
-            "SELECT value, keychain, vout, txid, script, is_spent FROM wallet_utxos",
+            "SELECT txid, vout, utxo, key_version FROM wallet_utxos",
             &[],
             self
         )?;
         let mut utxos: Vec<LocalUtxo> = vec![];
         for row in rows {
-            let value: u64 = row.get::<usize, i64>(0).try_into().unwrap();
-            let keychain: String = row.get(1);
-            let vout: u32 = row.get::<usize, i64>(2).try_into().unwrap();
-            let txid: Vec<u8> = row.get(3);
-            let script: Vec<u8> = row.get(4);
-            let is_spent: bool = row.get(5);
-
-            let keychain: KeychainKind = serde_json::from_str(&keychain)?;
-
-            utxos.push(LocalUtxo {
-                outpoint: OutPoint::new(deserialize(&txid)?, vout),
-                txout: TxOut {
-                    value,
-                    script_pubkey: script.into(),
-                },
-                keychain,
-                is_spent,
-            })
+            let txid: Vec<u8> = row.get(0);
+            let vout: u32 = row.get::<usize, i64>(1).try_into().unwrap();
+            let utxo: Vec<u8> = row.get(2);
+            let utxo = self.decrypt("wallet_utxos", &utxo_key(&txid, vout), row.get(3), &utxo)?;
+            utxos.push(serde_json::from_slice(&utxo)?);
         }
         Ok(utxos)
     }
 
     fn select_utxo_by_outpoint(&self, txid: &[u8], vout: u32) -> Result<Option<LocalUtxo>, Error> {
         let rows = query_blocking!(
-            "SELECT value, keychain, script, is_spent FROM wallet_utxos WHERE txid=$1 AND vout=$2",
+            "SELECT utxo, key_version FROM wallet_utxos WHERE txid=$1 AND vout=$2",
             &[&txid, &to_i64!(vout)],
             self
         )?;
-        match rows.get(0) {
+        match rows.first() {
             Some(row) => {
-                let value: u64 = row.get::<usize, i64>(0).try_into().unwrap();
-                let keychain: String = row.get(1);
-                let keychain: KeychainKind = serde_json::from_str(&keychain)?;
-                let script: Vec<u8> = row.get(2);
-                let script_pubkey: Script = script.into();
-                let is_spent: bool = row.get(3);
-
-                Ok(Some(LocalUtxo {
-                    outpoint: OutPoint::new(deserialize(txid)?, vout),
-                    txout: TxOut {
-                        value,
-                        script_pubkey,
-                    },
-                    keychain,
-                    is_spent,
-                }))
+                let utxo: Vec<u8> = row.get(0);
+                let utxo =
+                    self.decrypt("wallet_utxos", &utxo_key(txid, vout), row.get(1), &utxo)?;
+                Ok(Some(serde_json::from_slice(&utxo)?))
             }
             None => Ok(None),
         }
     }
 
     fn select_transactions(&self) -> Result<Vec<Transaction>, Error> {
-        let rows = query_blocking!("SELECT raw_tx FROM wallet_transactions", &[], self)?;
+        let rows = query_blocking!(
+            "SELECT raw_tx, key_version, txid FROM wallet_transactions",
+            &[],
+            self
+        )?;
         let mut txs: Vec<Transaction> = vec![];
         for row in rows {
             let raw_tx: Vec<u8> = row.get(0);
-            let tx: Transaction = deserialize(&raw_tx)?;
-            txs.push(tx);
+            let txid: Vec<u8> = row.get(2);
+            txs.push(self.decrypt_raw_tx(&txid, row.get(1), &raw_tx)?);
         }
         Ok(txs)
     }
 
     fn select_transaction_by_txid(&self, txid: &[u8]) -> Result<Option<Transaction>, Error> {
         let rows = query_blocking!(
-            "SELECT raw_tx FROM wallet_transactions WHERE txid=$1",
+            "SELECT raw_tx, key_version FROM wallet_transactions WHERE txid=$1",
             &[&txid],
             self
         )?;
-        match rows.get(0) {
+        match rows.first() {
             Some(row) => {
                 let raw_tx: Vec<u8> = row.get(0);
-                let tx: Transaction = deserialize(&raw_tx)?;
-                Ok(Some(tx))
+                Ok(Some(self.decrypt_raw_tx(txid, row.get(1), &raw_tx)?))
             }
             None => Ok(None),
         }
     }
 
     fn select_transaction_details_with_raw(&self) -> Result<Vec<TransactionDetails>, Error> {
-        let rows = query_blocking!("SELECT wtd.txid, wtd.timestamp, wtd.received, wtd.sent, wtd.fee, wtd.height, wt.raw_tx FROM wallet_transaction_details wtd, wallet_transactions wt WHERE wtd.txid = wt.txid", &[], self)?;
+        let rows = query_blocking!("SELECT wtd.txid, wtd.details, wtd.key_version, wt.raw_tx, wt.key_version FROM wallet_transaction_details wtd, wallet_transactions wt WHERE wtd.txid = wt.txid", &[], self)?;
         let mut transaction_details: Vec<TransactionDetails> = vec![];
         for row in rows {
             let txid: Vec<u8> = row.get(0);
-            let txid: Txid = deserialize(&txid)?;
-            let timestamp: Option<u64> = row
-                .get::<usize, Option<i64>>(1)
-                .map(|x| x.try_into().unwrap());
-            let received: u64 = row.get::<usize, i64>(2).try_into().unwrap();
-            let sent: u64 = row.get::<usize, i64>(3).try_into().unwrap();
-            let fee: Option<u64> = row
-                .get::<usize, Option<i64>>(4)
-                .map(|x| x.try_into().unwrap());
-            let height: Option<u32> = row
-                .get::<usize, Option<i64>>(5)
-                .map(|x| x.try_into().unwrap());
-            let raw_tx: Option<Vec<u8>> = row.get(6);
-            let tx: Option<Transaction> = match raw_tx {
-                Some(raw_tx) => {
-                    let tx: Transaction = deserialize(&raw_tx)?;
-                    Some(tx)
-                }
+            let details: Vec<u8> = row.get(1);
+            let mut details = self.decrypt_details(&txid, row.get(2), &details)?;
+            let raw_tx: Option<Vec<u8>> = row.get(3);
+            details.transaction = match raw_tx {
+                Some(raw_tx) => Some(self.decrypt_raw_tx(&txid, row.get(4), &raw_tx)?),
                 None => None,
             };
-
-            let confirmation_time = match (height, timestamp) {
-                (Some(height), Some(timestamp)) => Some(BlockTime { height, timestamp }),
-                _ => None,
-            };
-
-            transaction_details.push(TransactionDetails {
-                transaction: tx,
-                txid,
-                received,
-                sent,
-                fee,
-                confirmation_time,
-            });
+            transaction_details.push(details);
         }
         Ok(transaction_details)
     }
 
     fn select_transaction_details(&self) -> Result<Vec<TransactionDetails>, Error> {
         let rows = query_blocking!(
-            "SELECT txid, timestamp, received, sent, fee, height FROM wallet_transaction_details",
+            "SELECT txid, details, key_version FROM wallet_transaction_details",
             &[],
             self
         )?;
         let mut transaction_details: Vec<TransactionDetails> = vec![];
         for row in rows {
             let txid: Vec<u8> = row.get(0);
-            let txid: Txid = deserialize(&txid)?;
-            let timestamp: Option<u64> = row
-                .get::<usize, Option<i64>>(1)
-                .map(|x| x.try_into().unwrap());
-            let received: u64 = row.get::<usize, i64>(2).try_into().unwrap();
-            let sent: u64 = row.get::<usize, i64>(3).try_into().unwrap();
-            let fee: Option<u64> = row
-                .get::<usize, Option<i64>>(4)
-                .map(|x| x.try_into().unwrap());
-            let height: Option<u32> = row
-                .get::<usize, Option<i64>>(5)
-                .map(|x| x.try_into().unwrap());
-
-            let confirmation_time = match (height, timestamp) {
-                (Some(height), Some(timestamp)) => Some(BlockTime { height, timestamp }),
-                _ => None,
-            };
-
-            transaction_details.push(TransactionDetails {
-                transaction: None,
-                txid,
-                received,
-                sent,
-                fee,
-                confirmation_time,
-            });
+            let details: Vec<u8> = row.get(1);
+            transaction_details.push(self.decrypt_details(&txid, row.get(2), &details)?);
         }
         Ok(transaction_details)
     }
@@ -434,44 +461,18 @@ impl WalletDatabase {
         &self,
         txid: &[u8],
     ) -> Result<Option<TransactionDetails>, Error> {
-        let rows = query_blocking!("SELECT wtd.timestamp, wtd.received, wtd.sent, wtd.fee, wtd.height, wt.raw_tx FROM wallet_transaction_details wtd, wallet_transactions wt WHERE wtd.txid=wt.txid AND wtd.txid=$1", &[&txid], self)?;
+        let rows = query_blocking!("SELECT wtd.details, wtd.key_version, wt.raw_tx, wt.key_version FROM wallet_transaction_details wtd, wallet_transactions wt WHERE wtd.txid=wt.txid AND wtd.txid=$1", &[&txid], self)?;
 
-        match rows.get(0) {
+        match rows.first() {
             Some(row) => {
-                let timestamp: Option<u64> = row
-                    .get::<usize, Option<i64>>(0)
-                    .map(|x| x.try_into().unwrap());
-                let received: u64 = row.get::<usize, i64>(1).try_into().unwrap();
-                let sent: u64 = row.get::<usize, i64>(2).try_into().unwrap();
-                let fee: Option<u64> = row
-                    .get::<usize, Option<i64>>(3)
-                    .map(|x| x.try_into().unwrap());
-                let height: Option<u32> = row
-                    .get::<usize, Option<i64>>(4)
-                    .map(|x| x.try_into().unwrap());
-
-                let raw_tx: Option<Vec<u8>> = row.get(5);
-                let tx: Option<Transaction> = match raw_tx {
-                    Some(raw_tx) => {
-                        let tx: Transaction = deserialize(&raw_tx)?;
-                        Some(tx)
-                    }
+                let details: Vec<u8> = row.get(0);
+                let mut details = self.decrypt_details(txid, row.get(1), &details)?;
+                let raw_tx: Option<Vec<u8>> = row.get(2);
+                details.transaction = match raw_tx {
+                    Some(raw_tx) => Some(self.decrypt_raw_tx(txid, row.get(3), &raw_tx)?),
                     None => None,
                 };
-
-                let confirmation_time = match (height, timestamp) {
-                    (Some(height), Some(timestamp)) => Some(BlockTime { height, timestamp }),
-                    _ => None,
-                };
-
-                Ok(Some(TransactionDetails {
-                    transaction: tx,
-                    txid: deserialize(txid)?,
-                    received,
-                    sent,
-                    fee,
-                    confirmation_time,
-                }))
+                Ok(Some(details))
             }
             None => Ok(None),
         }
@@ -481,15 +482,22 @@ impl WalletDatabase {
         &self,
         keychain: String,
     ) -> Result<Option<u32>, Error> {
+        let keychain = keychain.as_str();
         let rows = query_blocking!(
-            "SELECT value FROM wallet_last_derivation_indices WHERE keychain=$1",
+            "SELECT last_index, key_version FROM wallet_last_derivation_indices WHERE keychain=$1",
             &[&keychain],
             self
         )?;
-        match rows.get(0) {
+        match rows.first() {
             Some(row) => {
-                let value: u32 = row.get::<usize, i64>(0).try_into().unwrap();
-                Ok(Some(value))
+                let last_index: Vec<u8> = row.get(0);
+                let last_index = self.decrypt(
+                    "wallet_last_derivation_indices",
+                    keychain.as_bytes(),
+                    row.get(1),
+                    &last_index,
+                )?;
+                Ok(Some(serde_json::from_slice(&last_index)?))
             }
             None => Ok(None),
         }
@@ -497,34 +505,38 @@ impl WalletDatabase {
 
     fn select_sync_time(&self) -> Result<Option<SyncTime>, Error> {
         let rows = query_blocking!(
-            "SELECT height, timestamp FROM wallet_sync_time WHERE id = 0",
+            "SELECT sync_time, key_version FROM wallet_sync_time WHERE id = 0",
             &[],
             self
         )?;
 
-        if let Some(row) = rows.get(0) {
-            Ok(Some(SyncTime {
-                block_time: BlockTime {
-                    height: row.get::<usize, i64>(0).try_into().unwrap(),
-                    timestamp: row.get::<usize, i64>(1).try_into().unwrap(),
-                },
-            }))
+        if let Some(row) = rows.first() {
+            let sync_time: Vec<u8> = row.get(0);
+            let sync_time =
+                self.decrypt("wallet_sync_time", SINGLETON_ROW, row.get(1), &sync_time)?;
+            Ok(Some(serde_json::from_slice(&sync_time)?))
         } else {
             Ok(None)
         }
     }
 
     fn select_checksum_by_keychain(&self, keychain: String) -> Result<Option<Vec<u8>>, Error> {
+        let keychain = keychain.as_str();
         let rows = query_blocking!(
-            "SELECT checksum FROM wallet_checksums WHERE keychain=$1",
+            "SELECT checksum, key_version FROM wallet_checksums WHERE keychain=$1",
             &[&keychain],
             self
         )?;
 
-        match rows.get(0) {
+        match rows.first() {
             Some(row) => {
                 let checksum: Vec<u8> = row.get(0);
-                Ok(Some(checksum))
+                Ok(Some(self.decrypt(
+                    "wallet_checksums",
+                    keychain.as_bytes(),
+                    row.get(1),
+                    &checksum,
+                )?))
             }
             None => Ok(None),
         }
@@ -602,14 +614,7 @@ impl BatchOperations for WalletDatabase {
     }
 
     fn set_utxo(&mut self, utxo: &LocalUtxo) -> Result<(), Error> {
-        self.insert_utxo(
-            utxo.txout.value,
-            serde_json::to_string(&utxo.keychain)?,
-            utxo.outpoint.vout,
-            &utxo.outpoint.txid,
-            utxo.txout.script_pubkey.as_bytes(),
-            utxo.is_spent,
-        )?;
+        self.insert_utxo(utxo)?;
         Ok(())
     }
 
@@ -864,23 +869,149 @@ impl Database for WalletDatabase {
 impl BatchDatabase for WalletDatabase {
     type Batch = WalletDatabase;
 
+    // BDK can't handle an error here, so a failure is reported by the batch's operations.
     fn begin_batch(&self) -> Self::Batch {
-        tokio::task::block_in_place(move || {
+        let client = tokio::task::block_in_place(move || {
             Handle::current().block_on(async move {
-                let database = WalletDatabase {
-                    client: self.client.clone(),
-                };
-                database.client.batch_execute("BEGIN").await.unwrap();
-                database
+                let client = self.pool.get().await?;
+                client.batch_execute("BEGIN").await?;
+                Ok::<_, anyhow::Error>(client)
             })
-        })
+        });
+        WalletDatabase {
+            pool: self.pool.clone(),
+            batch: Some(Arc::new(BatchConnection(
+                client
+                    .map_err(|e| error!("Failed to begin wallet batch: {}", e))
+                    .ok(),
+            ))),
+            cipher: self.cipher.clone(),
+        }
     }
 
     fn commit_batch(&mut self, batch: Self::Batch) -> Result<(), Error> {
+        let mut batch = batch
+            .batch
+            .and_then(|batch| Arc::try_unwrap(batch).ok())
+            .ok_or_else(|| Error::Generic("Not a wallet batch".to_string()))?;
+        let client = batch
+            .0
+            .take()
+            .ok_or_else(|| Error::Generic("Wallet batch failed to begin".to_string()))?;
         tokio::task::block_in_place(move || {
-            Handle::current()
-                .block_on(async move { batch.client.batch_execute("COMMIT").await.unwrap() });
-        });
-        Ok(())
+            Handle::current().block_on(async move {
+                match client.batch_execute("COMMIT").await {
+                    Ok(()) => Ok(()),
+                    Err(e) => {
+                        drop(Object::take(client));
+                        Err(Error::Generic(e.to_string()))
+                    }
+                }
+            })
+        })
     }
 }
+
+// The primary key of a wallet_utxos row, which its utxo is encrypted with.
+fn utxo_key(txid: &[u8], vout: u32) -> Vec<u8> {
+    let mut key = txid.to_vec();
+    key.extend_from_slice(&vout.to_be_bytes());
+    key
+}
+
+pub(crate) fn encrypted_columns() -> [EncryptedColumn; 6] {
+    [
+        EncryptedColumn {
+            table: "wallet_transactions",
+            column: "raw_tx",
+            primary_key: &[KeyColumn::Int("rowid")],
+            row_key: |row| row.get("txid"),
+            plaintext: Plaintext::InPlace,
+        },
+        EncryptedColumn {
+            table: "wallet_utxos",
+            column: "utxo",
+            primary_key: &[KeyColumn::Bytes("txid"), KeyColumn::Int("vout")],
+            row_key: |row| utxo_key(row.get("txid"), from_i64!(row, "vout")),
+            plaintext: Plaintext::Columns(
+                &["value", "keychain", "script", "is_spent"],
+                plaintext_utxo,
+            ),
+        },
+        EncryptedColumn {
+            table: "wallet_transaction_details",
+            column: "details",
+            primary_key: &[KeyColumn::Int("rowid")],
+            row_key: |row| row.get("txid"),
+            plaintext: Plaintext::Columns(
+                &["timestamp", "received", "sent", "fee", "height"],
+                plaintext_details,
+            ),
+        },
+        EncryptedColumn {
+            table: "wallet_last_derivation_indices",
+            column: "last_index",
+            primary_key: &[KeyColumn::Text("keychain")],
+            row_key: |row| row.get::<_, String>("keychain").into_bytes(),
+            plaintext: Plaintext::Columns(&["value"], |row| {
+                let value: u32 = from_i64!(row, "value");
+                Ok(serde_json::to_vec(&value)?)
+            }),
+        },
+        EncryptedColumn {
+            table: "wallet_checksums",
+            column: "checksum",
+            primary_key: &[KeyColumn::Int("rowid")],
+            row_key: |row| row.get::<_, String>("keychain").into_bytes(),
+            plaintext: Plaintext::InPlace,
+        },
+        EncryptedColumn {
+            table: "wallet_sync_time",
+            column: "sync_time",
+            primary_key: &[KeyColumn::Int("id")],
+            row_key: |_| SINGLETON_ROW.to_vec(),
+            plaintext: Plaintext::Columns(&["height", "timestamp"], |row| {
+                Ok(serde_json::to_vec(&SyncTime {
+                    block_time: BlockTime {
+                        height: from_i64!(row, "height"),
+                        timestamp: from_i64!(row, "timestamp"),
+                    },
+                })?)
+            }),
+        },
+    ]
+}
+
+fn plaintext_utxo(row: &Row) -> Result<Vec<u8>> {
+    let txid: Vec<u8> = row.get("txid");
+    let keychain: String = row.get("keychain");
+    let script: Vec<u8> = row.get("script");
+    let utxo = LocalUtxo {
+        outpoint: OutPoint::new(deserialize(&txid)?, from_i64!(row, "vout")),
+        txout: TxOut {
+            value: from_i64!(row, "value"),
+            script_pubkey: Script::from(script),
+        },
+        keychain: serde_json::from_str(&keychain)?,
+        is_spent: row.get("is_spent"),
+    };
+    Ok(serde_json::to_vec(&utxo)?)
+}
+
+fn plaintext_details(row: &Row) -> Result<Vec<u8>> {
+    let txid: Vec<u8> = row.get("txid");
+    let height: Option<u32> = from_maybe_i64!(row, "height");
+    let timestamp: Option<u64> = from_maybe_i64!(row, "timestamp");
+    let details = TransactionDetails {
+        transaction: None,
+        txid: deserialize(&txid)?,
+        received: from_i64!(row, "received"),
+        sent: from_i64!(row, "sent"),
+        fee: from_maybe_i64!(row, "fee"),
+        confirmation_time: match (height, timestamp) {
+            (Some(height), Some(timestamp)) => Some(BlockTime { height, timestamp }),
+            _ => None,
+        },
+    };
+    Ok(serde_json::to_vec(&details)?)
+}
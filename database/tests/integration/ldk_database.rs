@@ -1,41 +1,53 @@
 use std::net::{Ipv4Addr, SocketAddrV4};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 use std::vec;
 
 use bitcoin::blockdata::block::{Block, BlockHeader};
 use bitcoin::hashes::Hash;
 use bitcoin::{BlockHash, TxMerkleNode};
 use bitcoind::Client;
+use database::cipher::Cipher;
+use database::forward::Forward;
+use database::invoice::{Invoice, InvoiceStatus};
 use database::ldk_database::LdkDatabase;
-use database::migrate_database;
+use database::payment::{Payment, PaymentDirection, PaymentStatus};
 use database::peer::Peer;
-use lightning::chain::chainmonitor::ChainMonitor;
+use database::NoTls;
+use database::{encrypt_plaintext_rows, migrate_database, pool};
+use lightning::chain::chainmonitor::{ChainMonitor, MonitorUpdateId, Persist};
+use lightning::chain::channelmonitor::{ChannelMonitor, ChannelMonitorUpdate};
 use lightning::chain::keysinterface::{InMemorySigner, KeysManager};
-use lightning::chain::Filter;
+use lightning::chain::transaction::OutPoint;
+use lightning::chain::{ChannelMonitorUpdateStatus, Filter};
 use lightning::ln::{channelmanager, functional_test_utils::*};
+use lightning::ln::{PaymentHash, PaymentPreimage, PaymentSecret};
 use lightning::routing::gossip::NetworkGraph;
 use lightning::routing::scoring::{ProbabilisticScorer, ProbabilisticScoringParameters};
+use lightning::util::enforcing_trait_impls::EnforcingSigner;
 use lightning::util::events::{ClosureReason, MessageSendEventsProvider};
 use lightning::util::persist::Persister;
+use lightning::util::ser::Writeable;
 use lightning::util::test_utils as ln_utils;
 use lightning::{check_added_monitors, check_closed_broadcast, check_closed_event};
 use logger::KndLogger;
 use settings::Settings;
 use test_utils::random_public_key;
+use tokio::runtime::Handle;
 
-use crate::with_cockroach;
+use crate::{cipher, with_cockroach};
 
 async fn new_database(settings: &Settings, name: &str) -> LdkDatabase {
     let mut new_settings = settings.clone();
     new_settings.database_name = name.to_string();
     migrate_database(&new_settings).await.unwrap();
-    LdkDatabase::new(&new_settings).await.unwrap()
+    LdkDatabase::new(&new_settings, pool(&new_settings).unwrap(), cipher())
 }
 
 #[tokio::test(flavor = "multi_thread")]
 pub async fn test_peers() {
     with_cockroach(|settings| async move {
-        let database = LdkDatabase::new(settings).await.unwrap();
+        let database = LdkDatabase::new(settings, pool(settings).unwrap(), cipher());
 
         let peer = Peer {
             public_key: random_public_key(),
@@ -49,20 +61,217 @@ pub async fn test_peers() {
         let peers = database.fetch_peers().await.unwrap();
         assert!(peers.contains(&peer));
 
-        database.delete_peer(&peer).await;
+        database.delete_peer(&peer).await.unwrap();
         let peers = database.fetch_peers().await.unwrap();
         assert!(!peers.contains(&peer));
     })
     .await;
 }
 
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_invoices() {
+    with_cockroach(|settings| async move {
+        let database = LdkDatabase::new(settings, pool(settings).unwrap(), cipher());
+
+        let mut invoice = Invoice {
+            payment_hash: PaymentHash([1u8; 32]),
+            payment_secret: PaymentSecret([2u8; 32]),
+            bolt11: "lnbcrt1".to_string(),
+            amount_msat: Some(1000),
+            description: "test".to_string(),
+            expires_at: SystemTime::UNIX_EPOCH + Duration::from_secs(1000000),
+            status: InvoiceStatus::Unpaid,
+            preimage: None,
+            amount_received_msat: None,
+            paid_at: None,
+        };
+        database.persist_invoice(&invoice).await.unwrap();
+        assert!(database.fetch_invoices().await.unwrap().contains(&invoice));
+
+        invoice.status = InvoiceStatus::Paid;
+        invoice.preimage = Some(PaymentPreimage([3u8; 32]));
+        invoice.amount_received_msat = Some(1001);
+        invoice.paid_at = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(2000000));
+        database.persist_invoice(&invoice).await.unwrap();
+        assert_eq!(
+            Some(invoice.clone()),
+            database.fetch_invoice(&invoice.payment_hash).await.unwrap()
+        );
+        assert_eq!(
+            None,
+            database
+                .fetch_invoice(&PaymentHash([0u8; 32]))
+                .await
+                .unwrap()
+        );
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_payments() {
+    with_cockroach(|settings| async move {
+        let database = LdkDatabase::new(settings, pool(settings).unwrap(), cipher());
+
+        let mut outbound = Payment::new(
+            PaymentHash([1u8; 32]),
+            PaymentDirection::Outbound,
+            PaymentStatus::Pending,
+            Some(1000),
+        );
+        outbound.secret = Some(PaymentSecret([2u8; 32]));
+        outbound.timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(1000000);
+        database.persist_payment(&outbound).await.unwrap();
+
+        outbound.status = PaymentStatus::Succeeded;
+        outbound.preimage = Some(PaymentPreimage([3u8; 32]));
+        outbound.fee_msat = Some(10);
+        database.persist_payment(&outbound).await.unwrap();
+        assert_eq!(
+            Some(outbound.clone()),
+            database
+                .fetch_payment(&outbound.payment_hash, PaymentDirection::Outbound)
+                .await
+                .unwrap()
+        );
+
+        let mut inbound = Payment::new(
+            PaymentHash([4u8; 32]),
+            PaymentDirection::Inbound,
+            PaymentStatus::Succeeded,
+            Some(2000),
+        );
+        inbound.preimage = Some(PaymentPreimage([5u8; 32]));
+        inbound.timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(2000000);
+        database.persist_payment(&inbound).await.unwrap();
+
+        assert_eq!(
+            vec![inbound.clone(), outbound.clone()],
+            database.fetch_payments(None, None, None).await.unwrap()
+        );
+        assert_eq!(
+            vec![inbound.clone()],
+            database
+                .fetch_payments(None, Some(PaymentDirection::Inbound), None)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            vec![outbound.clone()],
+            database
+                .fetch_payments(Some(&outbound.payment_hash), None, None)
+                .await
+                .unwrap()
+        );
+        assert!(database
+            .fetch_payments(None, None, Some(PaymentStatus::Failed))
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            None,
+            database
+                .fetch_payment(&inbound.payment_hash, PaymentDirection::Outbound)
+                .await
+                .unwrap()
+        );
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_banned_nodes() {
+    with_cockroach(|settings| async move {
+        let database = LdkDatabase::new(settings, pool(settings).unwrap(), cipher());
+
+        let node_id = random_public_key();
+        database.persist_banned_node(&node_id).await.unwrap();
+        // Banning twice is fine.
+        database.persist_banned_node(&node_id).await.unwrap();
+        assert_eq!(vec![node_id], database.fetch_banned_nodes().await.unwrap());
+
+        database.delete_banned_node(&node_id).await.unwrap();
+        assert!(database.fetch_banned_nodes().await.unwrap().is_empty());
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_intercept_scids() {
+    with_cockroach(|settings| async move {
+        let database = LdkDatabase::new(settings, pool(settings).unwrap(), cipher());
+
+        let node_id = random_public_key();
+        database
+            .persist_intercept_scid(123456789, &node_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            Some(node_id),
+            database.fetch_intercept_scid(123456789).await.unwrap()
+        );
+        assert_eq!(
+            None,
+            database.fetch_intercept_scid(987654321).await.unwrap()
+        );
+        // Each scid is handed out once.
+        assert!(database
+            .persist_intercept_scid(123456789, &random_public_key())
+            .await
+            .is_err());
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_forwards() {
+    with_cockroach(|settings| async move {
+        let database = LdkDatabase::new(settings, pool(settings).unwrap(), cipher());
+
+        let mut first = Forward::new(Some([1u8; 32]), Some([2u8; 32]), Some(10), false);
+        first.amount_in_msat = Some(1010);
+        first.amount_out_msat = Some(1000);
+        first.timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(1000000);
+        database.persist_forward(&first).await.unwrap();
+
+        let mut second = Forward::new(Some([2u8; 32]), None, None, true);
+        second.timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(2000000);
+        database.persist_forward(&second).await.unwrap();
+
+        assert_eq!(
+            vec![second.clone(), first.clone()],
+            database.fetch_forwards(None, None).await.unwrap()
+        );
+        assert_eq!(
+            vec![second.clone()],
+            database
+                .fetch_forwards(Some(second.timestamp), None)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            vec![first.clone()],
+            database
+                .fetch_forwards(None, Some(second.timestamp))
+                .await
+                .unwrap()
+        );
+    })
+    .await;
+}
+
 // (Test copied from LDK FilesystemPersister).
 // Test relaying a few payments and check that the persisted data is updated the appropriate number of times.
 #[tokio::test(flavor = "multi_thread")]
 pub async fn test_channel_monitors() {
     with_cockroach(|settings| async move {
+        // Only the second database stores the full monitor every few updates.
         let database_0 = new_database(settings, "test1").await;
-        let database_1 = new_database(settings, "test2").await;
+        let mut compacting_settings = settings.clone();
+        compacting_settings.database_monitor_compaction_interval = 3;
+        let database_1 = new_database(&compacting_settings, "test2").await;
+        let persister_0 = WaitingPersister(&database_0);
+        let persister_1 = WaitingPersister(&database_1);
 
         // Create the nodes, giving them data databases.
         let chanmon_cfgs = create_chanmon_cfgs(2);
@@ -72,16 +281,16 @@ pub async fn test_channel_monitors() {
             &chanmon_cfgs[0].tx_broadcaster,
             &chanmon_cfgs[0].logger,
             &chanmon_cfgs[0].fee_estimator,
-            &database_0,
-            &node_cfgs[0].keys_manager,
+            &persister_0,
+            node_cfgs[0].keys_manager,
         );
         let chain_mon_1 = ln_utils::TestChainMonitor::new(
             Some(&chanmon_cfgs[1].chain_source),
             &chanmon_cfgs[1].tx_broadcaster,
             &chanmon_cfgs[1].logger,
             &chanmon_cfgs[1].fee_estimator,
-            &database_1,
-            &node_cfgs[1].keys_manager,
+            &persister_1,
+            node_cfgs[1].keys_manager,
         );
         node_cfgs[0].chain_monitor = chain_mon_0;
         node_cfgs[1].chain_monitor = chain_mon_1;
@@ -91,12 +300,20 @@ pub async fn test_channel_monitors() {
         // Check that the persisted channel data is empty before any channels are
         // open.
         let mut persisted_chan_data_0 = database_0
-            .fetch_channel_monitors(nodes[0].keys_manager)
+            .fetch_channel_monitors(
+                nodes[0].keys_manager,
+                &&chanmon_cfgs[0].tx_broadcaster,
+                &&chanmon_cfgs[0].fee_estimator,
+            )
             .await
             .unwrap();
         assert_eq!(persisted_chan_data_0.len(), 0);
         let mut persisted_chan_data_1 = database_1
-            .fetch_channel_monitors(nodes[0].keys_manager)
+            .fetch_channel_monitors(
+                nodes[0].keys_manager,
+                &&chanmon_cfgs[0].tx_broadcaster,
+                &&chanmon_cfgs[0].fee_estimator,
+            )
             .await
             .unwrap();
         assert_eq!(persisted_chan_data_1.len(), 0);
@@ -105,7 +322,11 @@ pub async fn test_channel_monitors() {
         macro_rules! check_persisted_data {
             ($expected_update_id: expr) => {
                 persisted_chan_data_0 = database_0
-                    .fetch_channel_monitors(nodes[0].keys_manager)
+                    .fetch_channel_monitors(
+                        nodes[0].keys_manager,
+                        &&chanmon_cfgs[0].tx_broadcaster,
+                        &&chanmon_cfgs[0].fee_estimator,
+                    )
                     .await
                     .unwrap();
                 assert_eq!(persisted_chan_data_0.len(), 1);
@@ -113,7 +334,11 @@ pub async fn test_channel_monitors() {
                     assert_eq!(mon.get_latest_update_id(), $expected_update_id);
                 }
                 persisted_chan_data_1 = database_1
-                    .fetch_channel_monitors(nodes[0].keys_manager)
+                    .fetch_channel_monitors(
+                        nodes[0].keys_manager,
+                        &&chanmon_cfgs[0].tx_broadcaster,
+                        &&chanmon_cfgs[0].fee_estimator,
+                    )
                     .await
                     .unwrap();
                 assert_eq!(persisted_chan_data_1.len(), 1);
@@ -152,7 +377,12 @@ pub async fn test_channel_monitors() {
         check_closed_broadcast!(nodes[0], true);
         check_added_monitors!(nodes[0], 1);
 
-        let node_txn = nodes[0].tx_broadcaster.txn_broadcasted.lock().unwrap();
+        let node_txn = nodes[0]
+            .tx_broadcaster
+            .txn_broadcasted
+            .lock()
+            .unwrap()
+            .clone();
         assert_eq!(node_txn.len(), 1);
 
         let header = BlockHeader {
@@ -183,7 +413,7 @@ pub async fn test_channel_monitors() {
 #[tokio::test(flavor = "multi_thread")]
 pub async fn test_network_graph() {
     with_cockroach(|settings| async move {
-        let database = LdkDatabase::new(settings).await.unwrap();
+        let database = LdkDatabase::new(settings, pool(settings).unwrap(), cipher());
 
         let network_graph = Arc::new(NetworkGraph::new(
             BlockHash::all_zeros(),
@@ -225,10 +455,150 @@ pub async fn test_network_graph() {
             .await
             .unwrap()
             .is_some());
+
+        database.delete_scorer().await.unwrap();
+        assert!(database
+            .fetch_scorer(
+                ProbabilisticScoringParameters::default(),
+                network_graph.clone()
+            )
+            .await
+            .unwrap()
+            .is_none());
     })
     .await;
 }
 
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_encryption_at_rest() {
+    with_cockroach(|settings| async move {
+        let mut new_settings = settings.clone();
+        new_settings.database_name = "test_encryption".to_string();
+        migrate_database(&new_settings).await.unwrap();
+        let (client, connection) = tokio_postgres::connect(
+            &format!(
+                "host={} port={} user={} dbname={}",
+                new_settings.database_host,
+                new_settings.database_port,
+                new_settings.database_user,
+                new_settings.database_name
+            ),
+            NoTls,
+        )
+        .await
+        .unwrap();
+        tokio::spawn(connection);
+
+        // Rows from before encryption was added get encrypted on startup.
+        let graph = NetworkGraph::new(BlockHash::all_zeros(), KndLogger::global()).encode();
+        client
+            .execute("INSERT INTO network_graph (graph) VALUES ($1)", &[&graph])
+            .await
+            .unwrap();
+        // Each row is encrypted with its own key, even when they hold the same blob.
+        for update_id in [1i64, 2] {
+            client
+                .execute(
+                    "INSERT INTO channel_monitor_updates (out_point, update, update_id) \
+                    VALUES ($1, $2, $3)",
+                    &[&b"out_point".to_vec(), &b"update".to_vec(), &update_id],
+                )
+                .await
+                .unwrap();
+        }
+        let pool = pool(&new_settings).unwrap();
+        encrypt_plaintext_rows(&pool, &cipher()).await.unwrap();
+        let database = LdkDatabase::new(&new_settings, pool.clone(), cipher());
+        let row = client
+            .query_one("SELECT graph, key_version FROM network_graph", &[])
+            .await
+            .unwrap();
+        assert_ne!(graph, row.get::<_, Vec<u8>>("graph"));
+        assert_eq!(cipher().key_version(), row.get::<_, i64>("key_version"));
+        assert!(database.fetch_graph().await.unwrap().is_some());
+        for row in client
+            .query(
+                "SELECT update, update_id, key_version FROM channel_monitor_updates",
+                &[],
+            )
+            .await
+            .unwrap()
+        {
+            let mut row_key = b"out_point".to_vec();
+            row_key.extend_from_slice(&row.get::<_, i64>("update_id").to_be_bytes());
+            let update: Vec<u8> = row.get("update");
+            assert_eq!(
+                b"update".to_vec(),
+                cipher()
+                    .decrypt(
+                        "channel_monitor_updates",
+                        &row_key,
+                        row.get("key_version"),
+                        &update
+                    )
+                    .unwrap()
+            );
+        }
+
+        let other_key = LdkDatabase::new(&new_settings, pool.clone(), Cipher::new(&[4u8; 32]));
+        assert!(other_key.fetch_graph().await.is_err());
+
+        // Plaintext written afterwards is neither encrypted nor read.
+        client
+            .execute(
+                "UPDATE network_graph SET graph = $1, key_version = 0",
+                &[&graph],
+            )
+            .await
+            .unwrap();
+        encrypt_plaintext_rows(&pool, &cipher()).await.unwrap();
+        let row = client
+            .query_one("SELECT key_version FROM network_graph", &[])
+            .await
+            .unwrap();
+        assert_eq!(0, row.get::<_, i64>("key_version"));
+        assert!(database.fetch_graph().await.is_err());
+    })
+    .await;
+}
+
+// LDK's test utils expect every monitor update to complete immediately.
+struct WaitingPersister<'a>(&'a LdkDatabase);
+
+impl WaitingPersister<'_> {
+    fn wait(&self, status: ChannelMonitorUpdateStatus) -> ChannelMonitorUpdateStatus {
+        assert_eq!(ChannelMonitorUpdateStatus::InProgress, status);
+        tokio::task::block_in_place(|| {
+            Handle::current().block_on(self.0.wait_for_monitor_writes())
+        });
+        ChannelMonitorUpdateStatus::Completed
+    }
+}
+
+impl Persist<EnforcingSigner> for WaitingPersister<'_> {
+    fn persist_new_channel(
+        &self,
+        funding_txo: OutPoint,
+        monitor: &ChannelMonitor<EnforcingSigner>,
+        update_id: MonitorUpdateId,
+    ) -> ChannelMonitorUpdateStatus {
+        self.wait(self.0.persist_new_channel(funding_txo, monitor, update_id))
+    }
+
+    fn update_persisted_channel(
+        &self,
+        funding_txo: OutPoint,
+        update: &Option<ChannelMonitorUpdate>,
+        monitor: &ChannelMonitor<EnforcingSigner>,
+        update_id: MonitorUpdateId,
+    ) -> ChannelMonitorUpdateStatus {
+        self.wait(
+            self.0
+                .update_persisted_channel(funding_txo, update, monitor, update_id),
+        )
+    }
+}
+
 type TestScorer = Mutex<ProbabilisticScorer<Arc<NetworkGraph<Arc<KndLogger>>>, Arc<KndLogger>>>;
 
 type KndTestChainMonitor = ChainMonitor<
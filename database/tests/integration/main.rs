@@ -3,6 +3,7 @@ use std::sync::atomic::AtomicU16;
 use std::sync::atomic::Ordering;
 use std::sync::Mutex;
 
+use database::cipher::Cipher;
 use database::migrate_database;
 use futures::Future;
 use futures::FutureExt;
@@ -51,6 +52,10 @@ async fn cockroach() -> &'static (Settings, Mutex<CockroachManager>) {
     })
 }
 
+pub fn cipher() -> Cipher {
+    Cipher::new(&[3u8; 32])
+}
+
 pub async fn teardown() {
     if COCKROACH_REF_COUNT.fetch_sub(1, Ordering::AcqRel) == 1 {
         let mut lock = cockroach().await.1.lock().unwrap();
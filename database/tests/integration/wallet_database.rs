@@ -1,17 +1,18 @@
 use std::str::FromStr;
 
-use crate::with_cockroach;
+use crate::{cipher, with_cockroach};
 use bdk::database::{BatchDatabase, BatchOperations, Database, SyncTime};
 use bdk::{BlockTime, KeychainKind, LocalUtxo, TransactionDetails};
 use bitcoin::consensus::encode::deserialize;
 use bitcoin::hashes::hex::*;
 use bitcoin::*;
 use database::wallet_database::WalletDatabase;
+use database::{encrypt_plaintext_rows, migrate_database, pool, NoTls};
 
 #[tokio::test(flavor = "multi_thread")]
 pub async fn test_script_pubkey() {
     with_cockroach(|settings| async move {
-        let mut wallet_database = WalletDatabase::new(&settings).await.unwrap();
+        let mut wallet_database = WalletDatabase::new(pool(settings).unwrap(), cipher());
         let script = Script::from(
             Vec::<u8>::from_hex("76a91402306a7c23f3e8010de41e9e591348bb83f11daa88ac").unwrap(),
         );
@@ -53,7 +54,7 @@ pub async fn test_script_pubkey() {
 #[tokio::test(flavor = "multi_thread")]
 pub async fn test_utxo() {
     with_cockroach(|settings| async move {
-        let mut wallet_database = WalletDatabase::new(&settings).await.unwrap();
+        let mut wallet_database = WalletDatabase::new(pool(settings).unwrap(), cipher());
         let outpoint = OutPoint::from_str(
             "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456:0",
         )
@@ -83,7 +84,7 @@ pub async fn test_utxo() {
 #[tokio::test(flavor = "multi_thread")]
 pub async fn test_raw_tx() {
     with_cockroach(|settings| async move {
-    let mut wallet_database = WalletDatabase::new(&settings).await.unwrap();
+    let mut wallet_database = WalletDatabase::new(pool(settings).unwrap(), cipher());
     let hex_tx = Vec::<u8>::from_hex("0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000").unwrap();
     let tx: Transaction = deserialize(&hex_tx).unwrap();
 
@@ -98,7 +99,7 @@ pub async fn test_raw_tx() {
 #[tokio::test(flavor = "multi_thread")]
 pub async fn test_tx() {
     with_cockroach(|settings| async move {
-    let mut wallet_database = WalletDatabase::new(&settings).await.unwrap();
+    let mut wallet_database = WalletDatabase::new(pool(settings).unwrap(), cipher());
     let hex_tx = Vec::<u8>::from_hex("0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000").unwrap();
     let tx: Transaction = deserialize(&hex_tx).unwrap();
     let txid = tx.txid();
@@ -139,7 +140,7 @@ pub async fn test_tx() {
 #[tokio::test(flavor = "multi_thread")]
 pub async fn test_last_index() {
     with_cockroach(|settings| async move {
-        let mut wallet_database = WalletDatabase::new(&settings).await.unwrap();
+        let mut wallet_database = WalletDatabase::new(pool(settings).unwrap(), cipher());
         wallet_database
             .set_last_index(KeychainKind::External, 1337)
             .unwrap();
@@ -185,7 +186,7 @@ pub async fn test_last_index() {
 #[tokio::test(flavor = "multi_thread")]
 pub async fn test_sync_time() {
     with_cockroach(|settings| async move {
-        let mut wallet_database = WalletDatabase::new(&settings).await.unwrap();
+        let mut wallet_database = WalletDatabase::new(pool(settings).unwrap(), cipher());
         assert!(wallet_database.get_sync_time().unwrap().is_none());
 
         wallet_database
@@ -207,3 +208,159 @@ pub async fn test_sync_time() {
     })
     .await;
 }
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_batch_connection() {
+    with_cockroach(|settings| async move {
+        let mut pool_settings = settings.clone();
+        pool_settings.database_pool_size = 1;
+        pool_settings.database_pool_timeout_secs = 1;
+        let wallet_database = WalletDatabase::new(pool(&pool_settings).unwrap(), cipher());
+        let script = Script::from(
+            Vec::<u8>::from_hex("76a914c2306a7c23f3e8010de41e9e591348bb83f11daa88ac").unwrap(),
+        );
+        let keychain = KeychainKind::Internal;
+        let mut batch = wallet_database.begin_batch();
+        batch.set_script_pubkey(&script, keychain, 4711).unwrap();
+
+        // The batch holds the only connection.
+        assert!(wallet_database
+            .get_script_pubkey_from_path(keychain, 4711)
+            .is_err());
+
+        // Dropping the batch rolls it back and frees the connection.
+        drop(batch);
+        assert_eq!(
+            wallet_database
+                .get_script_pubkey_from_path(keychain, 4711)
+                .unwrap(),
+            None
+        );
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_encryption_row_key() {
+    with_cockroach(|settings| async move {
+        let mut new_settings = settings.clone();
+        new_settings.database_name = "test_wallet_encryption".to_string();
+        migrate_database(&new_settings).await.unwrap();
+        let mut wallet_database = WalletDatabase::new(pool(&new_settings).unwrap(), cipher());
+        let hex_tx = Vec::<u8>::from_hex("0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000").unwrap();
+        let tx: Transaction = deserialize(&hex_tx).unwrap();
+        let mut other_tx = tx.clone();
+        other_tx.lock_time = PackedLockTime(1);
+        wallet_database.set_raw_tx(&tx).unwrap();
+        wallet_database.set_raw_tx(&other_tx).unwrap();
+
+        // A blob copied to another row does not decrypt.
+        let (client, connection) = tokio_postgres::connect(
+            &format!(
+                "host={} port={} user={} dbname={}",
+                new_settings.database_host,
+                new_settings.database_port,
+                new_settings.database_user,
+                new_settings.database_name
+            ),
+            NoTls,
+        )
+        .await
+        .unwrap();
+        tokio::spawn(connection);
+        client
+            .execute(
+                "UPDATE wallet_transactions SET raw_tx = \
+                (SELECT raw_tx FROM wallet_transactions WHERE txid = $1) WHERE txid = $2",
+                &[&tx.txid().to_vec(), &other_tx.txid().to_vec()],
+            )
+            .await
+            .unwrap();
+        assert_eq!(wallet_database.get_raw_tx(&tx.txid()).unwrap(), Some(tx));
+        assert!(wallet_database.get_raw_tx(&other_tx.txid()).is_err());
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_encrypt_plaintext_rows() {
+    with_cockroach(|settings| async move {
+        let mut new_settings = settings.clone();
+        new_settings.database_name = "test_wallet_plaintext".to_string();
+        migrate_database(&new_settings).await.unwrap();
+        let (client, connection) = tokio_postgres::connect(
+            &format!(
+                "host={} port={} user={} dbname={}",
+                new_settings.database_host,
+                new_settings.database_port,
+                new_settings.database_user,
+                new_settings.database_name
+            ),
+            NoTls,
+        )
+        .await
+        .unwrap();
+        tokio::spawn(connection);
+
+        // Rows as they were written before encryption was added.
+        let txid =
+            Txid::from_hex("e2dd8ad6ad9e9e7fc2fe3bcbc5fc27d60bcdf2ccf3b1d2f2fc0f9b17ec9a3c0d")
+                .unwrap();
+        let script = Script::from(
+            Vec::<u8>::from_hex("76a91402306a7c23f3e8010de41e9e591348bb83f11daa88ac").unwrap(),
+        );
+        client
+            .execute(
+                "INSERT INTO wallet_utxos (value, keychain, vout, txid, script, is_spent) \
+                VALUES (4711, $1, 1, $2, $3, false)",
+                &[
+                    &serde_json::to_string(&KeychainKind::External).unwrap(),
+                    &txid.to_vec(),
+                    &script.to_bytes(),
+                ],
+            )
+            .await
+            .unwrap();
+        client
+            .execute(
+                "INSERT INTO wallet_sync_time (id, height, timestamp) VALUES (0, 100, 1000)",
+                &[],
+            )
+            .await
+            .unwrap();
+
+        let pool = pool(&new_settings).unwrap();
+        encrypt_plaintext_rows(&pool, &cipher()).await.unwrap();
+        let wallet_database = WalletDatabase::new(pool, cipher());
+        let row = client
+            .query_one("SELECT value, script, key_version FROM wallet_utxos", &[])
+            .await
+            .unwrap();
+        assert_eq!(None, row.get::<_, Option<i64>>("value"));
+        assert_eq!(None, row.get::<_, Option<Vec<u8>>>("script"));
+        assert_eq!(cipher().key_version(), row.get::<_, i64>("key_version"));
+        assert_eq!(
+            wallet_database
+                .get_utxo(&OutPoint::new(txid, 1))
+                .unwrap()
+                .unwrap(),
+            LocalUtxo {
+                outpoint: OutPoint::new(txid, 1),
+                txout: TxOut {
+                    value: 4711,
+                    script_pubkey: script,
+                },
+                keychain: KeychainKind::External,
+                is_spent: false,
+            }
+        );
+        assert_eq!(
+            wallet_database.get_sync_time().unwrap().unwrap().block_time,
+            BlockTime {
+                height: 100,
+                timestamp: 1000
+            }
+        );
+    })
+    .await;
+}
@@ -1,3 +1,4 @@
+use bitcoin::secp256k1::PublicKey;
 use bitcoin::Network;
 use clap::Parser;
 
@@ -30,6 +31,97 @@ pub struct Settings {
     #[clap(long, default_value = "127.0.0.1:9234", env = "KND_LISTEN_ADDRESSES")]
     pub knd_listen_addresses: Vec<String>,
 
+    #[clap(long, default_value = "0", env = "KND_CHANNEL_MIN_SIZE_SATS")]
+    pub channel_min_size_sats: u64,
+    #[clap(long, env = "KND_CHANNEL_MAX_SIZE_SATS")]
+    pub channel_max_size_sats: Option<u64>,
+    /// Only accept inbound channels from these peers. Empty allows all peers.
+    #[clap(long, value_delimiter = ',', env = "KND_CHANNEL_PEER_ALLOWLIST")]
+    pub channel_peer_allowlist: Vec<PublicKey>,
+    #[clap(long, value_delimiter = ',', env = "KND_CHANNEL_PEER_DENYLIST")]
+    pub channel_peer_denylist: Vec<PublicKey>,
+    #[clap(long, default_value = "2", env = "KND_CHANNEL_MAX_PENDING_PER_PEER")]
+    pub channel_max_pending_per_peer: usize,
+    /// Peers trusted to open zero confirmation channels to us.
+    #[clap(long, value_delimiter = ',', env = "KND_CHANNEL_ZERO_CONF_PEERS")]
+    pub channel_zero_conf_peers: Vec<PublicKey>,
+
+    /// Intercept HTLCs to our intercept scids and open channels just in time to forward them.
+    /// The recipients must accept these channels with zero confirmations.
+    #[clap(long, env = "KND_LSP_MODE")]
+    pub lsp_mode: bool,
+    /// How long an intercepted HTLC waits for its just in time channel to become ready.
+    #[clap(long, default_value = "60", env = "KND_LSP_INTERCEPT_TIMEOUT_SECS")]
+    pub lsp_intercept_timeout_secs: u64,
+
+    /// Rapid Gossip Sync snapshots to bootstrap the network graph from. Either a file path or an
+    /// http(s) URL which the timestamp of the last applied snapshot is appended to.
+    #[clap(long, env = "KND_RGS_SNAPSHOT")]
+    pub rgs_snapshot: Option<String>,
+    #[clap(long, default_value = "3600", env = "KND_RGS_SYNC_INTERVAL_SECS")]
+    pub rgs_sync_interval_secs: u64,
+
+    /// Probe a well connected node this often to train the scorer. Disabled when not set.
+    #[clap(long, env = "KND_PROBE_INTERVAL_SECS")]
+    pub probe_interval_secs: Option<u64>,
+    #[clap(long, default_value = "50000000", env = "KND_PROBE_AMOUNT_MSAT")]
+    pub probe_amount_msat: u64,
+
+    // Parameters of the probabilistic scorer, see ProbabilisticScoringParameters in LDK.
+    #[clap(long, default_value = "500", env = "KND_SCORER_BASE_PENALTY_MSAT")]
+    pub scorer_base_penalty_msat: u64,
+    #[clap(
+        long,
+        default_value = "8192",
+        env = "KND_SCORER_BASE_PENALTY_AMOUNT_MULTIPLIER_MSAT"
+    )]
+    pub scorer_base_penalty_amount_multiplier_msat: u64,
+    #[clap(
+        long,
+        default_value = "30000",
+        env = "KND_SCORER_LIQUIDITY_PENALTY_MULTIPLIER_MSAT"
+    )]
+    pub scorer_liquidity_penalty_multiplier_msat: u64,
+    #[clap(
+        long,
+        default_value = "192",
+        env = "KND_SCORER_LIQUIDITY_PENALTY_AMOUNT_MULTIPLIER_MSAT"
+    )]
+    pub scorer_liquidity_penalty_amount_multiplier_msat: u64,
+    #[clap(
+        long,
+        default_value = "21600",
+        env = "KND_SCORER_LIQUIDITY_OFFSET_HALF_LIFE_SECS"
+    )]
+    pub scorer_liquidity_offset_half_life_secs: u64,
+    #[clap(
+        long,
+        default_value = "10000",
+        env = "KND_SCORER_HISTORICAL_LIQUIDITY_PENALTY_MULTIPLIER_MSAT"
+    )]
+    pub scorer_historical_liquidity_penalty_multiplier_msat: u64,
+    #[clap(
+        long,
+        default_value = "64",
+        env = "KND_SCORER_HISTORICAL_LIQUIDITY_PENALTY_AMOUNT_MULTIPLIER_MSAT"
+    )]
+    pub scorer_historical_liquidity_penalty_amount_multiplier_msat: u64,
+    #[clap(
+        long,
+        default_value = "1209600",
+        env = "KND_SCORER_HISTORICAL_NO_UPDATES_HALF_LIFE_SECS"
+    )]
+    pub scorer_historical_no_updates_half_life_secs: u64,
+    #[clap(
+        long,
+        default_value = "250",
+        env = "KND_SCORER_ANTI_PROBING_PENALTY_MSAT"
+    )]
+    pub scorer_anti_probing_penalty_msat: u64,
+    /// Never route through these nodes.
+    #[clap(long, value_delimiter = ',', env = "KND_SCORER_BANNED_NODES")]
+    pub scorer_banned_nodes: Vec<PublicKey>,
+
     #[clap(long, default_value = "127.0.0.1:2233", env = "KND_EXPORTER_ADDRESS")]
     pub exporter_address: String,
     #[clap(long, default_value = "127.0.0.1:2244", env = "KND_REST_API_ADDRESS")]
@@ -45,6 +137,44 @@ pub struct Settings {
     pub database_name: String,
     #[clap(long, default_value = "", env = "KND_DATABASE_PASSWORD")]
     pub database_password: String,
+    /// Store the whole channel monitor instead of the update after this many updates.
+    #[clap(
+        long,
+        default_value = "100",
+        value_parser = clap::value_parser!(u64).range(1..),
+        env = "KND_DATABASE_MONITOR_COMPACTION_INTERVAL"
+    )]
+    pub database_monitor_compaction_interval: u64,
+    /// Maximum number of connections to the database.
+    #[clap(
+        long,
+        default_value = "16",
+        value_parser = clap::value_parser!(u64).range(1..),
+        env = "KND_DATABASE_POOL_SIZE"
+    )]
+    pub database_pool_size: u64,
+    /// Timeout for connecting to the database and for the health check of a pooled connection.
+    #[clap(long, default_value = "10", env = "KND_DATABASE_CONNECT_TIMEOUT_SECS")]
+    pub database_connect_timeout_secs: u64,
+    /// How long to wait for a free connection when all of them are in use.
+    #[clap(long, default_value = "10", env = "KND_DATABASE_POOL_TIMEOUT_SECS")]
+    pub database_pool_timeout_secs: u64,
+    /// require encrypts the connection without checking the server certificate, verify-ca checks
+    /// that it was signed by the CA and verify-full also checks that it is for the host.
+    #[clap(
+        long,
+        default_value = "disable",
+        value_parser = ["disable", "require", "verify-ca", "verify-full"],
+        env = "KND_DATABASE_SSL_MODE"
+    )]
+    pub database_ssl_mode: String,
+    #[clap(long, env = "KND_DATABASE_CA_CERT_PATH")]
+    pub database_ca_cert_path: Option<String>,
+    /// Authenticate with a client certificate, e.g. client.root.crt and client.root.key.
+    #[clap(long, env = "KND_DATABASE_CLIENT_CERT_PATH")]
+    pub database_client_cert_path: Option<String>,
+    #[clap(long, env = "KND_DATABASE_CLIENT_KEY_PATH")]
+    pub database_client_key_path: Option<String>,
 }
 
 impl Settings {
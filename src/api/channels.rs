@@ -1,17 +1,42 @@
+use std::collections::HashMap;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
 
 use api::Channel;
+use api::ChannelClose;
+use api::ChannelCloseResponse;
+use api::ChannelDetail;
+use api::ChannelFee;
+use api::ChannelFees;
+use api::ChannelIds;
+use api::Forward;
+use api::ForwardTotals;
 use api::FundChannel;
 use api::FundChannelResponse;
+use api::ListForwards;
+use api::ListForwardsResponse;
+use api::SetChannelFee;
+use axum::extract::{Path, Query};
 use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
 use bitcoin::secp256k1::PublicKey;
+use bitcoin::{Address, OutPoint};
 use hex::ToHex;
+use lightning::ln::channelmanager::{ChannelDetails, MIN_CLTV_EXPIRY_DELTA};
+use lightning::ln::script::ShutdownScript;
+use lightning::util::ser::Writeable;
 use log::{info, warn};
 
 use crate::handle_auth_err;
+use crate::handle_bad_request;
 use crate::handle_err;
 use crate::to_string_empty;
 
+use super::parse_channel_id;
+use super::unix_secs;
+use super::ChannelConfigUpdate;
+use super::FeeRateSpec;
+use super::FundingOptions;
 use super::KndMacaroon;
 use super::LightningInterface;
 use super::MacaroonAuth;
@@ -26,43 +51,148 @@ pub(crate) async fn list_channels(
     let channels: Vec<Channel> = lightning_interface
         .list_channels()
         .iter()
-        .map(|c| Channel {
-            id: c.counterparty.node_id.to_string(),
-            connected: c.is_usable.to_string(),
-            state: (if c.is_usable {
-                "usable"
-            } else if c.is_channel_ready {
-                "ready"
-            } else {
-                "pending"
-            })
-            .to_string(),
-            short_channel_id: to_string_empty!(c.short_channel_id),
-            channel_id: c.channel_id.encode_hex(),
-            funding_txid: to_string_empty!(c.funding_txo.map(|x| x.txid)),
-            private: (!c.is_public).to_string(),
-            msatoshi_to_us: "".to_string(),
-            msatoshi_total: c.channel_value_satoshis.to_string(),
-            msatoshi_to_them: "".to_string(),
-            their_channel_reserve_satoshis: c
-                .counterparty
-                .unspendable_punishment_reserve
-                .to_string(),
-            our_channel_reserve_satoshis: to_string_empty!(c.unspendable_punishment_reserve),
-            spendable_msatoshi: c.outbound_capacity_msat.to_string(),
-            direction: if c.is_outbound { 1 } else { 0 },
-            alias: lightning_interface.get_node(c.counterparty.node_id).map_or(
-                "".to_string(),
-                |n| {
-                    n.announcement_info
-                        .map_or("".to_string(), |a| a.alias.to_string())
-                },
-            ),
-        })
+        .map(|c| to_api_channel(c, &lightning_interface))
         .collect();
     Ok(Json(channels))
 }
 
+pub(crate) async fn get_channel(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_readonly_macaroon(&macaroon.0))?;
+
+    let channel_id = parse_channel_id(&id)?;
+    let channel = lightning_interface
+        .list_channels()
+        .into_iter()
+        .find(|c| c.channel_id == channel_id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let response = ChannelDetail {
+        channel: to_api_channel(&channel, &lightning_interface),
+        fee_base_msat: channel.config.map(|c| c.forwarding_fee_base_msat),
+        fee_proportional_millionths: channel
+            .config
+            .map(|c| c.forwarding_fee_proportional_millionths),
+        cltv_expiry_delta: channel.config.map(|c| c.cltv_expiry_delta),
+        confirmations: channel.confirmations,
+        confirmations_required: channel.confirmations_required,
+        funding_outpoint: channel
+            .funding_txo
+            .map(|txo| format!("{}:{}", txo.txid, txo.index)),
+        features: hex::encode(&channel.counterparty.features.encode()[2..]),
+    };
+    Ok(Json(response))
+}
+
+fn to_api_channel(
+    c: &ChannelDetails,
+    lightning_interface: &Arc<dyn LightningInterface + Send + Sync>,
+) -> Channel {
+    Channel {
+        id: c.counterparty.node_id.to_string(),
+        connected: c.is_usable.to_string(),
+        state: (if c.is_usable {
+            "usable"
+        } else if c.is_channel_ready {
+            "ready"
+        } else {
+            "pending"
+        })
+        .to_string(),
+        short_channel_id: to_string_empty!(c.short_channel_id),
+        channel_id: c.channel_id.encode_hex(),
+        funding_txid: to_string_empty!(c.funding_txo.map(|x| x.txid)),
+        private: (!c.is_public).to_string(),
+        msatoshi_to_us: c.balance_msat.to_string(),
+        msatoshi_total: (c.channel_value_satoshis * 1000).to_string(),
+        // What they can send us plus the reserve they must keep, like the peer sees it.
+        msatoshi_to_them: (c.inbound_capacity_msat
+            + c.counterparty.unspendable_punishment_reserve * 1000)
+            .to_string(),
+        their_channel_reserve_satoshis: c.counterparty.unspendable_punishment_reserve.to_string(),
+        our_channel_reserve_satoshis: to_string_empty!(c.unspendable_punishment_reserve),
+        spendable_msatoshi: c.outbound_capacity_msat.to_string(),
+        receivable_msatoshi: c.inbound_capacity_msat.to_string(),
+        direction: if c.is_outbound { 1 } else { 0 },
+        alias: lightning_interface
+            .get_node(c.counterparty.node_id)
+            .map_or("".to_string(), |n| {
+                n.announcement_info
+                    .map_or("".to_string(), |a| a.alias.to_string())
+            }),
+    }
+}
+
+pub(crate) async fn list_forwards(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<ListForwards>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_readonly_macaroon(&macaroon.0))?;
+
+    let start = params
+        .start
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+    let end = params
+        .end
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+    let forwards = handle_err!(lightning_interface.list_forwards(start, end).await)?;
+
+    // A forward's fee counts for both of its channels.
+    let mut totals: HashMap<[u8; 32], ChannelFees> = HashMap::new();
+    for forward in &forwards {
+        let sides = [
+            (forward.prev_channel_id, true, forward.amount_in_msat),
+            (forward.next_channel_id, false, forward.amount_out_msat),
+        ];
+        for (channel_id, incoming, amount_msat) in sides {
+            if let Some(channel_id) = channel_id {
+                let total = totals.entry(channel_id).or_insert_with(|| ChannelFees {
+                    channel_id: channel_id.encode_hex(),
+                    incoming: ForwardTotals::default(),
+                    outgoing: ForwardTotals::default(),
+                });
+                let side = if incoming {
+                    &mut total.incoming
+                } else {
+                    &mut total.outgoing
+                };
+                side.forwards += 1;
+                side.amount_msat += amount_msat.unwrap_or_default();
+                side.fee_msat += forward.fee_earned_msat.unwrap_or_default();
+            }
+        }
+    }
+    let mut fee_totals: Vec<ChannelFees> = totals.into_values().collect();
+    fee_totals.sort_by(|a, b| {
+        let fees = |total: &ChannelFees| total.incoming.fee_msat + total.outgoing.fee_msat;
+        fees(b)
+            .cmp(&fees(a))
+            .then_with(|| a.channel_id.cmp(&b.channel_id))
+    });
+
+    let response = ListForwardsResponse {
+        forwards: forwards
+            .into_iter()
+            .map(|forward| Forward {
+                in_channel: forward.prev_channel_id.map(|id| id.encode_hex()),
+                out_channel: forward.next_channel_id.map(|id| id.encode_hex()),
+                amount_in_msat: forward.amount_in_msat,
+                amount_out_msat: forward.amount_out_msat,
+                fee_msat: forward.fee_earned_msat,
+                claim_from_onchain_tx: forward.claim_from_onchain_tx,
+                timestamp: unix_secs(forward.timestamp),
+            })
+            .collect(),
+        fee_totals,
+    };
+    Ok(Json(response))
+}
+
 pub(crate) async fn open_channel(
     macaroon: KndMacaroon,
     Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
@@ -75,10 +205,45 @@ pub(crate) async fn open_channel(
     let public_key = handle_err!(PublicKey::from_slice(&pub_key_bytes))?;
     let value = handle_err!(fund_channel.satoshis.parse())?;
     let push_msat = handle_err!(fund_channel.push_msat.parse())?;
+    let fee_rate = if fund_channel.fee_rate.is_empty() {
+        FeeRateSpec::default()
+    } else {
+        handle_bad_request!(FeeRateSpec::from_str(&fund_channel.fee_rate))?
+    };
+    let mut utxos = vec![];
+    for utxo in &fund_channel.utxos {
+        utxos.push(handle_bad_request!(OutPoint::from_str(utxo))?);
+    }
+    let funding = FundingOptions {
+        fee_rate,
+        utxos,
+        min_conf: fund_channel.min_conf.into(),
+    };
+    let announce = if fund_channel.announce.is_empty() {
+        true
+    } else {
+        handle_bad_request!(fund_channel.announce.parse())?
+    };
+    let close_to = if fund_channel.close_to.is_empty() {
+        None
+    } else {
+        let address = handle_bad_request!(Address::from_str(&fund_channel.close_to))?;
+        if address.network != lightning_interface.network() {
+            info!("Address {} is for a different network", address);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        match ShutdownScript::try_from(address.script_pubkey()) {
+            Ok(script) => Some(script),
+            Err(_) => {
+                info!("Address {} can not be used as a shutdown script", address);
+                return Err(StatusCode::BAD_REQUEST);
+            }
+        }
+    };
 
     let result = handle_err!(
         lightning_interface
-            .open_channel(public_key, value, push_msat, None)
+            .open_channel(public_key, value, push_msat, funding, announce, close_to)
             .await
     )?;
     let transaction = handle_err!(serde_json::to_string(&result.transaction))?;
@@ -89,3 +254,100 @@ pub(crate) async fn open_channel(
     };
     Ok(Json(response))
 }
+
+pub(crate) async fn set_channel_fee(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(set_channel_fee): Json<SetChannelFee>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_admin_macaroon(&macaroon.0))?;
+
+    let channel_ids = match set_channel_fee.id {
+        ChannelIds::One(id) if id == "all" => None,
+        ChannelIds::One(id) => Some(vec![parse_channel_id(&id)?]),
+        ChannelIds::Many(ids) => Some(
+            ids.iter()
+                .map(|id| parse_channel_id(id))
+                .collect::<Result<Vec<[u8; 32]>, StatusCode>>()?,
+        ),
+    };
+    if let Some(channel_ids) = &channel_ids {
+        let channels = lightning_interface.list_channels();
+        if !channel_ids
+            .iter()
+            .all(|id| channels.iter().any(|c| c.channel_id == *id))
+        {
+            return Err(StatusCode::NOT_FOUND);
+        }
+    }
+    if set_channel_fee
+        .cltv_expiry_delta
+        .is_some_and(|delta| delta < MIN_CLTV_EXPIRY_DELTA)
+    {
+        info!(
+            "cltv_expiry_delta must be at least {}",
+            MIN_CLTV_EXPIRY_DELTA
+        );
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let update = ChannelConfigUpdate {
+        forwarding_fee_base_msat: set_channel_fee.base,
+        forwarding_fee_proportional_millionths: set_channel_fee.ppm,
+        cltv_expiry_delta: set_channel_fee.cltv_expiry_delta,
+        max_dust_htlc_exposure_msat: set_channel_fee.max_dust_htlc_exposure_msat,
+    };
+
+    let applied = handle_err!(lightning_interface.set_channel_fee(channel_ids, update))?;
+    let response: Vec<ChannelFee> = applied
+        .into_iter()
+        .map(|(channel_id, config)| ChannelFee {
+            channel_id: channel_id.encode_hex(),
+            base: config.forwarding_fee_base_msat,
+            ppm: config.forwarding_fee_proportional_millionths,
+            cltv_expiry_delta: config.cltv_expiry_delta,
+            max_dust_htlc_exposure_msat: config.max_dust_htlc_exposure_msat,
+        })
+        .collect();
+    Ok(Json(response))
+}
+
+pub(crate) async fn close_channel(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(channel_close): Json<ChannelClose>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_admin_macaroon(&macaroon.0))?;
+
+    let channel_id = parse_channel_id(&channel_close.channel_id)?;
+
+    let result = handle_err!(
+        lightning_interface
+            .close_channel(&channel_id, channel_close.force)
+            .await
+    )?;
+    let tx = result
+        .transaction
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose();
+    let response = ChannelCloseResponse {
+        channel_id: result.channel_id.encode_hex(),
+        tx: handle_err!(tx)?,
+        txid: result.transaction.as_ref().map(|tx| tx.txid().to_string()),
+        close_type: if channel_close.force {
+            "unilateral"
+        } else {
+            "mutual"
+        }
+        .to_string(),
+        status: if result.transaction.is_some() {
+            "broadcast"
+        } else {
+            "pending"
+        }
+        .to_string(),
+    };
+    Ok(Json(response))
+}
@@ -0,0 +1,108 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Error, Result};
+use lightning::chain::chaininterface::ConfirmationTarget;
+
+/// Bitcoin Core's default minimum relay fee of 1 sat/vB. Funding and withdrawal transactions are
+/// on-chain transactions, so LDK's higher floor for commitment transactions doesn't apply.
+const MIN_RELAY_FEE_SAT_PER_KW: u32 = 250;
+
+/// The fee rate accepted by the REST API: urgent/normal/slow/<sats>perkw/<sats>perkb.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FeeRateSpec {
+    Target(ConfirmationTarget),
+    PerKw(u32),
+    PerKb(u32),
+}
+
+impl FromStr for FeeRateSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let spec = match s {
+            "urgent" => return Ok(FeeRateSpec::Target(ConfirmationTarget::HighPriority)),
+            "normal" => return Ok(FeeRateSpec::Target(ConfirmationTarget::Normal)),
+            "slow" => return Ok(FeeRateSpec::Target(ConfirmationTarget::Background)),
+            _ => {
+                if let Some(sats) = s.strip_suffix("perkw") {
+                    FeeRateSpec::PerKw(sats.parse()?)
+                } else if let Some(sats) = s.strip_suffix("perkb") {
+                    FeeRateSpec::PerKb(sats.parse()?)
+                } else {
+                    return Err(anyhow!(
+                        "Unknown fee rate {}, expected urgent/normal/slow/<sats>perkw/<sats>perkb",
+                        s
+                    ));
+                }
+            }
+        };
+        // Compared in the requested unit, a rate that rounds up to the minimum is still below it.
+        let below_minimum = match spec {
+            FeeRateSpec::Target(_) => false,
+            FeeRateSpec::PerKw(sats) => sats < MIN_RELAY_FEE_SAT_PER_KW,
+            FeeRateSpec::PerKb(sats) => sats < MIN_RELAY_FEE_SAT_PER_KW * 4,
+        };
+        if below_minimum {
+            bail!(
+                "Fee rate {} is below the minimum relay fee of {}perkw",
+                s,
+                MIN_RELAY_FEE_SAT_PER_KW
+            );
+        }
+        Ok(spec)
+    }
+}
+
+impl Default for FeeRateSpec {
+    fn default() -> Self {
+        FeeRateSpec::Target(ConfirmationTarget::Normal)
+    }
+}
+
+impl FeeRateSpec {
+    /// The explicit rate in sats per 1000 weight units, None when it needs to be estimated.
+    pub fn sat_per_kw(&self) -> Option<u32> {
+        match self {
+            FeeRateSpec::Target(_) => None,
+            FeeRateSpec::PerKw(sats) => Some(*sats),
+            // A virtual byte is four weight units. Rounded up so the rate is never lower.
+            FeeRateSpec::PerKb(sats) => Some(sats.div_ceil(4)),
+        }
+    }
+}
+
+#[test]
+fn test_fee_rate_spec() {
+    assert!(matches!(
+        FeeRateSpec::from_str("urgent").unwrap(),
+        FeeRateSpec::Target(ConfirmationTarget::HighPriority)
+    ));
+    assert!(matches!(
+        FeeRateSpec::from_str("slow").unwrap(),
+        FeeRateSpec::Target(ConfirmationTarget::Background)
+    ));
+    assert_eq!(
+        Some(1000),
+        FeeRateSpec::from_str("1000perkw").unwrap().sat_per_kw()
+    );
+    assert_eq!(
+        Some(1000),
+        FeeRateSpec::from_str("4000perkb").unwrap().sat_per_kw()
+    );
+    assert_eq!(
+        Some(250),
+        FeeRateSpec::from_str("1000perkb").unwrap().sat_per_kw()
+    );
+    assert_eq!(
+        Some(253),
+        FeeRateSpec::from_str("1010perkb").unwrap().sat_per_kw()
+    );
+    assert_eq!(
+        Some(250),
+        FeeRateSpec::from_str("250perkw").unwrap().sat_per_kw()
+    );
+    assert!(FeeRateSpec::from_str("249perkw").is_err());
+    assert!(FeeRateSpec::from_str("999perkb").is_err());
+    assert!(FeeRateSpec::from_str("fast").is_err());
+    assert!(FeeRateSpec::from_str("-1perkw").is_err());
+}
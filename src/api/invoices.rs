@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use api::{GenerateInvoice, GenerateInvoiceResponse, Invoice, ListInvoices};
+use axum::extract::Query;
+use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
+use hex::ToHex;
+use log::{info, warn};
+
+use crate::handle_auth_err;
+use crate::handle_err;
+
+use super::KndMacaroon;
+use super::LightningInterface;
+use super::MacaroonAuth;
+use super::{parse_payment_hash, unix_secs};
+
+const DEFAULT_EXPIRY_SECS: u32 = 3600;
+
+pub(crate) async fn generate_invoice(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(generate_invoice): Json<GenerateInvoice>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_admin_macaroon(&macaroon.0))?;
+
+    let invoice = handle_err!(
+        lightning_interface
+            .create_invoice(
+                generate_invoice.amount,
+                generate_invoice.description,
+                generate_invoice.expiry.unwrap_or(DEFAULT_EXPIRY_SECS),
+            )
+            .await
+    )?;
+    let response = GenerateInvoiceResponse {
+        payment_hash: invoice.payment_hash.0.encode_hex(),
+        expires_at: unix_secs(invoice.expires_at),
+        bolt11: invoice.bolt11,
+    };
+    Ok(Json(response))
+}
+
+pub(crate) async fn list_invoices(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<ListInvoices>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_readonly_macaroon(&macaroon.0))?;
+
+    let payment_hash = match params.payment_hash {
+        Some(hash) => Some(parse_payment_hash(&hash)?),
+        None => None,
+    };
+    let invoices: Vec<Invoice> =
+        handle_err!(lightning_interface.list_invoices(payment_hash).await)?
+            .into_iter()
+            .map(|invoice| Invoice {
+                payment_hash: invoice.payment_hash.0.encode_hex(),
+                status: if invoice.is_expired() {
+                    "expired"
+                } else {
+                    invoice.status.as_str()
+                }
+                .to_string(),
+                bolt11: invoice.bolt11,
+                msatoshi: invoice.amount_msat,
+                description: invoice.description,
+                expires_at: unix_secs(invoice.expires_at),
+                msatoshi_received: invoice.amount_received_msat,
+                paid_at: invoice.paid_at.map(unix_secs),
+                payment_preimage: invoice.preimage.map(|p| p.0.encode_hex()),
+            })
+            .collect();
+    Ok(Json(invoices))
+}
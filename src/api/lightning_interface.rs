@@ -1,7 +1,25 @@
+use std::net::SocketAddr;
+use std::time::SystemTime;
+
 use anyhow::Result;
 use async_trait::async_trait;
-use bitcoin::{secp256k1::PublicKey, Network, Transaction, Txid};
-use lightning::{ln::channelmanager::ChannelDetails, routing::gossip, util::config::UserConfig};
+use bitcoin::{secp256k1::PublicKey, Network, OutPoint, Transaction, Txid};
+use database::forward::Forward;
+use database::invoice::Invoice;
+
+use super::FeeRateSpec;
+use database::payment::{Payment, PaymentDirection, PaymentStatus};
+use lightning::{
+    ln::{
+        channelmanager::ChannelDetails, features::NodeFeatures, script::ShutdownScript,
+        PaymentHash, PaymentPreimage,
+    },
+    routing::{
+        gossip::{self, NodeId},
+        router::{PaymentParameters, Route, RouteHintHop, RouteHop},
+    },
+    util::config::ChannelConfig,
+};
 
 #[async_trait]
 pub trait LightningInterface {
@@ -13,6 +31,9 @@ pub trait LightningInterface {
 
     fn network(&self) -> Network;
 
+    /// Our view of the chain has caught up with the headers bitcoind knows about.
+    fn synced_to_chain(&self) -> bool;
+
     fn num_active_channels(&self) -> usize;
 
     fn num_inactive_channels(&self) -> usize;
@@ -27,19 +48,172 @@ pub trait LightningInterface {
 
     fn wallet_balance(&self) -> u64;
 
+    /// Channel monitor writes that have not been committed yet.
+    fn pending_monitor_writes(&self) -> usize;
+
+    /// Failed attempts to write a channel monitor since startup.
+    fn failed_monitor_writes(&self) -> u64;
+
     fn version(&self) -> String;
 
     fn list_channels(&self) -> Vec<ChannelDetails>;
 
     fn get_node(&self, node_id: PublicKey) -> Option<gossip::NodeInfo>;
 
+    /// Up to `limit` nodes from the network graph ordered by id, starting after `after`.
+    fn graph_nodes(&self, after: Option<NodeId>, limit: usize) -> Vec<(NodeId, gossip::NodeInfo)>;
+
+    /// Up to `limit` channels from the network graph ordered by short channel id, starting after `after`.
+    fn graph_channels(&self, after: Option<u64>, limit: usize) -> Vec<(u64, gossip::ChannelInfo)>;
+
+    fn graph_channel(&self, short_channel_id: u64) -> Option<gossip::ChannelInfo>;
+
+    /// Find a route with our scorer as if we were about to pay the node.
+    fn find_route(
+        &self,
+        payee: PublicKey,
+        amount_msat: u64,
+        final_cltv_expiry_delta: u32,
+    ) -> Result<Route>;
+
+    async fn connect_peer(&self, public_key: PublicKey, peer_address: SocketAddr) -> Result<()>;
+
+    /// Connected peers and those we have an address stored for.
+    async fn list_peers(&self) -> Result<Vec<Peer>>;
+
+    /// Forgetting the peer stops us reconnecting to it if we have channels together.
+    async fn disconnect_peer(&self, public_key: PublicKey, forget: bool) -> Result<()>;
+
+    /// With `close_to` the channel commits upfront to paying us there when it closes.
     async fn open_channel(
         &self,
         their_network_key: PublicKey,
         channel_value_satoshis: u64,
         push_msat: u64,
-        override_config: Option<UserConfig>,
+        funding: FundingOptions,
+        announce: bool,
+        close_to: Option<ShutdownScript>,
     ) -> Result<OpenChannelResult>;
+
+    /// Update the forwarding policy of the given channels, or all of them when None.
+    /// Returns the config now applied to each channel.
+    fn set_channel_fee(
+        &self,
+        channel_ids: Option<Vec<[u8; 32]>>,
+        update: ChannelConfigUpdate,
+    ) -> Result<Vec<([u8; 32], ChannelConfig)>>;
+
+    async fn close_channel(&self, channel_id: &[u8; 32], force: bool)
+        -> Result<CloseChannelResult>;
+
+    /// Newest first, from `start` (inclusive) to `end` (exclusive).
+    async fn list_forwards(
+        &self,
+        start: Option<SystemTime>,
+        end: Option<SystemTime>,
+    ) -> Result<Vec<Forward>>;
+
+    async fn create_invoice(
+        &self,
+        amount_msat: Option<u64>,
+        description: String,
+        expiry_secs: u32,
+    ) -> Result<Invoice>;
+
+    async fn list_invoices(&self, payment_hash: Option<PaymentHash>) -> Result<Vec<Invoice>>;
+
+    /// Pay a BOLT11 invoice and wait until the payment succeeds or we give up retrying.
+    /// The amount is only required for invoices without one.
+    async fn pay_invoice(
+        &self,
+        invoice: lightning_invoice::Invoice,
+        amount_msat: Option<u64>,
+    ) -> Result<PaymentOutcome>;
+
+    /// Newest first. Each filter is ignored when None.
+    async fn list_payments(
+        &self,
+        payment_hash: Option<PaymentHash>,
+        direction: Option<PaymentDirection>,
+        status: Option<PaymentStatus>,
+    ) -> Result<Vec<Payment>>;
+
+    /// Send a spontaneous payment to a node and wait for the outcome like `pay_invoice`.
+    async fn keysend(
+        &self,
+        payee_pubkey: PublicKey,
+        amount_msat: u64,
+    ) -> Result<(PaymentHash, PaymentOutcome)>;
+
+    /// Send a probe along a single path to the payee and wait for it to come back.
+    async fn probe(
+        &self,
+        payment_params: PaymentParameters,
+        amount_msat: u64,
+        final_cltv_expiry_delta: u32,
+    ) -> Result<ProbeOutcome>;
+
+    /// The scorer's estimate of the liquidity over the channel towards `target`, if it has
+    /// learned anything about it.
+    fn estimated_channel_liquidity_range(
+        &self,
+        short_channel_id: u64,
+        target: &NodeId,
+    ) -> Option<(u64, u64)>;
+
+    /// Avoid the node when routing, until it is unbanned.
+    async fn ban_node(&self, node_id: PublicKey) -> Result<()>;
+
+    async fn unban_node(&self, node_id: PublicKey) -> Result<()>;
+
+    /// Forget everything the scorer has learned. Banned nodes stay banned.
+    async fn reset_scorer(&self) -> Result<()>;
+
+    /// Hand out a short channel id for the node to put in its invoice route hints. HTLCs sent
+    /// to it are intercepted and forwarded over a channel opened just in time. Needs LSP mode.
+    async fn create_intercept_scid(&self, node_id: PublicKey) -> Result<RouteHintHop>;
+}
+
+/// How to build the funding transaction of a channel we open.
+#[derive(Clone, Default)]
+pub struct FundingOptions {
+    pub fee_rate: FeeRateSpec,
+    /// Spend exactly these coins, any confirmed enough coins when empty.
+    pub utxos: Vec<OutPoint>,
+    pub min_conf: u32,
+}
+
+/// Changes to a channel's forwarding policy. Fields left as None are kept.
+#[derive(Clone, Copy, Default)]
+pub struct ChannelConfigUpdate {
+    pub forwarding_fee_base_msat: Option<u32>,
+    pub forwarding_fee_proportional_millionths: Option<u32>,
+    pub cltv_expiry_delta: Option<u16>,
+    pub max_dust_htlc_exposure_msat: Option<u64>,
+}
+
+impl ChannelConfigUpdate {
+    pub fn apply(&self, config: &mut ChannelConfig) {
+        if let Some(base) = self.forwarding_fee_base_msat {
+            config.forwarding_fee_base_msat = base;
+        }
+        if let Some(ppm) = self.forwarding_fee_proportional_millionths {
+            config.forwarding_fee_proportional_millionths = ppm;
+        }
+        if let Some(delta) = self.cltv_expiry_delta {
+            config.cltv_expiry_delta = delta;
+        }
+        if let Some(exposure) = self.max_dust_htlc_exposure_msat {
+            config.max_dust_htlc_exposure_msat = exposure;
+        }
+    }
+}
+
+pub struct Peer {
+    pub public_key: PublicKey,
+    pub socket_addr: Option<SocketAddr>,
+    pub connected: bool,
+    pub node_features: Option<NodeFeatures>,
 }
 
 pub struct OpenChannelResult {
@@ -47,3 +221,46 @@ pub struct OpenChannelResult {
     pub txid: Txid,
     pub channel_id: [u8; 32],
 }
+
+pub struct CloseChannelResult {
+    pub channel_id: [u8; 32],
+    /// None when it was not broadcast in time, the channel is still being closed.
+    pub transaction: Option<Transaction>,
+}
+
+#[derive(Clone, Debug)]
+pub enum ProbeOutcome {
+    Succeeded {
+        path: Vec<RouteHop>,
+    },
+    Failed {
+        path: Vec<RouteHop>,
+        /// The channel that failed the probe, if known.
+        short_channel_id: Option<u64>,
+    },
+    /// There is no path to send the probe along.
+    NoRoute {
+        reason: String,
+    },
+    /// We stopped waiting for the probe to come back.
+    TimedOut,
+}
+
+#[derive(Clone, Debug)]
+pub enum PaymentOutcome {
+    Succeeded {
+        preimage: PaymentPreimage,
+        fee_paid_msat: Option<u64>,
+        /// Hops of the longest path, a multi-path payment takes several.
+        hops: usize,
+    },
+    Failed {
+        reason: String,
+        /// The recipient rejected the payment so retrying will not help.
+        permanent: bool,
+        /// The channel responsible for the last failed attempt, if known.
+        short_channel_id: Option<u64>,
+    },
+    /// We stopped waiting for the payment, it may still succeed or fail.
+    Pending,
+}
@@ -0,0 +1,37 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use api::{InterceptScid, RouteHint};
+use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
+use bitcoin::secp256k1::PublicKey;
+use log::info;
+
+use crate::handle_auth_err;
+use crate::handle_bad_request;
+
+use super::KndMacaroon;
+use super::LightningInterface;
+use super::MacaroonAuth;
+
+pub(crate) async fn intercept_scid(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(intercept_scid): Json<InterceptScid>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_admin_macaroon(&macaroon.0))?;
+
+    let node_id = handle_bad_request!(PublicKey::from_str(&intercept_scid.id))?;
+    let hop = handle_bad_request!(lightning_interface.create_intercept_scid(node_id).await)?;
+    info!(
+        "Created intercept scid {} for {}",
+        hop.short_channel_id, node_id
+    );
+    Ok(Json(RouteHint {
+        node_id: hop.src_node_id.to_string(),
+        short_channel_id: hop.short_channel_id,
+        fee_base_msat: hop.fees.base_msat,
+        fee_proportional_millionths: hop.fees.proportional_millionths,
+        cltv_expiry_delta: hop.cltv_expiry_delta,
+    }))
+}
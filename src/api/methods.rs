@@ -25,7 +25,7 @@ pub(crate) async fn get_info(
         num_inactive_channels: lightning_interface.num_inactive_channels(),
         num_peers: lightning_interface.num_peers(),
         block_height: lightning_interface.block_height(),
-        synced_to_chain: true,
+        synced_to_chain: lightning_interface.synced_to_chain(),
         testnet: lightning_interface.network() != Network::Bitcoin,
         chains: vec![Chain {
             chain: "bitcoin".to_string(),
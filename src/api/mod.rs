@@ -1,32 +1,55 @@
 mod channels;
+mod fee_rate;
+mod invoices;
 mod lightning_interface;
+mod lsp;
 mod macaroon_auth;
 mod methods;
+mod network;
+mod payments;
+mod peers;
+mod scorer;
 mod wallet;
 mod wallet_interface;
 
-pub use lightning_interface::{LightningInterface, OpenChannelResult};
+pub use fee_rate::FeeRateSpec;
+pub use lightning_interface::{
+    ChannelConfigUpdate, CloseChannelResult, FundingOptions, LightningInterface, OpenChannelResult,
+    PaymentOutcome, Peer, ProbeOutcome,
+};
 pub use macaroon_auth::{KndMacaroon, MacaroonAuth};
 pub use wallet_interface::WalletInterface;
 
 use self::methods::get_info;
 use crate::api::{
-    channels::{list_channels, open_channel},
-    wallet::get_balance,
+    channels::{
+        close_channel, get_channel, list_channels, list_forwards, open_channel, set_channel_fee,
+    },
+    invoices::{generate_invoice, list_invoices},
+    lsp::intercept_scid,
+    network::{get_network_channel, get_node, get_route, list_network_channels, list_nodes},
+    payments::{get_payment, keysend, list_payments, pay_invoice, probe},
+    peers::{connect_peer, disconnect_peer, list_peers},
+    scorer::{ban_node, reset_scorer, scorer_channel, unban_node},
+    wallet::{get_balance, list_transactions, list_utxos, new_address, withdraw},
 };
 use anyhow::Result;
 use api::routes;
 use axum::{
     extract::Extension,
     response::IntoResponse,
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
 use axum_server::{tls_rustls::RustlsConfig, Handle};
 use futures::{future::Shared, Future};
 use hyper::StatusCode;
+use lightning::ln::PaymentHash;
 use log::{error, info};
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use tower_http::cors::CorsLayer;
 
 pub async fn start_rest_api(
@@ -46,8 +69,36 @@ pub async fn start_rest_api(
         .route(routes::ROOT, get(root))
         .route(routes::GET_INFO, get(get_info))
         .route(routes::GET_BALANCE, get(get_balance))
+        .route(routes::NEW_ADDR, get(new_address))
+        .route(routes::WITHDRAW, post(withdraw))
+        .route(routes::LIST_UTXOS, get(list_utxos))
+        .route(routes::LIST_TRANSACTIONS, get(list_transactions))
         .route(routes::LIST_CHANNELS, get(list_channels))
+        .route(routes::GET_CHANNEL, get(get_channel))
+        .route(routes::LIST_FORWARDS, get(list_forwards))
+        .route(routes::SET_CHANNEL_FEE, post(set_channel_fee))
         .route(routes::OPEN_CHANNEL, post(open_channel))
+        .route(routes::CLOSE_CHANNEL, post(close_channel))
+        .route(routes::GENERATE_INVOICE, post(generate_invoice))
+        .route(routes::LIST_INVOICES, get(list_invoices))
+        .route(routes::PAY_INVOICE, post(pay_invoice))
+        .route(routes::KEYSEND, post(keysend))
+        .route(routes::LIST_PAYMENTS, get(list_payments))
+        .route(routes::GET_PAYMENT, get(get_payment))
+        .route(routes::PROBE, post(probe))
+        .route(routes::CONNECT_PEER, post(connect_peer))
+        .route(routes::LIST_PEERS, get(list_peers))
+        .route(routes::DISCONNECT_PEER, delete(disconnect_peer))
+        .route(routes::LIST_NODES, get(list_nodes))
+        .route(routes::GET_NODE, get(get_node))
+        .route(routes::LIST_NETWORK_CHANNELS, get(list_network_channels))
+        .route(routes::GET_NETWORK_CHANNEL, get(get_network_channel))
+        .route(routes::GET_ROUTE, get(get_route))
+        .route(routes::INTERCEPT_SCID, post(intercept_scid))
+        .route(routes::SCORER_CHANNEL, get(scorer_channel))
+        .route(routes::BAN_NODE, post(ban_node))
+        .route(routes::UNBAN_NODE, post(unban_node))
+        .route(routes::RESET_SCORER, post(reset_scorer))
         .fallback(handler_404)
         .layer(cors)
         .layer(Extension(lightning_api))
@@ -121,3 +172,31 @@ macro_rules! handle_auth_err {
         })
     };
 }
+
+#[macro_export]
+macro_rules! handle_bad_request {
+    ($parse:expr) => {
+        $parse.map_err(|e| {
+            info!("{}", e);
+            StatusCode::BAD_REQUEST
+        })
+    };
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn parse_channel_id(id: &str) -> Result<[u8; 32], StatusCode> {
+    let bytes = handle_bad_request!(hex::decode(id))?;
+    handle_bad_request!(bytes.try_into().map_err(|_| "Channel ID must be 32 bytes"))
+}
+
+fn parse_payment_hash(hash: &str) -> Result<PaymentHash, StatusCode> {
+    let bytes = handle_bad_request!(hex::decode(hash))?;
+    Ok(PaymentHash(handle_bad_request!(bytes
+        .try_into()
+        .map_err(|_| "Payment hash must be 32 bytes"))?))
+}
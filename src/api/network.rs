@@ -0,0 +1,204 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use api::{
+    ChannelPolicy, GetRoute, ListGraph, NetworkChannel, NetworkNode, Route, RouteHop, RoutePath,
+};
+use axum::extract::{Path, Query};
+use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
+use bitcoin::secp256k1::PublicKey;
+use hex::ToHex;
+use lightning::ln::msgs::NetAddress;
+use lightning::routing::gossip::{ChannelInfo, ChannelUpdateInfo, NodeId, NodeInfo};
+use lightning::routing::router;
+use lightning::util::ser::Writeable;
+use lightning_invoice::DEFAULT_MIN_FINAL_CLTV_EXPIRY;
+use log::info;
+
+use crate::handle_auth_err;
+use crate::handle_bad_request;
+
+use super::KndMacaroon;
+use super::LightningInterface;
+use super::MacaroonAuth;
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+pub(crate) async fn list_nodes(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<ListGraph>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_readonly_macaroon(&macaroon.0))?;
+
+    let after = match params.after {
+        Some(id) => Some(NodeId::from_pubkey(&handle_bad_request!(
+            PublicKey::from_str(&id)
+        )?)),
+        None => None,
+    };
+    let nodes: Vec<NetworkNode> = lightning_interface
+        .graph_nodes(after, limit(params.limit))
+        .iter()
+        .map(|(id, node)| to_api_node(id, node))
+        .collect();
+    Ok(Json(nodes))
+}
+
+pub(crate) async fn get_node(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_readonly_macaroon(&macaroon.0))?;
+
+    let public_key = handle_bad_request!(PublicKey::from_str(&id))?;
+    let node = lightning_interface
+        .get_node(public_key)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(to_api_node(&NodeId::from_pubkey(&public_key), &node)))
+}
+
+pub(crate) async fn list_network_channels(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<ListGraph>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_readonly_macaroon(&macaroon.0))?;
+
+    let after = match params.after {
+        Some(scid) => Some(handle_bad_request!(scid.parse())?),
+        None => None,
+    };
+    let channels: Vec<NetworkChannel> = lightning_interface
+        .graph_channels(after, limit(params.limit))
+        .iter()
+        .map(|(scid, channel)| to_api_channel(*scid, channel))
+        .collect();
+    Ok(Json(channels))
+}
+
+pub(crate) async fn get_network_channel(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(short_channel_id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_readonly_macaroon(&macaroon.0))?;
+
+    let short_channel_id = handle_bad_request!(short_channel_id.parse())?;
+    let channel = lightning_interface
+        .graph_channel(short_channel_id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(to_api_channel(short_channel_id, &channel)))
+}
+
+pub(crate) async fn get_route(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path((id, msatoshi)): Path<(String, String)>,
+    Query(params): Query<GetRoute>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_readonly_macaroon(&macaroon.0))?;
+
+    let payee = handle_bad_request!(PublicKey::from_str(&id))?;
+    let amount_msat = handle_bad_request!(msatoshi.parse())?;
+    let final_cltv = params
+        .final_cltv
+        .unwrap_or(DEFAULT_MIN_FINAL_CLTV_EXPIRY as u32);
+    let route = lightning_interface
+        .find_route(payee, amount_msat, final_cltv)
+        .map_err(|e| {
+            info!("No route to {}: {}", payee, e);
+            StatusCode::NOT_FOUND
+        })?;
+    let response = Route {
+        paths: route
+            .paths
+            .iter()
+            .map(|path| RoutePath {
+                hops: path.iter().map(to_api_hop).collect(),
+                fee_msat: path
+                    .split_last()
+                    .map(|(_, hops)| hops.iter().map(|hop| hop.fee_msat).sum())
+                    .unwrap_or_default(),
+                cltv_expiry_delta: path.iter().map(|hop| hop.cltv_expiry_delta).sum(),
+            })
+            .collect(),
+        fee_msat: route.get_total_fees(),
+        amount_msat: route.get_total_amount(),
+    };
+    Ok(Json(response))
+}
+
+pub(super) fn to_api_hop(hop: &router::RouteHop) -> RouteHop {
+    RouteHop {
+        id: hop.pubkey.to_string(),
+        short_channel_id: hop.short_channel_id,
+        fee_msat: hop.fee_msat,
+        cltv_expiry_delta: hop.cltv_expiry_delta,
+    }
+}
+
+fn limit(limit: Option<usize>) -> usize {
+    limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT)
+}
+
+fn to_api_node(id: &NodeId, node: &NodeInfo) -> NetworkNode {
+    let announcement = node.announcement_info.as_ref();
+    NetworkNode {
+        id: id.as_slice().encode_hex(),
+        alias: announcement.map(|a| a.alias.to_string()),
+        color: announcement.map(|a| a.rgb.encode_hex()),
+        last_update: announcement.map(|a| a.last_update),
+        addresses: announcement
+            .map(|a| a.addresses.iter().filter_map(net_address).collect())
+            .unwrap_or_default(),
+        features: announcement.map(|a| hex::encode(&a.features.encode()[2..])),
+        channels: node.channels.clone(),
+    }
+}
+
+fn to_api_channel(short_channel_id: u64, channel: &ChannelInfo) -> NetworkChannel {
+    NetworkChannel {
+        short_channel_id,
+        node_one: channel.node_one.as_slice().encode_hex(),
+        node_two: channel.node_two.as_slice().encode_hex(),
+        capacity_sats: channel.capacity_sats,
+        one_to_two: channel.one_to_two.as_ref().map(to_api_policy),
+        two_to_one: channel.two_to_one.as_ref().map(to_api_policy),
+    }
+}
+
+fn to_api_policy(update: &ChannelUpdateInfo) -> ChannelPolicy {
+    ChannelPolicy {
+        enabled: update.enabled,
+        base_fee_msat: update.fees.base_msat,
+        fee_proportional_millionths: update.fees.proportional_millionths,
+        cltv_expiry_delta: update.cltv_expiry_delta,
+        htlc_minimum_msat: update.htlc_minimum_msat,
+        htlc_maximum_msat: update.htlc_maximum_msat,
+        last_update: update.last_update,
+    }
+}
+
+// Tor addresses are left out.
+fn net_address(address: &NetAddress) -> Option<String> {
+    match address {
+        NetAddress::IPv4 { addr, port } => {
+            Some(format!("{}:{}", std::net::Ipv4Addr::from(*addr), port))
+        }
+        NetAddress::IPv6 { addr, port } => {
+            Some(format!("[{}]:{}", std::net::Ipv6Addr::from(*addr), port))
+        }
+        NetAddress::Hostname { hostname, port } => {
+            Some(format!("{}:{}", String::from(hostname.clone()), port))
+        }
+        _ => None,
+    }
+}
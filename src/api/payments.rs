@@ -0,0 +1,244 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Result};
+use api::{
+    Keysend, ListPayments, PayInvoice, Payment, PaymentFailure, PaymentResponse, Probe,
+    ProbeResponse,
+};
+use axum::extract::{Path, Query};
+use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::PublicKey;
+use database::payment::{PaymentDirection, PaymentStatus};
+use hex::ToHex;
+use lightning::ln::PaymentHash;
+use lightning::routing::router::PaymentParameters;
+use lightning_invoice::DEFAULT_MIN_FINAL_CLTV_EXPIRY;
+use log::{info, warn};
+
+use crate::handle_auth_err;
+use crate::handle_bad_request;
+use crate::handle_err;
+
+use super::network::to_api_hop;
+use super::KndMacaroon;
+use super::LightningInterface;
+use super::MacaroonAuth;
+use super::PaymentOutcome;
+use super::ProbeOutcome;
+use super::{parse_payment_hash, unix_secs};
+
+pub(crate) async fn pay_invoice(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(pay_invoice): Json<PayInvoice>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_admin_macaroon(&macaroon.0))?;
+
+    let invoice = handle_bad_request!(lightning_invoice::Invoice::from_str(&pay_invoice.invoice))?;
+    let payment_hash = PaymentHash(invoice.payment_hash().into_inner());
+    let outcome = handle_err!(
+        lightning_interface
+            .pay_invoice(invoice, pay_invoice.amount)
+            .await
+    )?;
+    Ok(Json(payment_response(payment_hash, outcome)))
+}
+
+pub(crate) async fn keysend(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(keysend): Json<Keysend>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_admin_macaroon(&macaroon.0))?;
+
+    let pubkey = handle_bad_request!(PublicKey::from_str(&keysend.pubkey))?;
+    let (payment_hash, outcome) =
+        handle_err!(lightning_interface.keysend(pubkey, keysend.amount).await)?;
+    Ok(Json(payment_response(payment_hash, outcome)))
+}
+
+pub(crate) async fn probe(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(probe): Json<Probe>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_admin_macaroon(&macaroon.0))?;
+
+    let (payment_params, amount_msat, final_cltv) = handle_bad_request!(probe_params(&probe))?;
+    let payee = payment_params.payee_pubkey;
+    let outcome = handle_err!(
+        lightning_interface
+            .probe(payment_params, amount_msat, final_cltv)
+            .await
+    )?;
+    let response = match outcome {
+        ProbeOutcome::Succeeded { path } => ProbeResponse {
+            success: true,
+            hops: path.iter().map(to_api_hop).collect(),
+            failed_hop: None,
+        },
+        ProbeOutcome::Failed {
+            path,
+            short_channel_id,
+        } => ProbeResponse {
+            success: false,
+            hops: path.iter().map(to_api_hop).collect(),
+            failed_hop: path
+                .iter()
+                .find(|hop| Some(hop.short_channel_id) == short_channel_id)
+                .map(to_api_hop),
+        },
+        ProbeOutcome::NoRoute { reason } => {
+            info!("No route to {}: {}", payee, reason);
+            return Err(StatusCode::NOT_FOUND);
+        }
+        ProbeOutcome::TimedOut => {
+            info!("Timed out probing {}", payee);
+            return Err(StatusCode::GATEWAY_TIMEOUT);
+        }
+    };
+    Ok(Json(response))
+}
+
+pub(crate) async fn list_payments(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Query(params): Query<ListPayments>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_readonly_macaroon(&macaroon.0))?;
+
+    let direction = match params.direction {
+        Some(direction) => Some(handle_bad_request!(PaymentDirection::parse(&direction))?),
+        None => None,
+    };
+    let status = match params.status {
+        Some(status) => Some(handle_bad_request!(PaymentStatus::parse(&status))?),
+        None => None,
+    };
+    let payments: Vec<Payment> = handle_err!(
+        lightning_interface
+            .list_payments(None, direction, status)
+            .await
+    )?
+    .into_iter()
+    .map(to_api_payment)
+    .collect();
+    Ok(Json(payments))
+}
+
+pub(crate) async fn get_payment(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(payment_hash): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_readonly_macaroon(&macaroon.0))?;
+
+    let payment_hash = parse_payment_hash(&payment_hash)?;
+    // In the unlikely case we both sent and received with this hash, show the latest.
+    let payment = handle_err!(
+        lightning_interface
+            .list_payments(Some(payment_hash), None, None)
+            .await
+    )?
+    .into_iter()
+    .next()
+    .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(to_api_payment(payment)))
+}
+
+// Where to probe, how much and the final CLTV delta.
+fn probe_params(probe: &Probe) -> Result<(PaymentParameters, u64, u32)> {
+    match (&probe.pubkey, &probe.invoice) {
+        (Some(pubkey), None) => {
+            let payee = PublicKey::from_str(pubkey)?;
+            let amount_msat = probe
+                .amount
+                .ok_or_else(|| anyhow!("Amount is required to probe a node"))?;
+            Ok((
+                PaymentParameters::from_node_id(payee),
+                amount_msat,
+                DEFAULT_MIN_FINAL_CLTV_EXPIRY as u32,
+            ))
+        }
+        (None, Some(invoice)) => {
+            let invoice = lightning_invoice::Invoice::from_str(invoice)?;
+            let amount_msat = invoice
+                .amount_milli_satoshis()
+                .or(probe.amount)
+                .ok_or_else(|| anyhow!("Amount is required for invoices without one"))?;
+            let mut payment_params =
+                PaymentParameters::from_node_id(invoice.recover_payee_pub_key())
+                    .with_route_hints(invoice.route_hints());
+            if let Some(features) = invoice.features() {
+                payment_params = payment_params.with_features(features.clone());
+            }
+            Ok((
+                payment_params,
+                amount_msat,
+                invoice.min_final_cltv_expiry() as u32,
+            ))
+        }
+        _ => bail!("Either a pubkey or an invoice is required"),
+    }
+}
+
+fn to_api_payment(payment: database::payment::Payment) -> Payment {
+    Payment {
+        payment_hash: payment.payment_hash.0.encode_hex(),
+        direction: payment.direction.as_str().to_string(),
+        status: payment.status.as_str().to_string(),
+        msatoshi: payment.amount_msat,
+        fee_msat: payment.fee_msat,
+        payment_preimage: payment.preimage.map(|p| p.0.encode_hex()),
+        created_at: unix_secs(payment.timestamp),
+    }
+}
+
+fn payment_response(payment_hash: PaymentHash, outcome: PaymentOutcome) -> PaymentResponse {
+    let payment_hash = payment_hash.0.encode_hex();
+    match outcome {
+        PaymentOutcome::Succeeded {
+            preimage,
+            fee_paid_msat,
+            hops,
+        } => PaymentResponse {
+            payment_hash,
+            status: "succeeded".to_string(),
+            payment_preimage: Some(preimage.0.encode_hex()),
+            fee_msat: fee_paid_msat,
+            hops: Some(hops),
+            failure: None,
+        },
+        PaymentOutcome::Failed {
+            reason,
+            permanent,
+            short_channel_id,
+        } => PaymentResponse {
+            payment_hash,
+            status: "failed".to_string(),
+            payment_preimage: None,
+            fee_msat: None,
+            hops: None,
+            failure: Some(PaymentFailure {
+                reason,
+                permanent,
+                short_channel_id,
+            }),
+        },
+        PaymentOutcome::Pending => PaymentResponse {
+            payment_hash,
+            status: "pending".to_string(),
+            payment_preimage: None,
+            fee_msat: None,
+            hops: None,
+            failure: None,
+        },
+    }
+}
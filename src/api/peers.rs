@@ -0,0 +1,90 @@
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use api::{ConnectPeer, ConnectPeerResponse, DisconnectPeer, Peer};
+use axum::extract::{Path, Query};
+use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
+use bitcoin::secp256k1::PublicKey;
+use lightning::util::ser::Writeable;
+use log::{info, warn};
+
+use crate::handle_auth_err;
+use crate::handle_bad_request;
+use crate::handle_err;
+
+use super::KndMacaroon;
+use super::LightningInterface;
+use super::MacaroonAuth;
+
+pub(crate) async fn connect_peer(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Json(connect_peer): Json<ConnectPeer>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_admin_macaroon(&macaroon.0))?;
+
+    let (public_key, peer_address) = handle_bad_request!(parse_peer_address(&connect_peer.id))?;
+    handle_err!(
+        lightning_interface
+            .connect_peer(public_key, peer_address)
+            .await
+    )?;
+    Ok(Json(ConnectPeerResponse {
+        id: public_key.to_string(),
+    }))
+}
+
+pub(crate) async fn list_peers(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_readonly_macaroon(&macaroon.0))?;
+
+    let peers: Vec<Peer> = handle_err!(lightning_interface.list_peers().await)?
+        .into_iter()
+        .map(|peer| Peer {
+            id: peer.public_key.to_string(),
+            connected: peer.connected,
+            netaddr: peer.socket_addr.map(|addr| addr.to_string()),
+            // Skip the length prefix, leaving the big endian flags.
+            features: peer
+                .node_features
+                .map(|features| hex::encode(&features.encode()[2..])),
+        })
+        .collect();
+    Ok(Json(peers))
+}
+
+pub(crate) async fn disconnect_peer(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(id): Path<String>,
+    Query(params): Query<DisconnectPeer>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_admin_macaroon(&macaroon.0))?;
+
+    let public_key = handle_bad_request!(PublicKey::from_str(&id))?;
+    handle_err!(
+        lightning_interface
+            .disconnect_peer(public_key, params.forget)
+            .await
+    )?;
+    Ok(())
+}
+
+fn parse_peer_address(id: &str) -> Result<(PublicKey, SocketAddr)> {
+    let (public_key, address) = id
+        .split_once('@')
+        .ok_or_else(|| anyhow!("Expected pubkey@host:port"))?;
+    let public_key = PublicKey::from_str(public_key)?;
+    let peer_address = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("Could not resolve {}", address))?;
+    Ok((public_key, peer_address))
+}
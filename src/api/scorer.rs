@@ -0,0 +1,83 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use api::{ChannelLiquidity, LiquidityRange};
+use axum::extract::Path;
+use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
+use bitcoin::secp256k1::PublicKey;
+use hex::ToHex;
+use log::{info, warn};
+
+use crate::handle_auth_err;
+use crate::handle_bad_request;
+use crate::handle_err;
+
+use super::KndMacaroon;
+use super::LightningInterface;
+use super::MacaroonAuth;
+
+pub(crate) async fn scorer_channel(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(short_channel_id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_admin_macaroon(&macaroon.0))?;
+
+    let short_channel_id = handle_bad_request!(short_channel_id.parse())?;
+    let channel = lightning_interface
+        .graph_channel(short_channel_id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let liquidity = |target| {
+        lightning_interface
+            .estimated_channel_liquidity_range(short_channel_id, target)
+            .map(|(min_msat, max_msat)| LiquidityRange { min_msat, max_msat })
+    };
+    Ok(Json(ChannelLiquidity {
+        short_channel_id,
+        node_one: channel.node_one.as_slice().encode_hex(),
+        node_two: channel.node_two.as_slice().encode_hex(),
+        one_to_two: liquidity(&channel.node_two),
+        two_to_one: liquidity(&channel.node_one),
+    }))
+}
+
+pub(crate) async fn ban_node(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_admin_macaroon(&macaroon.0))?;
+
+    let node_id = handle_bad_request!(PublicKey::from_str(&id))?;
+    handle_err!(lightning_interface.ban_node(node_id).await)?;
+    info!("Banned node {} from routing", node_id);
+    Ok(())
+}
+
+pub(crate) async fn unban_node(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_admin_macaroon(&macaroon.0))?;
+
+    let node_id = handle_bad_request!(PublicKey::from_str(&id))?;
+    handle_err!(lightning_interface.unban_node(node_id).await)?;
+    info!("Unbanned node {}", node_id);
+    Ok(())
+}
+
+pub(crate) async fn reset_scorer(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(lightning_interface): Extension<Arc<dyn LightningInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_admin_macaroon(&macaroon.0))?;
+
+    handle_err!(lightning_interface.reset_scorer().await)?;
+    info!("Reset the scorer");
+    Ok(())
+}
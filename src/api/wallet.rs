@@ -1,10 +1,15 @@
-use api::Balance;
+use api::{Balance, NewAddressResponse, Utxo, WalletTransaction, Withdraw, WithdrawResponse};
 use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
-use log::info;
+use bitcoin::Address;
+use log::{info, warn};
+use std::str::FromStr;
 use std::sync::Arc;
 
 use crate::handle_auth_err;
+use crate::handle_bad_request;
+use crate::handle_err;
 
+use super::FeeRateSpec;
 use super::KndMacaroon;
 use super::MacaroonAuth;
 use super::WalletInterface;
@@ -29,3 +34,94 @@ pub(crate) async fn get_balance(
         Err(StatusCode::INTERNAL_SERVER_ERROR)
     }
 }
+
+pub(crate) async fn new_address(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_admin_macaroon(&macaroon.0))?;
+
+    let address = handle_err!(wallet.new_address())?;
+    Ok(Json(NewAddressResponse {
+        address: address.address.to_string(),
+    }))
+}
+
+pub(crate) async fn withdraw(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
+    Json(withdraw): Json<Withdraw>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_admin_macaroon(&macaroon.0))?;
+
+    let address = handle_bad_request!(Address::from_str(&withdraw.address))?;
+    if address.network != wallet.network() {
+        info!("Address {} is for a different network", address);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let amount = match withdraw.satoshis.as_str() {
+        "all" => None,
+        satoshis => Some(handle_bad_request!(satoshis.parse())?),
+    };
+    let fee_rate = match withdraw.fee_rate {
+        Some(fee_rate) => handle_bad_request!(FeeRateSpec::from_str(&fee_rate))?,
+        None => FeeRateSpec::default(),
+    };
+    let transaction = handle_err!(wallet.withdraw(address, amount, fee_rate))?;
+    let response = WithdrawResponse {
+        tx: handle_err!(serde_json::to_string(&transaction))?,
+        txid: transaction.txid().to_string(),
+    };
+    Ok(Json(response))
+}
+
+pub(crate) async fn list_utxos(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_readonly_macaroon(&macaroon.0))?;
+
+    let utxos: Vec<Utxo> = handle_err!(wallet.list_utxos())?
+        .into_iter()
+        .map(|(utxo, confirmation_time)| Utxo {
+            txid: utxo.outpoint.txid.to_string(),
+            output: utxo.outpoint.vout,
+            value: utxo.txout.value,
+            address: Address::from_script(&utxo.txout.script_pubkey, wallet.network())
+                .map(|a| a.to_string())
+                .ok(),
+            status: if confirmation_time.is_some() {
+                "confirmed"
+            } else {
+                "unconfirmed"
+            }
+            .to_string(),
+            blockheight: confirmation_time.map(|t| t.height),
+        })
+        .collect();
+    Ok(Json(utxos))
+}
+
+pub(crate) async fn list_transactions(
+    macaroon: KndMacaroon,
+    Extension(macaroon_auth): Extension<Arc<MacaroonAuth>>,
+    Extension(wallet): Extension<Arc<dyn WalletInterface + Send + Sync>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    handle_auth_err!(macaroon_auth.verify_readonly_macaroon(&macaroon.0))?;
+
+    let transactions: Vec<WalletTransaction> = handle_err!(wallet.list_transactions())?
+        .into_iter()
+        .map(|tx| WalletTransaction {
+            txid: tx.txid.to_string(),
+            received: tx.received,
+            sent: tx.sent,
+            fee: tx.fee,
+            blockheight: tx.confirmation_time.as_ref().map(|t| t.height),
+            timestamp: tx.confirmation_time.map(|t| t.timestamp),
+        })
+        .collect();
+    Ok(Json(transactions))
+}
@@ -1,6 +1,26 @@
 use anyhow::Result;
-use bdk::Balance;
+use bdk::{wallet::AddressInfo, Balance, BlockTime, LocalUtxo, TransactionDetails};
+use bitcoin::{Address, Network, Transaction};
+
+use super::FeeRateSpec;
 
 pub trait WalletInterface {
     fn balance(&self) -> Result<Balance>;
+
+    fn network(&self) -> Network;
+
+    fn new_address(&self) -> Result<AddressInfo>;
+
+    /// Send to an address and broadcast. Without an amount the whole wallet is swept.
+    fn withdraw(
+        &self,
+        address: Address,
+        amount: Option<u64>,
+        fee_rate: FeeRateSpec,
+    ) -> Result<Transaction>;
+
+    /// Unspent outputs with the block they confirmed in, if any.
+    fn list_utxos(&self) -> Result<Vec<(LocalUtxo, Option<BlockTime>)>>;
+
+    fn list_transactions(&self) -> Result<Vec<TransactionDetails>>;
 }
@@ -0,0 +1,145 @@
+use bitcoin::secp256k1::PublicKey;
+use lightning::ln::features::ChannelTypeFeatures;
+use settings::Settings;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Decision {
+    Accept,
+    AcceptZeroConf,
+    Reject(String),
+}
+
+/// Decides which inbound channel requests we accept.
+pub(crate) struct ChannelAcceptor {
+    min_size_sats: u64,
+    max_size_sats: Option<u64>,
+    allowlist: Vec<PublicKey>,
+    denylist: Vec<PublicKey>,
+    max_pending_per_peer: usize,
+    zero_conf_peers: Vec<PublicKey>,
+}
+
+impl ChannelAcceptor {
+    pub fn new(settings: &Settings) -> ChannelAcceptor {
+        ChannelAcceptor {
+            min_size_sats: settings.channel_min_size_sats,
+            max_size_sats: settings.channel_max_size_sats,
+            allowlist: settings.channel_peer_allowlist.clone(),
+            denylist: settings.channel_peer_denylist.clone(),
+            max_pending_per_peer: settings.channel_max_pending_per_peer,
+            zero_conf_peers: settings.channel_zero_conf_peers.clone(),
+        }
+    }
+
+    /// `pending_channels` is the number of channels with the peer that are not ready yet,
+    /// not counting this one.
+    pub fn decide(
+        &self,
+        counterparty: &PublicKey,
+        funding_satoshis: u64,
+        channel_type: &ChannelTypeFeatures,
+        pending_channels: usize,
+    ) -> Decision {
+        if self.denylist.contains(counterparty) {
+            return Decision::Reject("peer is on the denylist".to_string());
+        }
+        if !self.allowlist.is_empty() && !self.allowlist.contains(counterparty) {
+            return Decision::Reject("peer is not on the allowlist".to_string());
+        }
+        if funding_satoshis < self.min_size_sats {
+            return Decision::Reject(format!(
+                "channel size {} is below the minimum {}",
+                funding_satoshis, self.min_size_sats
+            ));
+        }
+        if let Some(max_size_sats) = self.max_size_sats {
+            if funding_satoshis > max_size_sats {
+                return Decision::Reject(format!(
+                    "channel size {} is above the maximum {}",
+                    funding_satoshis, max_size_sats
+                ));
+            }
+        }
+        if pending_channels >= self.max_pending_per_peer {
+            return Decision::Reject(format!(
+                "peer already has {} pending channels",
+                pending_channels
+            ));
+        }
+        if self.zero_conf_peers.contains(counterparty) {
+            Decision::AcceptZeroConf
+        } else if channel_type.requires_zero_conf() {
+            Decision::Reject("zero conf channels are only accepted from trusted peers".to_string())
+        } else {
+            Decision::Accept
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_acceptor() -> ChannelAcceptor {
+    ChannelAcceptor {
+        min_size_sats: 20000,
+        max_size_sats: Some(1000000),
+        allowlist: vec![],
+        denylist: vec![],
+        max_pending_per_peer: 1,
+        zero_conf_peers: vec![],
+    }
+}
+
+#[test]
+fn test_decide_size_and_pending() {
+    let acceptor = test_acceptor();
+    let peer = test_utils::random_public_key();
+    let channel_type = ChannelTypeFeatures::empty();
+    assert_eq!(
+        Decision::Accept,
+        acceptor.decide(&peer, 20000, &channel_type, 0)
+    );
+    assert!(matches!(
+        acceptor.decide(&peer, 19999, &channel_type, 0),
+        Decision::Reject(_)
+    ));
+    assert!(matches!(
+        acceptor.decide(&peer, 1000001, &channel_type, 0),
+        Decision::Reject(_)
+    ));
+    assert!(matches!(
+        acceptor.decide(&peer, 20000, &channel_type, 1),
+        Decision::Reject(_)
+    ));
+}
+
+#[test]
+fn test_decide_peer_lists() {
+    let trusted = test_utils::random_public_key();
+    let denied = test_utils::random_public_key();
+    let other = test_utils::random_public_key();
+    let acceptor = ChannelAcceptor {
+        allowlist: vec![trusted, denied],
+        denylist: vec![denied],
+        zero_conf_peers: vec![trusted],
+        ..test_acceptor()
+    };
+    let mut zero_conf = ChannelTypeFeatures::empty();
+    zero_conf.set_zero_conf_required();
+    assert_eq!(
+        Decision::AcceptZeroConf,
+        acceptor.decide(&trusted, 20000, &zero_conf, 0)
+    );
+    assert!(matches!(
+        acceptor.decide(&denied, 20000, &ChannelTypeFeatures::empty(), 0),
+        Decision::Reject(_)
+    ));
+    assert!(matches!(
+        acceptor.decide(&other, 20000, &ChannelTypeFeatures::empty(), 0),
+        Decision::Reject(_)
+    ));
+
+    let acceptor = test_acceptor();
+    assert!(matches!(
+        acceptor.decide(&other, 20000, &zero_conf, 0),
+        Decision::Reject(_)
+    ));
+}
@@ -1,53 +1,87 @@
-use crate::api::{LightningInterface, OpenChannelResult, WalletInterface};
+use crate::api::{
+    self, ChannelConfigUpdate, CloseChannelResult, FundingOptions, LightningInterface,
+    OpenChannelResult, PaymentOutcome, ProbeOutcome, WalletInterface,
+};
+use crate::channel_acceptor::ChannelAcceptor;
 use crate::event_handler::EventHandler;
 use crate::key_generator::KeyGenerator;
+use crate::keys_manager::KndKeysManager;
+use crate::lsp::Interceptor;
 use crate::net_utils::do_connect_peer;
-use crate::payment_info::PaymentInfoStorage;
+use crate::prober::{find_probe_path, send_probe, start_prober};
+use crate::rapid_gossip_sync::{apply_snapshot, RapidGossipSync, SnapshotSource};
 use crate::wallet::Wallet;
 use crate::{net_utils, VERSION};
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
 use bitcoin::blockdata::constants::genesis_block;
+use bitcoin::hashes::sha256;
+use bitcoin::hashes::Hash as _;
 use bitcoin::secp256k1::PublicKey;
-use bitcoin::Transaction;
+use bitcoin::{Network, OutPoint, Transaction};
 use bitcoind::Client;
+use database::forward::Forward;
+use database::invoice::{Invoice, InvoiceStatus};
 use database::ldk_database::LdkDatabase;
-use lightning::chain::keysinterface::{InMemorySigner, KeysInterface, KeysManager, Recipient};
+use database::payment::{Payment, PaymentDirection, PaymentStatus};
+use database::peer::Peer;
+use hex::ToHex;
+use lightning::chain::keysinterface::{InMemorySigner, KeysInterface, Recipient};
 use lightning::chain::{self, ChannelMonitorUpdateStatus};
 use lightning::chain::{chainmonitor, Watch};
 use lightning::chain::{BestBlock, Filter};
+use lightning::ln::channelmanager::PaymentId;
+use lightning::ln::channelmanager::PaymentSendFailure;
 use lightning::ln::channelmanager::{self, ChannelDetails};
-use lightning::ln::channelmanager::{
-    ChainParameters, ChannelManagerReadArgs, SimpleArcChannelManager,
+use lightning::ln::channelmanager::{ChainParameters, ChannelManagerReadArgs};
+use lightning::ln::peer_handler::{self, IgnoringMessageHandler, MessageHandler};
+use lightning::ln::script::ShutdownScript;
+use lightning::ln::{PaymentHash, PaymentPreimage, PaymentSecret};
+use lightning::onion_message;
+use lightning::routing::gossip::{self, NodeId, P2PGossipSync, RoutingFees};
+use lightning::routing::router::{
+    DefaultRouter, PaymentParameters, Route, RouteHintHop, RouteParameters, Router as _,
 };
-use lightning::ln::peer_handler::{IgnoringMessageHandler, MessageHandler, SimpleArcPeerManager};
-use lightning::onion_message::SimpleArcOnionMessenger;
-use lightning::routing::gossip::{self, NodeId, P2PGossipSync};
-use lightning::routing::router::DefaultRouter;
 use lightning::routing::scoring::{ProbabilisticScorer, ProbabilisticScoringParameters};
-use lightning::util::config::UserConfig;
+use lightning::util::config::{ChannelConfig, UserConfig};
 use lightning::util::errors::APIError;
 use lightning_background_processor::{BackgroundProcessor, GossipSync};
 use lightning_block_sync::init;
 use lightning_block_sync::poll;
 use lightning_block_sync::SpvClient;
 use lightning_block_sync::UnboundedCache;
-use lightning_invoice::payment;
+use lightning_invoice::payment::PaymentError;
+use lightning_invoice::{payment, utils, Currency};
 use lightning_net_tokio::SocketDescriptor;
-use log::{error, warn};
+use log::{error, info, warn};
 use logger::KndLogger;
 use rand::{random, thread_rng, Rng};
 use settings::Settings;
 use std::collections::HashMap;
 use std::hash::Hash;
+use std::net::SocketAddr;
+use std::ops::Bound;
 use std::ops::Deref;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 use tokio::runtime::Handle;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::oneshot::{self, Receiver, Sender};
 use tokio::sync::RwLock;
 
+// Comfortably above the MIN_FINAL_CLTV_EXPIRY that LDK recipients enforce.
+const KEYSEND_FINAL_CLTV_EXPIRY_DELTA: u32 = 40;
+
+// Probes are failed back by the destination so they don't take as long as payments.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(60);
+
+// LDK goes on with a payment after this, its status is kept up to date in the database.
+const PAYMENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+// A cooperative close needs the peer, which may be offline or slow to agree on the fee.
+const CLOSE_CHANNEL_TIMEOUT: Duration = Duration::from_secs(60);
+
 #[async_trait]
 impl LightningInterface for Controller {
     fn identity_pubkey(&self) -> PublicKey {
@@ -66,6 +100,14 @@ impl LightningInterface for Controller {
         self.peer_manager.get_peer_node_ids().len()
     }
 
+    fn pending_monitor_writes(&self) -> usize {
+        self.database.pending_monitor_writes()
+    }
+
+    fn failed_monitor_writes(&self) -> u64 {
+        self.database.failed_monitor_writes()
+    }
+
     fn wallet_balance(&self) -> u64 {
         match self.wallet.balance() {
             Ok(balance) => balance.confirmed,
@@ -95,16 +137,37 @@ impl LightningInterface for Controller {
         self.settings.bitcoin_network
     }
 
+    fn synced_to_chain(&self) -> bool {
+        let info = tokio::task::block_in_place(move || {
+            Handle::current().block_on(self.bitcoind_client.get_blockchain_info())
+        });
+        // The SPV client connects each block it sees to the channel manager.
+        let spv_tip = self.channel_manager.current_best_block().height() as usize;
+        spv_tip >= info.latest_headers && self.wallet.is_synced()
+    }
+
     fn num_active_channels(&self) -> usize {
-        0
+        self.channel_manager
+            .list_channels()
+            .iter()
+            .filter(|c| c.is_usable)
+            .count()
     }
 
     fn num_inactive_channels(&self) -> usize {
-        0
+        self.channel_manager
+            .list_channels()
+            .iter()
+            .filter(|c| c.is_channel_ready && !c.is_usable)
+            .count()
     }
 
     fn num_pending_channels(&self) -> usize {
-        0
+        self.channel_manager
+            .list_channels()
+            .iter()
+            .filter(|c| !c.is_channel_ready)
+            .count()
     }
 
     fn list_channels(&self) -> Vec<ChannelDetails> {
@@ -116,25 +179,71 @@ impl LightningInterface for Controller {
         their_network_key: PublicKey,
         channel_value_satoshis: u64,
         push_msat: u64,
-        override_config: Option<UserConfig>,
+        funding: FundingOptions,
+        announce: bool,
+        close_to: Option<ShutdownScript>,
     ) -> Result<OpenChannelResult> {
+        // Fail before talking to the peer if our coins cannot pay for the channel.
+        self.wallet.check_funding(
+            channel_value_satoshis,
+            &funding,
+            self.channel_manager.current_best_block().height(),
+        )?;
         let user_channel_id: u128 = random();
-        let channel_id = self
-            .channel_manager
-            .create_channel(
-                their_network_key,
-                channel_value_satoshis,
-                push_msat,
-                user_channel_id,
-                override_config,
-            )
-            .map_err(api_error)?;
+        self.async_api_requests
+            .channel_funding
+            .write()
+            .await
+            .insert(user_channel_id, funding);
         let receiver = self
             .async_api_requests
             .channel_opens
             .insert(user_channel_id)
             .await;
-        let transaction = receiver.await?;
+        let mut config = *self.channel_manager.get_current_default_configuration();
+        config.channel_handshake_config.announced_channel = announce;
+        if close_to.is_some() {
+            config
+                .channel_handshake_config
+                .commit_upfront_shutdown_pubkey = true;
+        }
+        let wants_close_to = close_to.is_some();
+        let (created, close_to_used) = self.keys_manager.with_shutdown_script(close_to, || {
+            self.channel_manager.create_channel(
+                their_network_key,
+                channel_value_satoshis,
+                push_msat,
+                user_channel_id,
+                Some(config),
+            )
+        });
+        let created = match created {
+            Ok(channel_id) if wants_close_to && !close_to_used => {
+                // Nothing was funded yet so this only forgets the channel.
+                let _ = self
+                    .channel_manager
+                    .force_close_without_broadcasting_txn(&channel_id, &their_network_key);
+                Err(anyhow!("Channel did not commit to the close_to address"))
+            }
+            Ok(channel_id) => Ok(channel_id),
+            Err(e) => Err(api_error(e)),
+        };
+        let channel_id = match created {
+            Ok(channel_id) => channel_id,
+            Err(e) => {
+                self.async_api_requests
+                    .channel_opens
+                    .remove(&user_channel_id)
+                    .await;
+                self.async_api_requests
+                    .channel_funding
+                    .write()
+                    .await
+                    .remove(&user_channel_id);
+                return Err(e);
+            }
+        };
+        let transaction = receiver.await??;
         let txid = transaction.txid();
         Ok(OpenChannelResult {
             transaction,
@@ -143,22 +252,438 @@ impl LightningInterface for Controller {
         })
     }
 
+    fn set_channel_fee(
+        &self,
+        channel_ids: Option<Vec<[u8; 32]>>,
+        update: ChannelConfigUpdate,
+    ) -> Result<Vec<([u8; 32], ChannelConfig)>> {
+        let channels = self.channel_manager.list_channels();
+        let selected = match channel_ids {
+            None => channels.iter().collect(),
+            Some(channel_ids) => channel_ids
+                .iter()
+                .map(|channel_id| {
+                    channels
+                        .iter()
+                        .find(|c| c.channel_id == *channel_id)
+                        .ok_or_else(|| {
+                            anyhow!("Channel {} not found", channel_id.encode_hex::<String>())
+                        })
+                })
+                .collect::<Result<Vec<&ChannelDetails>>>()?,
+        };
+        let mut applied = vec![];
+        for channel in selected {
+            let mut config = channel.config.unwrap_or_default();
+            update.apply(&mut config);
+            // The channel manager is persisted after this so the config survives restarts.
+            self.channel_manager
+                .update_channel_config(
+                    &channel.counterparty.node_id,
+                    &[channel.channel_id],
+                    &config,
+                )
+                .map_err(api_error)?;
+            applied.push((channel.channel_id, config));
+        }
+        Ok(applied)
+    }
+
+    async fn close_channel(
+        &self,
+        channel_id: &[u8; 32],
+        force: bool,
+    ) -> Result<CloseChannelResult> {
+        let channel = self
+            .channel_manager
+            .list_channels()
+            .into_iter()
+            .find(|c| c.channel_id == *channel_id)
+            .with_context(|| format!("Channel {} not found", channel_id.encode_hex::<String>()))?;
+        let funding_txo = channel
+            .funding_txo
+            .context("Channel has not been funded yet")?
+            .into_bitcoin_outpoint();
+        // The closing transaction may be broadcast before close returns so start listening first.
+        let receiver = self
+            .async_api_requests
+            .channel_closes
+            .insert(funding_txo)
+            .await;
+        let result = if force {
+            self.channel_manager
+                .force_close_broadcasting_latest_txn(channel_id, &channel.counterparty.node_id)
+        } else {
+            self.channel_manager
+                .close_channel(channel_id, &channel.counterparty.node_id)
+        };
+        if let Err(e) = result {
+            self.async_api_requests
+                .channel_closes
+                .remove(&funding_txo)
+                .await;
+            return Err(api_error(e));
+        }
+        let transaction = match tokio::time::timeout(CLOSE_CHANNEL_TIMEOUT, receiver).await {
+            Ok(transaction) => Some(transaction?),
+            Err(_) => {
+                // LDK goes on closing the channel, we just stop waiting for it.
+                self.async_api_requests
+                    .channel_closes
+                    .remove(&funding_txo)
+                    .await;
+                None
+            }
+        };
+        Ok(CloseChannelResult {
+            channel_id: *channel_id,
+            transaction,
+        })
+    }
+
+    async fn create_invoice(
+        &self,
+        amount_msat: Option<u64>,
+        description: String,
+        expiry_secs: u32,
+    ) -> Result<Invoice> {
+        let bolt11 = utils::create_invoice_from_channelmanager(
+            &self.channel_manager,
+            self.keys_manager.clone(),
+            KndLogger::global(),
+            currency(self.settings.bitcoin_network),
+            amount_msat,
+            description.clone(),
+            expiry_secs,
+        )
+        .map_err(|e| anyhow!("Failed to create invoice: {}", e))?;
+        let invoice = Invoice {
+            payment_hash: PaymentHash(bolt11.payment_hash().into_inner()),
+            payment_secret: *bolt11.payment_secret(),
+            bolt11: bolt11.to_string(),
+            amount_msat,
+            description,
+            expires_at: SystemTime::UNIX_EPOCH
+                + bolt11.duration_since_epoch()
+                + bolt11.expiry_time(),
+            status: InvoiceStatus::Unpaid,
+            preimage: None,
+            amount_received_msat: None,
+            paid_at: None,
+        };
+        self.database.persist_invoice(&invoice).await?;
+        Ok(invoice)
+    }
+
+    async fn list_invoices(&self, payment_hash: Option<PaymentHash>) -> Result<Vec<Invoice>> {
+        match payment_hash {
+            Some(payment_hash) => Ok(self
+                .database
+                .fetch_invoice(&payment_hash)
+                .await?
+                .into_iter()
+                .collect()),
+            None => self.database.fetch_invoices().await,
+        }
+    }
+
+    async fn pay_invoice(
+        &self,
+        invoice: lightning_invoice::Invoice,
+        amount_msat: Option<u64>,
+    ) -> Result<PaymentOutcome> {
+        if invoice.currency() != currency(self.settings.bitcoin_network) {
+            bail!("Invoice is for a different network");
+        }
+        let payment_hash = PaymentHash(invoice.payment_hash().into_inner());
+        let amount_msat = invoice.amount_milli_satoshis().or(amount_msat);
+        self.send_payment(
+            payment_hash,
+            Some(*invoice.payment_secret()),
+            amount_msat,
+            || {
+                payment_sent(match (invoice.amount_milli_satoshis(), amount_msat) {
+                    (None, Some(amount_msat)) => self
+                        .invoice_payer
+                        .pay_zero_value_invoice(&invoice, amount_msat),
+                    _ => self.invoice_payer.pay_invoice(&invoice),
+                })
+            },
+        )
+        .await
+    }
+
+    async fn list_forwards(
+        &self,
+        start: Option<SystemTime>,
+        end: Option<SystemTime>,
+    ) -> Result<Vec<Forward>> {
+        self.database.fetch_forwards(start, end).await
+    }
+
+    async fn list_payments(
+        &self,
+        payment_hash: Option<PaymentHash>,
+        direction: Option<PaymentDirection>,
+        status: Option<PaymentStatus>,
+    ) -> Result<Vec<Payment>> {
+        self.database
+            .fetch_payments(payment_hash.as_ref(), direction, status)
+            .await
+    }
+
+    async fn keysend(
+        &self,
+        payee_pubkey: PublicKey,
+        amount_msat: u64,
+    ) -> Result<(PaymentHash, PaymentOutcome)> {
+        let payment_preimage = PaymentPreimage(self.keys_manager.get_secure_random_bytes());
+        let payment_hash = PaymentHash(
+            <sha256::Hash as bitcoin::hashes::Hash>::hash(&payment_preimage.0).into_inner(),
+        );
+        let outcome = self
+            .send_payment(payment_hash, None, Some(amount_msat), || {
+                payment_sent(self.invoice_payer.pay_pubkey(
+                    payee_pubkey,
+                    payment_preimage,
+                    amount_msat,
+                    KEYSEND_FINAL_CLTV_EXPIRY_DELTA,
+                ))
+            })
+            .await?;
+        Ok((payment_hash, outcome))
+    }
+
+    async fn connect_peer(&self, public_key: PublicKey, peer_address: SocketAddr) -> Result<()> {
+        do_connect_peer(public_key, peer_address, self.peer_manager.clone())
+            .await
+            .map_err(|_| anyhow!("Failed to connect to peer {}", public_key))?;
+        self.database
+            .persist_peer(&Peer {
+                public_key,
+                socket_addr: peer_address,
+            })
+            .await
+    }
+
+    async fn list_peers(&self) -> Result<Vec<api::Peer>> {
+        let connected = self.peer_manager.get_peer_node_ids();
+        let stored = self.database.fetch_peers().await?;
+        let mut peers: Vec<(PublicKey, Option<SocketAddr>)> = stored
+            .iter()
+            .map(|peer| (peer.public_key, Some(peer.socket_addr)))
+            .collect();
+        for public_key in &connected {
+            if !stored.iter().any(|peer| peer.public_key == *public_key) {
+                peers.push((*public_key, None));
+            }
+        }
+        let graph = self.network_graph.read_only();
+        Ok(peers
+            .into_iter()
+            .map(|(public_key, socket_addr)| api::Peer {
+                public_key,
+                socket_addr,
+                connected: connected.contains(&public_key),
+                node_features: graph
+                    .node(&NodeId::from_pubkey(&public_key))
+                    .and_then(|node| node.announcement_info.as_ref())
+                    .map(|info| info.features.clone()),
+            })
+            .collect())
+    }
+
+    async fn disconnect_peer(&self, public_key: PublicKey, forget: bool) -> Result<()> {
+        if forget {
+            for peer in self.database.fetch_peers().await? {
+                if peer.public_key == public_key {
+                    self.database.delete_peer(&peer).await?;
+                }
+            }
+        }
+        self.peer_manager.disconnect_by_node_id(public_key, false);
+        Ok(())
+    }
+
     fn get_node(&self, public_key: PublicKey) -> Option<gossip::NodeInfo> {
         self.network_graph
             .read_only()
             .node(&NodeId::from_pubkey(&public_key))
             .cloned()
     }
+
+    fn graph_nodes(&self, after: Option<NodeId>, limit: usize) -> Vec<(NodeId, gossip::NodeInfo)> {
+        let graph = self.network_graph.read_only();
+        let start = match after {
+            Some(after) => Bound::Excluded(after),
+            None => Bound::Unbounded,
+        };
+        graph
+            .nodes()
+            .range((start, Bound::Unbounded))
+            .take(limit)
+            .map(|(id, node)| (*id, node.clone()))
+            .collect()
+    }
+
+    fn graph_channels(&self, after: Option<u64>, limit: usize) -> Vec<(u64, gossip::ChannelInfo)> {
+        let graph = self.network_graph.read_only();
+        let start = match after {
+            Some(after) => Bound::Excluded(after),
+            None => Bound::Unbounded,
+        };
+        graph
+            .channels()
+            .range((start, Bound::Unbounded))
+            .take(limit)
+            .map(|(scid, channel)| (*scid, channel.clone()))
+            .collect()
+    }
+
+    fn graph_channel(&self, short_channel_id: u64) -> Option<gossip::ChannelInfo> {
+        self.network_graph
+            .read_only()
+            .channel(short_channel_id)
+            .cloned()
+    }
+
+    fn find_route(
+        &self,
+        payee: PublicKey,
+        amount_msat: u64,
+        final_cltv_expiry_delta: u32,
+    ) -> Result<Route> {
+        let route_params = RouteParameters {
+            payment_params: PaymentParameters::from_node_id(payee),
+            final_value_msat: amount_msat,
+            final_cltv_expiry_delta,
+        };
+        let first_hops = self.channel_manager.list_usable_channels();
+        self.query_router
+            .find_route(
+                &self.identity_pubkey(),
+                &route_params,
+                Some(&first_hops.iter().collect::<Vec<_>>()),
+                self.channel_manager.compute_inflight_htlcs(),
+            )
+            .map_err(|e| anyhow!(e.err))
+    }
+
+    async fn probe(
+        &self,
+        payment_params: PaymentParameters,
+        amount_msat: u64,
+        final_cltv_expiry_delta: u32,
+    ) -> Result<ProbeOutcome> {
+        let path = match find_probe_path(
+            &self.channel_manager,
+            &self.query_router,
+            payment_params,
+            amount_msat,
+            final_cltv_expiry_delta,
+        ) {
+            Ok(path) => path,
+            Err(e) => {
+                return Ok(ProbeOutcome::NoRoute {
+                    reason: e.to_string(),
+                })
+            }
+        };
+        let payment_id = send_probe(&self.channel_manager, path)?;
+        // The outcome takes a round trip through our peers so it comes after we register for it.
+        let receiver = self.async_api_requests.probes.insert(payment_id).await;
+        match tokio::time::timeout(PROBE_TIMEOUT, receiver).await {
+            Ok(outcome) => Ok(outcome?),
+            Err(_) => {
+                self.async_api_requests.probes.remove(&payment_id).await;
+                Ok(ProbeOutcome::TimedOut)
+            }
+        }
+    }
+
+    fn estimated_channel_liquidity_range(
+        &self,
+        short_channel_id: u64,
+        target: &NodeId,
+    ) -> Option<(u64, u64)> {
+        self.scorer
+            .lock()
+            .unwrap()
+            .estimated_channel_liquidity_range(short_channel_id, target)
+    }
+
+    async fn ban_node(&self, node_id: PublicKey) -> Result<()> {
+        self.database.persist_banned_node(&node_id).await?;
+        self.scorer
+            .lock()
+            .unwrap()
+            .add_banned(&NodeId::from_pubkey(&node_id));
+        Ok(())
+    }
+
+    async fn unban_node(&self, node_id: PublicKey) -> Result<()> {
+        self.database.delete_banned_node(&node_id).await?;
+        self.scorer
+            .lock()
+            .unwrap()
+            .remove_banned(&NodeId::from_pubkey(&node_id));
+        Ok(())
+    }
+
+    async fn reset_scorer(&self) -> Result<()> {
+        self.database.delete_scorer().await?;
+        let banned_nodes = self.database.fetch_banned_nodes().await?;
+        *self.scorer.lock().unwrap() = ProbabilisticScorer::new(
+            scoring_params(&self.settings, &banned_nodes),
+            self.network_graph.clone(),
+            KndLogger::global(),
+        );
+        Ok(())
+    }
+
+    async fn create_intercept_scid(&self, node_id: PublicKey) -> Result<RouteHintHop> {
+        if !self.settings.lsp_mode {
+            bail!("LSP mode is not enabled");
+        }
+        let short_channel_id = self.channel_manager.get_intercept_scid();
+        self.database
+            .persist_intercept_scid(short_channel_id, &node_id)
+            .await?;
+        let config = self
+            .channel_manager
+            .get_current_default_configuration()
+            .channel_config;
+        Ok(RouteHintHop {
+            src_node_id: self.identity_pubkey(),
+            short_channel_id,
+            fees: RoutingFees {
+                base_msat: config.forwarding_fee_base_msat,
+                proportional_millionths: config.forwarding_fee_proportional_millionths,
+            },
+            cltv_expiry_delta: config.cltv_expiry_delta,
+            htlc_minimum_msat: None,
+            htlc_maximum_msat: None,
+        })
+    }
 }
 
 pub struct AsyncAPIRequests {
-    pub channel_opens: AsyncSenders<u128, Transaction>,
+    pub channel_opens: AsyncSenders<u128, Result<Transaction>>,
+    // How to fund each channel we are opening.
+    pub channel_funding: RwLock<HashMap<u128, FundingOptions>>,
+    pub channel_closes: AsyncSenders<OutPoint, Transaction>,
+    pub payments: AsyncSenders<PaymentHash, PaymentOutcome>,
+    pub probes: AsyncSenders<PaymentId, ProbeOutcome>,
 }
 
 impl AsyncAPIRequests {
     fn new() -> AsyncAPIRequests {
         AsyncAPIRequests {
             channel_opens: AsyncSenders::new(),
+            channel_funding: RwLock::new(HashMap::new()),
+            channel_closes: AsyncSenders::new(),
+            payments: AsyncSenders::new(),
+            probes: AsyncSenders::new(),
         }
     }
 }
@@ -180,6 +705,10 @@ impl<K: Eq + Hash, V> AsyncSenders<K, V> {
         rx
     }
 
+    async fn remove(&self, k: &K) {
+        self.senders.write().await.remove(k);
+    }
+
     pub async fn send(&self, k: K, v: V) {
         if let Some(tx) = self.senders.write().await.remove(&k) {
             if tx.send(v).is_err() {
@@ -189,6 +718,40 @@ impl<K: Eq + Hash, V> AsyncSenders<K, V> {
     }
 }
 
+fn scoring_params(
+    settings: &Settings,
+    banned_nodes: &[PublicKey],
+) -> ProbabilisticScoringParameters {
+    let mut params = ProbabilisticScoringParameters {
+        base_penalty_msat: settings.scorer_base_penalty_msat,
+        base_penalty_amount_multiplier_msat: settings.scorer_base_penalty_amount_multiplier_msat,
+        liquidity_penalty_multiplier_msat: settings.scorer_liquidity_penalty_multiplier_msat,
+        liquidity_offset_half_life: Duration::from_secs(
+            settings.scorer_liquidity_offset_half_life_secs,
+        ),
+        liquidity_penalty_amount_multiplier_msat: settings
+            .scorer_liquidity_penalty_amount_multiplier_msat,
+        historical_liquidity_penalty_multiplier_msat: settings
+            .scorer_historical_liquidity_penalty_multiplier_msat,
+        historical_liquidity_penalty_amount_multiplier_msat: settings
+            .scorer_historical_liquidity_penalty_amount_multiplier_msat,
+        historical_no_updates_half_life: Duration::from_secs(
+            settings.scorer_historical_no_updates_half_life_secs,
+        ),
+        anti_probing_penalty_msat: settings.scorer_anti_probing_penalty_msat,
+        ..Default::default()
+    };
+    params.add_banned_from_list(
+        settings
+            .scorer_banned_nodes
+            .iter()
+            .chain(banned_nodes)
+            .map(NodeId::from_pubkey)
+            .collect(),
+    );
+    params
+}
+
 fn api_error(error: APIError) -> anyhow::Error {
     anyhow::Error::msg(match error {
         APIError::APIMisuseError { ref err } => format!("Misuse error: {}", err),
@@ -211,17 +774,90 @@ fn api_error(error: APIError) -> anyhow::Error {
     })
 }
 
+fn currency(network: Network) -> Currency {
+    match network {
+        Network::Bitcoin => Currency::Bitcoin,
+        Network::Testnet => Currency::BitcoinTestnet,
+        Network::Regtest => Currency::Regtest,
+        Network::Signet => Currency::Signet,
+    }
+}
+
+fn payment_sent(result: Result<PaymentId, PaymentError>) -> Result<(), String> {
+    match result {
+        // Some paths are still in flight so wait for the events.
+        Ok(_) | Err(PaymentError::Sending(PaymentSendFailure::PartialFailure { .. })) => Ok(()),
+        Err(PaymentError::Invoice(err)) => Err(format!("Invalid invoice: {}", err)),
+        Err(PaymentError::Routing(err)) => Err(format!("Failed to find route: {}", err.err)),
+        Err(PaymentError::Sending(PaymentSendFailure::ParameterError(err))) => {
+            Err(api_error(err).to_string())
+        }
+        Err(PaymentError::Sending(err)) => Err(format!("Failed to send payment: {:?}", err)),
+    }
+}
+
 pub struct Controller {
     settings: Arc<Settings>,
+    database: Arc<LdkDatabase>,
     bitcoind_client: Arc<Client>,
+    keys_manager: Arc<KndKeysManager>,
     channel_manager: Arc<ChannelManager>,
     peer_manager: Arc<PeerManager>,
     network_graph: Arc<NetworkGraph>,
+    query_router: Arc<Router>,
+    scorer: Arc<Mutex<Scorer>>,
     wallet: Arc<Wallet>,
+    invoice_payer: Arc<InvoicePayer<EventHandler>>,
     async_api_requests: Arc<AsyncAPIRequests>,
 }
 
 impl Controller {
+    /// Track an outbound payment and wait for the events that tell us how it ended.
+    async fn send_payment(
+        &self,
+        payment_hash: PaymentHash,
+        payment_secret: Option<PaymentSecret>,
+        amount_msat: Option<u64>,
+        send: impl FnOnce() -> Result<(), String>,
+    ) -> Result<PaymentOutcome> {
+        if let Some(Payment {
+            status: PaymentStatus::Pending,
+            ..
+        }) = self
+            .database
+            .fetch_payment(&payment_hash, PaymentDirection::Outbound)
+            .await?
+        {
+            bail!("Payment is already in progress");
+        }
+        let mut payment = Payment::new(
+            payment_hash,
+            PaymentDirection::Outbound,
+            PaymentStatus::Pending,
+            amount_msat,
+        );
+        payment.secret = payment_secret;
+        self.database.persist_payment(&payment).await?;
+        let receiver = self.async_api_requests.payments.insert(payment_hash).await;
+        if let Err(reason) = send() {
+            self.async_api_requests.payments.remove(&payment_hash).await;
+            payment.status = PaymentStatus::Failed;
+            self.database.persist_payment(&payment).await?;
+            return Ok(PaymentOutcome::Failed {
+                reason,
+                permanent: false,
+                short_channel_id: None,
+            });
+        }
+        match tokio::time::timeout(PAYMENT_TIMEOUT, receiver).await {
+            Ok(outcome) => Ok(outcome?),
+            Err(_) => {
+                self.async_api_requests.payments.remove(&payment_hash).await;
+                Ok(PaymentOutcome::Pending)
+            }
+        }
+    }
+
     pub fn stop(&self) {
         // Disconnect our peers and stop accepting new connections. This ensures we don't continue
         // updating our channel data after we've stopped the background processor.
@@ -263,15 +899,26 @@ impl Controller {
             fee_estimator.clone(),
             database.clone(),
         ));
+        let weak_chain_monitor = Arc::downgrade(&chain_monitor);
+        database.on_monitor_persisted(move |funding_txo, update_id| {
+            if let Some(chain_monitor) = weak_chain_monitor.upgrade() {
+                if let Err(e) = chain_monitor.channel_monitor_updated(funding_txo, update_id) {
+                    error!(
+                        "Failed to complete monitor update for {:?}: {:?}",
+                        funding_txo, e
+                    );
+                }
+            }
+        });
 
         let is_first_start = database.is_first_start().await?;
-        // Initialize the KeysManager
+        // Initialize the KndKeysManager
         // The key seed that we use to derive the node privkey (that corresponds to the node pubkey) and
         // other secret key material.
         let cur = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap();
-        let keys_manager = Arc::new(KeysManager::new(
+        let keys_manager = Arc::new(KndKeysManager::new(
             &key_generator.lightning_seed(),
             cur.as_secs(),
             cur.subsec_nanos(),
@@ -279,12 +926,14 @@ impl Controller {
 
         // Initialize the ChannelManager
         let mut channelmonitors = database
-            .fetch_channel_monitors(keys_manager.clone())
+            .fetch_channel_monitors(keys_manager.clone(), &broadcaster, &fee_estimator)
             .await?;
         let mut user_config = UserConfig::default();
         user_config
             .channel_handshake_limits
             .force_announced_channel_preference = false;
+        user_config.manually_accept_inbound_channels = true;
+        user_config.accept_intercept_htlcs = settings.lsp_mode;
         let (channel_manager_blockhash, channel_manager) = {
             if is_first_start {
                 let getinfo_resp = bitcoind_client.get_blockchain_info().await;
@@ -366,14 +1015,15 @@ impl Controller {
             );
         }
 
-        // Give ChannelMonitors to ChainMonitor
+        // Give ChannelMonitors to ChainMonitor, they are written again in the background.
         for item in chain_listener_channel_monitors.drain(..) {
             let channel_monitor = item.1 .0;
             let funding_outpoint = item.2;
-            assert_eq!(
-                chain_monitor.watch_channel(funding_outpoint, channel_monitor),
-                ChannelMonitorUpdateStatus::Completed
-            );
+            if chain_monitor.watch_channel(funding_outpoint, channel_monitor)
+                == ChannelMonitorUpdateStatus::PermanentFailure
+            {
+                bail!("Failed to watch channel {:?}", funding_outpoint);
+            }
         }
 
         // Initialize the P2PGossipSync
@@ -391,6 +1041,21 @@ impl Controller {
             KndLogger::global(),
         ));
 
+        // Bootstrap the graph from a Rapid Gossip Sync snapshot so a fresh node can route
+        // straight away instead of waiting for gossip from peers.
+        let rapid_gossip_sync = match &settings.rgs_snapshot {
+            Some(source) => {
+                let source = SnapshotSource::new(source);
+                let rapid_gossip_sync = Arc::new(RapidGossipSync::new(network_graph.clone()));
+                match apply_snapshot(&rapid_gossip_sync, &source).await {
+                    Ok(timestamp) => info!("Applied gossip snapshot from {}", timestamp),
+                    Err(e) => warn!("{}", e),
+                }
+                Some((rapid_gossip_sync, source))
+            }
+            None => None,
+        };
+
         // Initialize the PeerManager
         let channel_manager: Arc<ChannelManager> = Arc::new(channel_manager);
         let onion_messenger: Arc<OnionMessenger> = Arc::new(OnionMessenger::new(
@@ -474,33 +1139,53 @@ impl Controller {
         });
 
         let async_api_requests = Arc::new(AsyncAPIRequests::new());
+        // Closing transactions are only seen when broadcast so match them to close requests by the funding outpoint they spend.
+        let mut broadcasts = bitcoind_client.subscribe_broadcasts();
+        let broadcast_api_requests = async_api_requests.clone();
+        tokio::spawn(async move {
+            loop {
+                match broadcasts.recv().await {
+                    Ok(tx) => {
+                        for input in tx.input.iter() {
+                            broadcast_api_requests
+                                .channel_closes
+                                .send(input.previous_output, tx.clone())
+                                .await;
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Missed {} broadcast transactions", skipped)
+                    }
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        });
         // Handle LDK Events
-        // TODO: persist payment info to disk
-        let inbound_payments: PaymentInfoStorage = Arc::new(Mutex::new(HashMap::new()));
-        let outbound_payments: PaymentInfoStorage = Arc::new(Mutex::new(HashMap::new()));
         let event_handler = EventHandler::new(
             channel_manager.clone(),
             bitcoind_client.clone(),
             keys_manager.clone(),
-            inbound_payments,
-            outbound_payments,
             settings.bitcoin_network,
             network_graph.clone(),
             wallet.clone(),
+            database.clone(),
             async_api_requests.clone(),
+            ChannelAcceptor::new(&settings),
+            Arc::new(Interceptor::new(
+                channel_manager.clone(),
+                Duration::from_secs(settings.lsp_intercept_timeout_secs),
+            )),
         );
 
         // Initialize routing ProbabilisticScorer
+        let scoring_params = scoring_params(&settings, &database.fetch_banned_nodes().await?);
         let scorer = Arc::new(Mutex::new(
             database
-                .fetch_scorer(
-                    ProbabilisticScoringParameters::default(),
-                    network_graph.clone(),
-                )
+                .fetch_scorer(scoring_params.clone(), network_graph.clone())
                 .await?
                 .unwrap_or_else(|| {
                     ProbabilisticScorer::new(
-                        ProbabilisticScoringParameters::default(),
+                        scoring_params,
                         network_graph.clone(),
                         KndLogger::global(),
                     )
@@ -514,6 +1199,13 @@ impl Controller {
             keys_manager.get_secure_random_bytes(),
             scorer.clone(),
         );
+        // A second router over the same scorer for route queries from the API.
+        let query_router = Arc::new(DefaultRouter::new(
+            network_graph.clone(),
+            KndLogger::global(),
+            keys_manager.get_secure_random_bytes(),
+            scorer.clone(),
+        ));
         let invoice_payer = Arc::new(InvoicePayer::new(
             channel_manager.clone(),
             router,
@@ -528,22 +1220,58 @@ impl Controller {
             invoice_payer.clone(),
             chain_monitor.clone(),
             channel_manager.clone(),
-            GossipSync::p2p(gossip_sync.clone()),
+            match &rapid_gossip_sync {
+                Some((rapid_gossip_sync, _)) => GossipSync::Rapid(rapid_gossip_sync.clone()),
+                None => GossipSync::P2P(gossip_sync.clone()),
+            },
             peer_manager.clone(),
             KndLogger::global(),
-            Some(scorer),
+            Some(scorer.clone()),
         );
 
+        if let Some(probe_interval_secs) = settings.probe_interval_secs {
+            start_prober(
+                channel_manager.clone(),
+                network_graph.clone(),
+                query_router.clone(),
+                settings.probe_amount_msat,
+                Duration::from_secs(probe_interval_secs),
+                shutdown_flag.clone(),
+            );
+        }
+
+        // Regularly apply the latest gossip snapshot.
+        if let Some((rapid_gossip_sync, source)) = rapid_gossip_sync {
+            let stop_sync = shutdown_flag.clone();
+            let sync_interval = Duration::from_secs(settings.rgs_sync_interval_secs);
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(sync_interval);
+                // The first tick completes immediately and we just synced.
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    if stop_sync.load(Ordering::Acquire) {
+                        return;
+                    }
+                    match apply_snapshot(&rapid_gossip_sync, &source).await {
+                        Ok(timestamp) => info!("Applied gossip snapshot from {}", timestamp),
+                        Err(e) => warn!("{}", e),
+                    }
+                }
+            });
+        }
+
         // Regularly reconnect to channel peers.
         let connect_cm = channel_manager.clone();
         let connect_pm = peer_manager.clone();
         let stop_connect = shutdown_flag.clone();
+        let connect_database = database.clone();
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(1));
             loop {
                 interval.tick().await;
 
-                match database.fetch_peers().await {
+                match connect_database.fetch_peers().await {
                     Ok(peers) => {
                         let node_ids = connect_pm.get_peer_node_ids();
                         for node_id in connect_cm
@@ -602,11 +1330,16 @@ impl Controller {
         Ok((
             Controller {
                 settings,
+                database,
                 bitcoind_client,
+                keys_manager,
                 channel_manager,
                 peer_manager,
                 network_graph,
+                query_router,
+                scorer,
                 wallet,
+                invoice_payer,
                 async_api_requests,
             },
             background_processor,
@@ -623,26 +1356,31 @@ type ChainMonitor = chainmonitor::ChainMonitor<
     Arc<LdkDatabase>,
 >;
 
-pub(crate) type PeerManager = SimpleArcPeerManager<
+pub(crate) type PeerManager = peer_handler::PeerManager<
     SocketDescriptor,
-    ChainMonitor,
-    Client,
-    Client,
-    dyn chain::Access + Send + Sync,
-    KndLogger,
+    Arc<ChannelManager>,
+    Arc<P2PGossipSync<Arc<NetworkGraph>, Arc<dyn chain::Access + Send + Sync>, Arc<KndLogger>>>,
+    Arc<OnionMessenger>,
+    Arc<KndLogger>,
+    IgnoringMessageHandler,
 >;
 
-pub(crate) type ChannelManager = SimpleArcChannelManager<ChainMonitor, Client, Client, KndLogger>;
+pub(crate) type ChannelManager = channelmanager::ChannelManager<
+    Arc<ChainMonitor>,
+    Arc<Client>,
+    Arc<KndKeysManager>,
+    Arc<Client>,
+    Arc<KndLogger>,
+>;
 
 pub(crate) type InvoicePayer<E> =
     payment::InvoicePayer<Arc<ChannelManager>, Router, Arc<KndLogger>, E>;
 
-type Router = DefaultRouter<
-    Arc<NetworkGraph>,
-    Arc<KndLogger>,
-    Arc<Mutex<ProbabilisticScorer<Arc<NetworkGraph>, Arc<KndLogger>>>>,
->;
+pub(crate) type Router = DefaultRouter<Arc<NetworkGraph>, Arc<KndLogger>, Arc<Mutex<Scorer>>>;
+
+type Scorer = ProbabilisticScorer<Arc<NetworkGraph>, Arc<KndLogger>>;
 
 pub(crate) type NetworkGraph = gossip::NetworkGraph<Arc<KndLogger>>;
 
-type OnionMessenger = SimpleArcOnionMessenger<KndLogger>;
+type OnionMessenger =
+    onion_message::OnionMessenger<Arc<KndKeysManager>, Arc<KndLogger>, IgnoringMessageHandler>;
@@ -1,61 +1,90 @@
-use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 
 use bitcoin::network::constants::Network;
 use bitcoin::secp256k1::Secp256k1;
 use bitcoin_bech32::WitnessProgram;
+use database::forward::Forward;
+use database::invoice::InvoiceStatus;
+use database::ldk_database::LdkDatabase;
+use database::payment::{Payment, PaymentDirection, PaymentStatus};
 use hex::ToHex;
 use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
-use lightning::chain::keysinterface::KeysManager;
+use lightning::ln::PaymentHash;
 use lightning::routing::gossip::NodeId;
 use lightning::util::events::{Event, PaymentPurpose};
-use log::{error, info};
+use log::{error, info, warn};
 use rand::{thread_rng, Rng};
 use tokio::runtime::Handle;
 
+use crate::api::{PaymentOutcome, ProbeOutcome};
+use crate::channel_acceptor::{ChannelAcceptor, Decision};
 use crate::controller::{AsyncAPIRequests, ChannelManager, NetworkGraph};
-use crate::payment_info::{HTLCStatus, MillisatAmount, PaymentInfo, PaymentInfoStorage};
+use crate::keys_manager::KndKeysManager;
+use crate::lsp::Interceptor;
 use crate::wallet::Wallet;
 use bitcoind::Client;
 
 pub(crate) struct EventHandler {
     channel_manager: Arc<ChannelManager>,
     bitcoind_client: Arc<Client>,
-    keys_manager: Arc<KeysManager>,
-    inbound_payments: PaymentInfoStorage,
-    outbound_payments: PaymentInfoStorage,
+    keys_manager: Arc<KndKeysManager>,
     network: Network,
     network_graph: Arc<NetworkGraph>,
     wallet: Arc<Wallet>,
+    database: Arc<LdkDatabase>,
     async_api_requests: Arc<AsyncAPIRequests>,
+    channel_acceptor: ChannelAcceptor,
+    interceptor: Arc<Interceptor<Arc<ChannelManager>>>,
+    // The last failed path of each outbound payment, reported once the payment is abandoned.
+    payment_failures: Mutex<HashMap<PaymentHash, PaymentOutcome>>,
+    // The most hops and the amount delivered by the paths of each outbound payment that
+    // succeeded so far. The payment is reported once its paths delivered the whole amount.
+    successful_paths: Mutex<HashMap<PaymentHash, (usize, u64)>>,
 }
 
 impl EventHandler {
-    // TODO remove when payments storage is in database
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         channel_manager: Arc<ChannelManager>,
         bitcoind_client: Arc<Client>,
-        keys_manager: Arc<KeysManager>,
-        inbound_payments: PaymentInfoStorage,
-        outbound_payments: PaymentInfoStorage,
+        keys_manager: Arc<KndKeysManager>,
         network: Network,
         network_graph: Arc<NetworkGraph>,
         wallet: Arc<Wallet>,
+        database: Arc<LdkDatabase>,
         async_api_requests: Arc<AsyncAPIRequests>,
+        channel_acceptor: ChannelAcceptor,
+        interceptor: Arc<Interceptor<Arc<ChannelManager>>>,
     ) -> EventHandler {
         EventHandler {
             channel_manager,
             bitcoind_client,
             keys_manager,
-            inbound_payments,
-            outbound_payments,
             network,
             network_graph,
             wallet,
+            database,
             async_api_requests,
+            channel_acceptor,
+            interceptor,
+            payment_failures: Mutex::new(HashMap::new()),
+            successful_paths: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn outbound_payment(&self, payment_hash: &PaymentHash) -> Option<Payment> {
+        match self
+            .database
+            .fetch_payment(payment_hash, PaymentDirection::Outbound)
+            .await
+        {
+            Ok(payment) => payment,
+            Err(e) => {
+                error!("Failed to fetch outbound payment: {}", e);
+                None
+            }
         }
     }
 }
@@ -91,13 +120,38 @@ impl EventHandler {
                 )
                 .expect("Lightning funding tx should always be to a SegWit output")
                 .to_address();
-                let mut outputs = vec![HashMap::with_capacity(1)];
+                let mut outputs = [HashMap::with_capacity(1)];
                 outputs[0].insert(addr, channel_value_satoshis as f64 / 100_000_000.0);
 
-                let funding_tx = self
-                    .wallet
-                    .fund_tx(&output_script, &channel_value_satoshis)
-                    .unwrap();
+                let funding = self
+                    .async_api_requests
+                    .channel_funding
+                    .write()
+                    .await
+                    .remove(&user_channel_id)
+                    .unwrap_or_default();
+                let funding_tx = match self.wallet.fund_tx(
+                    &output_script,
+                    &channel_value_satoshis,
+                    &funding,
+                    self.channel_manager.current_best_block().height(),
+                ) {
+                    Ok(funding_tx) => funding_tx,
+                    Err(e) => {
+                        error!("Failed to fund channel: {}", e);
+                        if let Err(e) = self.channel_manager.force_close_without_broadcasting_txn(
+                            &temporary_channel_id,
+                            &counterparty_node_id,
+                        ) {
+                            error!("Failed to abandon unfunded channel: {:?}", e);
+                        }
+                        self.async_api_requests
+                            .channel_opens
+                            .send(user_channel_id, Err(e))
+                            .await;
+                        return;
+                    }
+                };
 
                 // Give the funding transaction back to LDK for opening the channel.
                 if self
@@ -113,28 +167,95 @@ impl EventHandler {
                 }
                 self.async_api_requests
                     .channel_opens
-                    .send(user_channel_id, funding_tx)
+                    .send(user_channel_id, Ok(funding_tx))
                     .await;
             }
             Event::ChannelReady {
-                channel_id: _,
-                user_channel_id: _,
-                counterparty_node_id: _,
+                channel_id,
+                user_channel_id,
+                counterparty_node_id,
                 channel_type: _,
-            } => {}
+            } => {
+                self.interceptor
+                    .channel_ready(user_channel_id, &channel_id, counterparty_node_id);
+            }
             Event::ChannelClosed {
                 channel_id,
                 reason,
-                user_channel_id: _,
+                user_channel_id,
             } => {
                 info!("EVENT: Channel {:?} closed due to: {}", channel_id, reason);
+                self.interceptor.channel_closed(user_channel_id);
             }
             Event::DiscardFunding { .. } => {
                 // A "real" node should probably "lock" the UTXOs spent in funding transactions until
                 // the funding transaction either confirms, or this event is generated.
             }
-            Event::OpenChannelRequest { .. } => {
-                // Unreachable, we don't set manually_accept_inbound_channels
+            Event::OpenChannelRequest {
+                temporary_channel_id,
+                counterparty_node_id,
+                funding_satoshis,
+                push_msat: _,
+                channel_type,
+            } => {
+                let pending_channels = self
+                    .channel_manager
+                    .list_channels()
+                    .iter()
+                    .filter(|c| {
+                        c.counterparty.node_id == counterparty_node_id
+                            && c.channel_id != temporary_channel_id
+                            && !c.is_channel_ready
+                    })
+                    .count();
+                let decision = self.channel_acceptor.decide(
+                    &counterparty_node_id,
+                    funding_satoshis,
+                    &channel_type,
+                    pending_channels,
+                );
+                let user_channel_id: u128 = thread_rng().gen();
+                let result = match decision {
+                    Decision::Accept => {
+                        info!(
+                            "EVENT: Accepted {} sat channel from {}",
+                            funding_satoshis, counterparty_node_id
+                        );
+                        self.channel_manager.accept_inbound_channel(
+                            &temporary_channel_id,
+                            &counterparty_node_id,
+                            user_channel_id,
+                        )
+                    }
+                    Decision::AcceptZeroConf => {
+                        info!(
+                            "EVENT: Accepted {} sat zero conf channel from {}",
+                            funding_satoshis, counterparty_node_id
+                        );
+                        self.channel_manager
+                            .accept_inbound_channel_from_trusted_peer_0conf(
+                                &temporary_channel_id,
+                                &counterparty_node_id,
+                                user_channel_id,
+                            )
+                    }
+                    Decision::Reject(reason) => {
+                        info!(
+                            "EVENT: Rejected {} sat channel from {}: {}",
+                            funding_satoshis, counterparty_node_id, reason
+                        );
+                        self.channel_manager.force_close_broadcasting_latest_txn(
+                            &temporary_channel_id,
+                            &counterparty_node_id,
+                        )
+                    }
+                };
+                if let Err(e) = result {
+                    warn!(
+                        "Failed to handle channel request from {}: {:?}",
+                        counterparty_node_id, e
+                    );
+                }
             }
             Event::PaymentClaimable {
                 payment_hash,
@@ -176,23 +297,32 @@ impl EventHandler {
                     } => (payment_preimage, Some(payment_secret)),
                     PaymentPurpose::SpontaneousPayment(preimage) => (Some(preimage), None),
                 };
-                let mut payments = self.inbound_payments.lock().unwrap();
-                match payments.entry(payment_hash) {
-                    Entry::Occupied(mut e) => {
-                        let payment = e.get_mut();
-                        payment.status = HTLCStatus::Succeeded;
-                        payment.preimage = payment_preimage;
-                        payment.secret = payment_secret;
-                    }
-                    Entry::Vacant(e) => {
-                        e.insert(PaymentInfo {
-                            preimage: payment_preimage,
-                            secret: payment_secret,
-                            status: HTLCStatus::Succeeded,
-                            amt_msat: MillisatAmount(Some(amount_msat)),
-                        });
+                if payment_secret.is_some() {
+                    match self.database.fetch_invoice(&payment_hash).await {
+                        Ok(Some(mut invoice)) => {
+                            invoice.status = InvoiceStatus::Paid;
+                            invoice.preimage = payment_preimage;
+                            invoice.amount_received_msat = Some(amount_msat);
+                            invoice.paid_at = Some(SystemTime::now());
+                            if let Err(e) = self.database.persist_invoice(&invoice).await {
+                                error!("Failed to mark invoice as paid: {}", e);
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(e) => error!("Failed to fetch invoice: {}", e),
                     }
                 }
+                let mut payment = Payment::new(
+                    payment_hash,
+                    PaymentDirection::Inbound,
+                    PaymentStatus::Succeeded,
+                    Some(amount_msat),
+                );
+                payment.preimage = payment_preimage;
+                payment.secret = payment_secret;
+                if let Err(e) = self.database.persist_payment(&payment).await {
+                    error!("Failed to persist inbound payment: {}", e);
+                }
             }
             Event::PaymentSent {
                 payment_preimage,
@@ -200,39 +330,151 @@ impl EventHandler {
                 fee_paid_msat,
                 ..
             } => {
-                let mut payments = self.outbound_payments.lock().unwrap();
-                if let Some(payment) = payments.get_mut(&payment_hash) {
-                    payment.preimage = Some(payment_preimage);
-                    payment.status = HTLCStatus::Succeeded;
-                    info!(
-                        "EVENT: successfully sent payment of {} millisatoshis{} from \
+                let mut payment = match self.outbound_payment(&payment_hash).await {
+                    Some(payment) => payment,
+                    None => Payment::new(
+                        payment_hash,
+                        PaymentDirection::Outbound,
+                        PaymentStatus::Succeeded,
+                        None,
+                    ),
+                };
+                payment.preimage = Some(payment_preimage);
+                payment.status = PaymentStatus::Succeeded;
+                payment.fee_msat = fee_paid_msat;
+                info!(
+                    "EVENT: successfully sent payment of {} millisatoshis{} from \
 								 payment hash {:?} with preimage {:?}",
-                        payment.amt_msat,
-                        if let Some(fee) = fee_paid_msat {
-                            format!(" (fee {} msat)", fee)
+                    payment
+                        .amount_msat
+                        .map_or("unknown".to_string(), |amt| amt.to_string()),
+                    if let Some(fee) = fee_paid_msat {
+                        format!(" (fee {} msat)", fee)
+                    } else {
+                        "".to_string()
+                    },
+                    payment_hash.0.encode_hex::<String>(),
+                    payment_preimage.0.encode_hex::<String>()
+                );
+                if let Err(e) = self.database.persist_payment(&payment).await {
+                    error!("Failed to persist outbound payment: {}", e);
+                }
+            }
+            Event::PaymentPathSuccessful {
+                payment_hash: Some(payment_hash),
+                path,
+                ..
+            } => {
+                // PaymentSent comes first so by now we know the preimage and fee.
+                let outcome = match self.outbound_payment(&payment_hash).await {
+                    Some(Payment {
+                        status: PaymentStatus::Succeeded,
+                        preimage: Some(preimage),
+                        fee_msat,
+                        amount_msat,
+                        ..
+                    }) => {
+                        let mut successful_paths = self.successful_paths.lock().unwrap();
+                        let (hops, delivered_msat) =
+                            successful_paths.entry(payment_hash).or_default();
+                        *hops = (*hops).max(path.len());
+                        *delivered_msat += path.last().map_or(0, |hop| hop.fee_msat);
+                        if amount_msat.is_some_and(|amount_msat| *delivered_msat < amount_msat) {
+                            None
                         } else {
-                            "".to_string()
-                        },
-                        payment_hash.0.encode_hex::<String>(),
-                        payment_preimage.0.encode_hex::<String>()
-                    );
+                            let hops = *hops;
+                            successful_paths.remove(&payment_hash);
+                            Some(PaymentOutcome::Succeeded {
+                                preimage,
+                                fee_paid_msat: fee_msat,
+                                hops,
+                            })
+                        }
+                    }
+                    _ => None,
+                };
+                if let Some(outcome) = outcome {
+                    self.async_api_requests
+                        .payments
+                        .send(payment_hash, outcome)
+                        .await;
                 }
             }
             Event::PaymentPathSuccessful { .. } => {}
-            Event::PaymentPathFailed { .. } => {}
-            Event::ProbeSuccessful { .. } => {}
-            Event::ProbeFailed { .. } => {}
+            Event::PaymentPathFailed {
+                payment_hash,
+                payment_failed_permanently,
+                short_channel_id,
+                ..
+            } => {
+                // Only reaches us once the InvoicePayer has stopped retrying.
+                let reason = if payment_failed_permanently {
+                    "Payment rejected by destination"
+                } else {
+                    "Exhausted payment retry attempts"
+                };
+                self.payment_failures.lock().unwrap().insert(
+                    payment_hash,
+                    PaymentOutcome::Failed {
+                        reason: reason.to_string(),
+                        permanent: payment_failed_permanently,
+                        short_channel_id,
+                    },
+                );
+            }
+            Event::ProbeSuccessful {
+                payment_id,
+                payment_hash: _,
+                path,
+            } => {
+                self.async_api_requests
+                    .probes
+                    .send(payment_id, ProbeOutcome::Succeeded { path })
+                    .await;
+            }
+            Event::ProbeFailed {
+                payment_id,
+                payment_hash: _,
+                path,
+                short_channel_id,
+            } => {
+                self.async_api_requests
+                    .probes
+                    .send(
+                        payment_id,
+                        ProbeOutcome::Failed {
+                            path,
+                            short_channel_id,
+                        },
+                    )
+                    .await;
+            }
             Event::PaymentFailed { payment_hash, .. } => {
                 info!(
 				"EVENT: Failed to send payment to payment hash {:?}: exhausted payment retry attempts",
 				payment_hash.0.encode_hex::<String>()
 			);
 
-                let mut payments = self.outbound_payments.lock().unwrap();
-                if payments.contains_key(&payment_hash) {
-                    let payment = payments.get_mut(&payment_hash).unwrap();
-                    payment.status = HTLCStatus::Failed;
+                if let Some(mut payment) = self.outbound_payment(&payment_hash).await {
+                    payment.status = PaymentStatus::Failed;
+                    if let Err(e) = self.database.persist_payment(&payment).await {
+                        error!("Failed to persist outbound payment: {}", e);
+                    }
                 }
+                let outcome = self
+                    .payment_failures
+                    .lock()
+                    .unwrap()
+                    .remove(&payment_hash)
+                    .unwrap_or(PaymentOutcome::Failed {
+                        reason: "Payment abandoned".to_string(),
+                        permanent: false,
+                        short_channel_id: None,
+                    });
+                self.async_api_requests
+                    .payments
+                    .send(payment_hash, outcome)
+                    .await;
             }
             Event::PaymentForwarded {
                 prev_channel_id,
@@ -294,6 +536,15 @@ impl EventHandler {
                         from_prev_str, to_next_str, from_onchain_str
                     );
                 }
+                let forward = Forward::new(
+                    prev_channel_id,
+                    next_channel_id,
+                    fee_earned_msat,
+                    claim_from_onchain_tx,
+                );
+                if let Err(e) = self.database.persist_forward(&forward).await {
+                    error!("Failed to persist forward: {}", e);
+                }
             }
             Event::HTLCHandlingFailed { .. } => {}
             Event::PendingHTLCsForwardable { time_forwardable } => {
@@ -324,12 +575,35 @@ impl EventHandler {
                 self.bitcoind_client.broadcast_transaction(&spending_tx);
             }
             Event::HTLCIntercepted {
-                intercept_id: _,
-                requested_next_hop_scid: _,
-                payment_hash: _,
+                intercept_id,
+                requested_next_hop_scid,
+                payment_hash,
                 inbound_amount_msat: _,
-                expected_outbound_amount_msat: _,
-            } => {}
+                expected_outbound_amount_msat,
+            } => match self
+                .database
+                .fetch_intercept_scid(requested_next_hop_scid)
+                .await
+            {
+                Ok(Some(node_id)) => self.interceptor.htlc_intercepted(
+                    intercept_id,
+                    node_id,
+                    payment_hash,
+                    expected_outbound_amount_msat,
+                ),
+                Ok(None) => {
+                    info!(
+                        "Failing intercepted HTLC {} to unknown scid {}",
+                        payment_hash.0.encode_hex::<String>(),
+                        requested_next_hop_scid
+                    );
+                    self.interceptor.fail(intercept_id);
+                }
+                Err(e) => {
+                    error!("Failed to fetch intercept scid: {}", e);
+                    self.interceptor.fail(intercept_id);
+                }
+            },
         }
     }
 }
@@ -39,6 +39,11 @@ impl KeyGenerator {
         self.generate_key("macaroon/0")
     }
 
+    /// Encrypts what we store in the database.
+    pub fn database_key(&self) -> [u8; 32] {
+        self.generate_key("database/0")
+    }
+
     fn generate_key(&self, extra_input: &str) -> [u8; 32] {
         let mut engine = sha256::HashEngine::default();
         engine.input(&self.seed);
@@ -54,11 +59,14 @@ fn test_key_generator() {
     let wallet_seed = key_generator.wallet_seed();
     let lightning_seed = key_generator.lightning_seed();
     let macaroon_seed = key_generator.macaroon_seed();
+    let database_key = key_generator.database_key();
 
     assert_eq!(wallet_seed, key_generator.wallet_seed());
     assert_eq!(lightning_seed, key_generator.lightning_seed());
     assert_eq!(macaroon_seed, key_generator.macaroon_seed());
+    assert_eq!(database_key, key_generator.database_key());
 
     assert_ne!(wallet_seed, lightning_seed);
     assert_ne!(lightning_seed, macaroon_seed);
+    assert_ne!(macaroon_seed, database_key);
 }
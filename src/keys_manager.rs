@@ -0,0 +1,190 @@
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+
+use bitcoin::bech32::u5;
+use bitcoin::secp256k1::ecdh::SharedSecret;
+use bitcoin::secp256k1::ecdsa::RecoverableSignature;
+use bitcoin::secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey, Signing};
+use bitcoin::{Script, Transaction, TxOut};
+use lightning::chain::keysinterface::{
+    InMemorySigner, KeyMaterial, KeysInterface, KeysManager, Recipient, SpendableOutputDescriptor,
+};
+use lightning::ln::msgs::DecodeError;
+use lightning::ln::script::ShutdownScript;
+
+/// Wraps the LDK KeysManager so a channel can commit upfront to a shutdown script chosen
+/// by the user instead of one from our own keys.
+pub struct KndKeysManager {
+    inner: KeysManager,
+    // Handed out by the next call to get_shutdown_scriptpubkey on the thread creating the
+    // channel. LDK calls it from other threads for inbound channels and closes meanwhile.
+    next_shutdown_script: Mutex<Option<(ThreadId, ShutdownScript)>>,
+    // Held while a channel is created with its own shutdown script.
+    shutdown_script_lock: Mutex<()>,
+}
+
+impl KndKeysManager {
+    pub fn new(seed: &[u8; 32], starting_time_secs: u64, starting_time_nanos: u32) -> Self {
+        KndKeysManager {
+            inner: KeysManager::new(seed, starting_time_secs, starting_time_nanos),
+            next_shutdown_script: Mutex::new(None),
+            shutdown_script_lock: Mutex::new(()),
+        }
+    }
+
+    /// Run `f` with `shutdown_script` returned as our shutdown script. LDK asks for it
+    /// synchronously while creating a channel so a channel created inside `f` commits to it.
+    /// Also returns whether the script was handed out, the caller must not use the channel
+    /// otherwise.
+    pub fn with_shutdown_script<T>(
+        &self,
+        shutdown_script: Option<ShutdownScript>,
+        f: impl FnOnce() -> T,
+    ) -> (T, bool) {
+        let shutdown_script = match shutdown_script {
+            Some(shutdown_script) => shutdown_script,
+            None => return (f(), false),
+        };
+        let _guard = self.shutdown_script_lock.lock().unwrap();
+        *self.next_shutdown_script.lock().unwrap() =
+            Some((thread::current().id(), shutdown_script));
+        let result = f();
+        // Don't leak it to the next channel if LDK never asked for it.
+        let handed_out = self.next_shutdown_script.lock().unwrap().take().is_none();
+        (result, handed_out)
+    }
+
+    pub fn spend_spendable_outputs<C: Signing>(
+        &self,
+        descriptors: &[&SpendableOutputDescriptor],
+        outputs: Vec<TxOut>,
+        change_destination_script: Script,
+        feerate_sat_per_1000_weight: u32,
+        secp_ctx: &Secp256k1<C>,
+    ) -> Result<Transaction, ()> {
+        self.inner.spend_spendable_outputs(
+            descriptors,
+            outputs,
+            change_destination_script,
+            feerate_sat_per_1000_weight,
+            secp_ctx,
+        )
+    }
+}
+
+impl KeysInterface for KndKeysManager {
+    type Signer = InMemorySigner;
+
+    fn get_node_secret(&self, recipient: Recipient) -> Result<SecretKey, ()> {
+        self.inner.get_node_secret(recipient)
+    }
+
+    fn get_node_id(&self, recipient: Recipient) -> Result<PublicKey, ()> {
+        self.inner.get_node_id(recipient)
+    }
+
+    fn ecdh(
+        &self,
+        recipient: Recipient,
+        other_key: &PublicKey,
+        tweak: Option<&Scalar>,
+    ) -> Result<SharedSecret, ()> {
+        self.inner.ecdh(recipient, other_key, tweak)
+    }
+
+    fn get_destination_script(&self) -> Script {
+        self.inner.get_destination_script()
+    }
+
+    fn get_shutdown_scriptpubkey(&self) -> ShutdownScript {
+        let mut next_shutdown_script = self.next_shutdown_script.lock().unwrap();
+        match &*next_shutdown_script {
+            Some((thread_id, _)) if *thread_id == thread::current().id() => {
+                next_shutdown_script.take().unwrap().1
+            }
+            _ => self.inner.get_shutdown_scriptpubkey(),
+        }
+    }
+
+    fn generate_channel_keys_id(
+        &self,
+        inbound: bool,
+        channel_value_satoshis: u64,
+        user_channel_id: u128,
+    ) -> [u8; 32] {
+        self.inner
+            .generate_channel_keys_id(inbound, channel_value_satoshis, user_channel_id)
+    }
+
+    fn derive_channel_signer(
+        &self,
+        channel_value_satoshis: u64,
+        channel_keys_id: [u8; 32],
+    ) -> Self::Signer {
+        self.inner
+            .derive_channel_signer(channel_value_satoshis, channel_keys_id)
+    }
+
+    fn get_secure_random_bytes(&self) -> [u8; 32] {
+        self.inner.get_secure_random_bytes()
+    }
+
+    fn read_chan_signer(&self, reader: &[u8]) -> Result<Self::Signer, DecodeError> {
+        self.inner.read_chan_signer(reader)
+    }
+
+    fn sign_invoice(
+        &self,
+        hrp_bytes: &[u8],
+        invoice_data: &[u5],
+        recipient: Recipient,
+    ) -> Result<RecoverableSignature, ()> {
+        self.inner.sign_invoice(hrp_bytes, invoice_data, recipient)
+    }
+
+    fn get_inbound_payment_key_material(&self) -> KeyMaterial {
+        self.inner.get_inbound_payment_key_material()
+    }
+}
+
+#[test]
+fn test_with_shutdown_script() {
+    let keys_manager = KndKeysManager::new(&[1; 32], 0, 0);
+    let default_script = keys_manager.get_shutdown_scriptpubkey().into_inner();
+    let shutdown_script =
+        ShutdownScript::new_p2wpkh(&<bitcoin::WPubkeyHash as bitcoin::hashes::Hash>::all_zeros());
+
+    let (handed_out, consumed) = keys_manager
+        .with_shutdown_script(Some(shutdown_script.clone()), || {
+            keys_manager.get_shutdown_scriptpubkey()
+        });
+    assert!(consumed);
+    assert_eq!(
+        shutdown_script.clone().into_inner(),
+        handed_out.into_inner()
+    );
+    assert_eq!(
+        default_script,
+        keys_manager.get_shutdown_scriptpubkey().into_inner()
+    );
+
+    // Other threads, e.g. accepting an inbound channel meanwhile, get our own script.
+    let (other_thread, consumed) =
+        keys_manager.with_shutdown_script(Some(shutdown_script.clone()), || {
+            thread::scope(|s| {
+                s.spawn(|| keys_manager.get_shutdown_scriptpubkey())
+                    .join()
+                    .unwrap()
+            })
+        });
+    assert!(!consumed);
+    assert_eq!(default_script, other_thread.into_inner());
+
+    // Not asked for inside the closure so it must not be used by a later channel.
+    let ((), consumed) = keys_manager.with_shutdown_script(Some(shutdown_script), || ());
+    assert!(!consumed);
+    assert_eq!(
+        default_script,
+        keys_manager.get_shutdown_scriptpubkey().into_inner()
+    );
+}
@@ -1,11 +1,15 @@
 // This lib.rs is just to help with integration testing.
 pub mod api;
+mod channel_acceptor;
 pub mod controller;
 mod event_handler;
 pub mod key_generator;
+mod keys_manager;
+pub mod lsp;
 mod net_utils;
-mod payment_info;
+mod prober;
 pub mod prometheus;
+mod rapid_gossip_sync;
 pub mod wallet;
 
 pub const VERSION: &str = concat!("LYND v", env!("CARGO_PKG_VERSION"));
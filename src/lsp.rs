@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bitcoin::secp256k1::PublicKey;
+use hex::ToHex;
+use lightning::chain;
+use lightning::chain::chaininterface::{BroadcasterInterface, FeeEstimator};
+use lightning::chain::keysinterface::KeysInterface;
+use lightning::ln::channelmanager::{self, ChannelDetails, InterceptId};
+use lightning::ln::PaymentHash;
+use lightning::util::config::UserConfig;
+use lightning::util::errors::APIError;
+use lightning::util::logger::Logger;
+use log::{info, warn};
+use rand::random;
+
+// On top of the payment so the channel reserve and commitment fee don't eat into it.
+const JIT_CHANNEL_BUFFER_SATS: u64 = 20_000;
+
+struct InterceptedHtlc {
+    intercept_id: InterceptId,
+    amount_msat: u64,
+}
+
+/// The channel manager calls the interceptor makes, so it can also drive LDK's test nodes.
+pub trait InterceptChannelManager {
+    fn list_usable_channels(&self) -> Vec<ChannelDetails>;
+    fn get_current_default_configuration(&self) -> &UserConfig;
+    fn create_channel(
+        &self,
+        their_network_key: PublicKey,
+        channel_value_satoshis: u64,
+        push_msat: u64,
+        user_channel_id: u128,
+        override_config: Option<UserConfig>,
+    ) -> Result<[u8; 32], APIError>;
+    fn forward_intercepted_htlc(
+        &self,
+        intercept_id: InterceptId,
+        next_hop_channel_id: &[u8; 32],
+        next_node_id: PublicKey,
+        amt_to_forward_msat: u64,
+    ) -> Result<(), APIError>;
+    fn fail_intercepted_htlc(&self, intercept_id: InterceptId) -> Result<(), APIError>;
+}
+
+impl<M: Deref, T: Deref, K: Deref, F: Deref, L: Deref> InterceptChannelManager
+    for channelmanager::ChannelManager<M, T, K, F, L>
+where
+    M::Target: chain::Watch<<K::Target as KeysInterface>::Signer>,
+    T::Target: BroadcasterInterface,
+    K::Target: KeysInterface,
+    F::Target: FeeEstimator,
+    L::Target: Logger,
+{
+    fn list_usable_channels(&self) -> Vec<ChannelDetails> {
+        channelmanager::ChannelManager::list_usable_channels(self)
+    }
+
+    fn get_current_default_configuration(&self) -> &UserConfig {
+        channelmanager::ChannelManager::get_current_default_configuration(self)
+    }
+
+    fn create_channel(
+        &self,
+        their_network_key: PublicKey,
+        channel_value_satoshis: u64,
+        push_msat: u64,
+        user_channel_id: u128,
+        override_config: Option<UserConfig>,
+    ) -> Result<[u8; 32], APIError> {
+        channelmanager::ChannelManager::create_channel(
+            self,
+            their_network_key,
+            channel_value_satoshis,
+            push_msat,
+            user_channel_id,
+            override_config,
+        )
+    }
+
+    fn forward_intercepted_htlc(
+        &self,
+        intercept_id: InterceptId,
+        next_hop_channel_id: &[u8; 32],
+        next_node_id: PublicKey,
+        amt_to_forward_msat: u64,
+    ) -> Result<(), APIError> {
+        channelmanager::ChannelManager::forward_intercepted_htlc(
+            self,
+            intercept_id,
+            next_hop_channel_id,
+            next_node_id,
+            amt_to_forward_msat,
+        )
+    }
+
+    fn fail_intercepted_htlc(&self, intercept_id: InterceptId) -> Result<(), APIError> {
+        channelmanager::ChannelManager::fail_intercepted_htlc(self, intercept_id)
+    }
+}
+
+/// Forwards HTLCs sent to our intercept scids, opening a channel to the recipient
+/// first when we don't have one that can carry the payment.
+///
+/// The HTLC only waits `timeout` for the new channel, so the recipient has to accept our
+/// channels with zero confirmations (minimum_depth 0). An LDK recipient does that with
+/// `accept_inbound_channel_from_trusted_peer_0conf` and KND with KND_CHANNEL_ZERO_CONF_PEERS.
+pub struct Interceptor<C: Deref> {
+    channel_manager: C,
+    timeout: Duration,
+    // HTLCs waiting for the channel with this user_channel_id to become ready.
+    pending: Mutex<HashMap<u128, Vec<InterceptedHtlc>>>,
+}
+
+impl<C> Interceptor<C>
+where
+    C: Deref,
+    C::Target: InterceptChannelManager,
+{
+    pub fn new(channel_manager: C, timeout: Duration) -> Interceptor<C> {
+        Interceptor {
+            channel_manager,
+            timeout,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Forwards the HTLC or opens a channel for it. Returns the user_channel_id of the
+    /// channel when the HTLC waits for it, which must be expired after the timeout.
+    pub fn intercept(
+        &self,
+        intercept_id: InterceptId,
+        node_id: PublicKey,
+        payment_hash: PaymentHash,
+        expected_outbound_amount_msat: u64,
+    ) -> Option<u128> {
+        if let Some(channel) = self
+            .channel_manager
+            .list_usable_channels()
+            .iter()
+            .find(|c| {
+                c.counterparty.node_id == node_id
+                    && c.next_outbound_htlc_limit_msat >= expected_outbound_amount_msat
+            })
+        {
+            self.forward(
+                intercept_id,
+                &channel.channel_id,
+                node_id,
+                expected_outbound_amount_msat,
+            );
+            return None;
+        }
+
+        let channel_value_satoshis = expected_outbound_amount_msat / 1000
+            + expected_outbound_amount_msat / 50_000
+            + JIT_CHANNEL_BUFFER_SATS;
+        let user_channel_id: u128 = random();
+        let mut config = *self.channel_manager.get_current_default_configuration();
+        config.channel_handshake_config.announced_channel = false;
+        // We fund the channel ourselves, so it can be used as soon as the recipient lets us.
+        config.channel_handshake_limits.trust_own_funding_0conf = true;
+        // Register the HTLC before LDK can report the channel ready.
+        self.pending.lock().unwrap().insert(
+            user_channel_id,
+            vec![InterceptedHtlc {
+                intercept_id,
+                amount_msat: expected_outbound_amount_msat,
+            }],
+        );
+        if let Err(e) = self.channel_manager.create_channel(
+            node_id,
+            channel_value_satoshis,
+            0,
+            user_channel_id,
+            Some(config),
+        ) {
+            warn!(
+                "Failed to open just in time channel to {}: {:?}",
+                node_id, e
+            );
+            self.channel_closed(user_channel_id);
+            return None;
+        }
+        info!(
+            "Opening {} sat channel to {} for intercepted HTLC {}",
+            channel_value_satoshis,
+            node_id,
+            payment_hash.0.encode_hex::<String>()
+        );
+        Some(user_channel_id)
+    }
+
+    pub fn channel_ready(&self, user_channel_id: u128, channel_id: &[u8; 32], node_id: PublicKey) {
+        let htlcs = self.pending.lock().unwrap().remove(&user_channel_id);
+        for htlc in htlcs.unwrap_or_default() {
+            self.forward(htlc.intercept_id, channel_id, node_id, htlc.amount_msat);
+        }
+    }
+
+    /// Fail the HTLCs that were waiting for the channel.
+    pub fn channel_closed(&self, user_channel_id: u128) {
+        let htlcs = self.pending.lock().unwrap().remove(&user_channel_id);
+        for htlc in htlcs.unwrap_or_default() {
+            self.fail(htlc.intercept_id);
+        }
+    }
+
+    pub fn expire(&self, user_channel_id: u128, intercept_id: InterceptId) {
+        let mut pending = self.pending.lock().unwrap();
+        if let Some(htlcs) = pending.get_mut(&user_channel_id) {
+            if let Some(index) = htlcs.iter().position(|h| h.intercept_id == intercept_id) {
+                htlcs.remove(index);
+                info!("Timed out waiting for just in time channel");
+                self.fail(intercept_id);
+            }
+            if htlcs.is_empty() {
+                pending.remove(&user_channel_id);
+            }
+        }
+    }
+
+    fn forward(
+        &self,
+        intercept_id: InterceptId,
+        channel_id: &[u8; 32],
+        node_id: PublicKey,
+        amount_msat: u64,
+    ) {
+        if let Err(e) = self.channel_manager.forward_intercepted_htlc(
+            intercept_id,
+            channel_id,
+            node_id,
+            amount_msat,
+        ) {
+            warn!("Failed to forward intercepted HTLC: {:?}", e);
+            self.fail(intercept_id);
+        }
+    }
+
+    pub fn fail(&self, intercept_id: InterceptId) {
+        if let Err(e) = self.channel_manager.fail_intercepted_htlc(intercept_id) {
+            warn!("Failed to fail intercepted HTLC: {:?}", e);
+        }
+    }
+}
+
+impl<C> Interceptor<C>
+where
+    C: Deref + Send + Sync + 'static,
+    C::Target: InterceptChannelManager,
+{
+    /// `node_id` is the node the intercept scid was handed out for.
+    pub fn htlc_intercepted(
+        self: &Arc<Self>,
+        intercept_id: InterceptId,
+        node_id: PublicKey,
+        payment_hash: PaymentHash,
+        expected_outbound_amount_msat: u64,
+    ) {
+        if let Some(user_channel_id) = self.intercept(
+            intercept_id,
+            node_id,
+            payment_hash,
+            expected_outbound_amount_msat,
+        ) {
+            let interceptor = self.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(interceptor.timeout).await;
+                interceptor.expire(user_channel_id, intercept_id);
+            });
+        }
+    }
+}
@@ -1,21 +1,25 @@
 use anyhow::Result;
 use bitcoind::Client;
+use database::cipher::Cipher;
 use database::ldk_database::LdkDatabase;
-use database::migrate_database;
 use database::wallet_database::WalletDatabase;
+use database::{encrypt_plaintext_rows, migrate_database, pool};
 use futures::FutureExt;
 use lightning_knd::api::{start_rest_api, MacaroonAuth};
 use lightning_knd::controller::Controller;
 use lightning_knd::key_generator::KeyGenerator;
 use lightning_knd::prometheus::start_prometheus_exporter;
 use lightning_knd::wallet::Wallet;
-use log::{info, warn};
+use log::{error, info, warn};
 use settings::Settings;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::signal::unix::SignalKind;
 
+// How long shutdown waits for channel monitor writes before logging them as stuck.
+const STUCK_MONITOR_WRITES: Duration = Duration::from_secs(300);
+
 pub fn main() -> Result<()> {
     let settings = Arc::new(Settings::load());
     logger::KndLogger::init(&settings.node_id, settings.log_level.parse().unwrap());
@@ -35,8 +39,11 @@ pub fn main() -> Result<()> {
 
     let key_generator = Arc::new(KeyGenerator::init(&settings.data_dir));
 
-    let database = Arc::new(runtime.block_on(LdkDatabase::new(&settings))?);
-    let wallet_database = runtime.block_on(WalletDatabase::new(&settings))?;
+    let cipher = Cipher::new(&key_generator.database_key());
+    let pool = pool(&settings)?;
+    runtime.block_on(encrypt_plaintext_rows(&pool, &cipher))?;
+    let database = Arc::new(LdkDatabase::new(&settings, pool.clone(), cipher.clone()));
+    let wallet_database = WalletDatabase::new(pool, cipher);
 
     let bitcoind_client = Arc::new(runtime.block_on(Client::new(&settings))?);
     let wallet = Arc::new(Wallet::new(
@@ -48,7 +55,7 @@ pub fn main() -> Result<()> {
 
     let (controller, background_processor) = runtime.block_on(Controller::start_ldk(
         settings.clone(),
-        database,
+        database.clone(),
         bitcoind_client,
         wallet.clone(),
         key_generator.clone(),
@@ -87,8 +94,32 @@ pub fn main() -> Result<()> {
 
     info!("Shutting down");
     shutdown_flag.store(true, Ordering::Release);
+    // The final channel manager persist waits for the channel monitor writes before it.
     background_processor.stop().unwrap();
     controller.stop();
+    // Channel monitor writes are retried until they commit. Exiting before could lose funds.
+    let shutdown_started = Instant::now();
+    while runtime
+        .block_on(tokio::time::timeout(
+            Duration::from_secs(30),
+            database.wait_for_monitor_writes(),
+        ))
+        .is_err()
+    {
+        let waited = shutdown_started.elapsed();
+        if waited < STUCK_MONITOR_WRITES {
+            warn!(
+                "Waiting for {} channel monitor writes before shutting down",
+                database.pending_monitor_writes()
+            );
+        } else {
+            error!(
+                "Shutdown stuck for {}s on {} channel monitor writes, check the database. Killing the process could lose funds.",
+                waited.as_secs(),
+                database.pending_monitor_writes()
+            );
+        }
+    }
     runtime.shutdown_timeout(Duration::from_secs(30));
     info!("Stopped all threads. Process finished.");
     Ok(())
@@ -0,0 +1,184 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use bitcoin::secp256k1::PublicKey;
+use lightning::ln::channelmanager::PaymentId;
+use lightning::routing::gossip::{NodeId, ReadOnlyNetworkGraph};
+use lightning::routing::router::{PaymentParameters, RouteHop, RouteParameters, Router as _};
+use lightning_invoice::DEFAULT_MIN_FINAL_CLTV_EXPIRY;
+use log::{debug, info};
+use rand::seq::SliceRandom;
+
+use crate::controller::{ChannelManager, NetworkGraph, Router};
+
+// How many of the best connected nodes the background prober picks from.
+const PROBE_TARGETS: usize = 20;
+
+/// Find a single path for the payment to probe.
+pub(crate) fn find_probe_path(
+    channel_manager: &ChannelManager,
+    router: &Router,
+    payment_params: PaymentParameters,
+    amount_msat: u64,
+    final_cltv_expiry_delta: u32,
+) -> Result<Vec<RouteHop>> {
+    let route_params = RouteParameters {
+        payment_params: payment_params.with_max_path_count(1),
+        final_value_msat: amount_msat,
+        final_cltv_expiry_delta,
+    };
+    let first_hops = channel_manager.list_usable_channels();
+    let route = router
+        .find_route(
+            &channel_manager.get_our_node_id(),
+            &route_params,
+            Some(&first_hops.iter().collect::<Vec<_>>()),
+            channel_manager.compute_inflight_htlcs(),
+        )
+        .map_err(|e| anyhow!(e.err))?;
+    route
+        .paths
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Route has no paths"))
+}
+
+/// Send a probe along the path. The outcome is reported by a ProbeSuccessful or ProbeFailed
+/// event which also updates the scorer.
+pub(crate) fn send_probe(
+    channel_manager: &ChannelManager,
+    path: Vec<RouteHop>,
+) -> Result<PaymentId> {
+    let (_, payment_id) = channel_manager
+        .send_probe(path)
+        .map_err(|e| anyhow!("Failed to send probe: {:?}", e))?;
+    Ok(payment_id)
+}
+
+/// Regularly probe one of the best connected nodes so the scorer learns the liquidity on
+/// the way to them before payments depend on it.
+pub(crate) fn start_prober(
+    channel_manager: Arc<ChannelManager>,
+    network_graph: Arc<NetworkGraph>,
+    router: Arc<Router>,
+    amount_msat: u64,
+    probe_interval: Duration,
+    shutdown_flag: Arc<AtomicBool>,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(probe_interval);
+        loop {
+            interval.tick().await;
+            if shutdown_flag.load(Ordering::Acquire) {
+                return;
+            }
+            let our_node_id = NodeId::from_pubkey(&channel_manager.get_our_node_id());
+            let target = probe_targets(&network_graph.read_only(), &our_node_id, PROBE_TARGETS)
+                .choose(&mut rand::thread_rng())
+                .and_then(|node_id| PublicKey::from_slice(node_id.as_slice()).ok());
+            let target = match target {
+                Some(target) => target,
+                None => continue,
+            };
+            match find_probe_path(
+                &channel_manager,
+                &router,
+                PaymentParameters::from_node_id(target),
+                amount_msat,
+                DEFAULT_MIN_FINAL_CLTV_EXPIRY as u32,
+            )
+            .and_then(|path| send_probe(&channel_manager, path))
+            {
+                Ok(_) => debug!("Sent {} msat probe to {}", amount_msat, target),
+                Err(e) => info!("Failed to probe {}: {}", target, e),
+            }
+        }
+    });
+}
+
+// The `count` nodes with the most capacity, not counting us.
+fn probe_targets(graph: &ReadOnlyNetworkGraph, our_node_id: &NodeId, count: usize) -> Vec<NodeId> {
+    let mut capacities: Vec<(NodeId, u64)> = graph
+        .nodes()
+        .iter()
+        .filter(|(node_id, _)| *node_id != our_node_id)
+        .map(|(node_id, node)| {
+            let capacity_msat = node
+                .channels
+                .iter()
+                .filter_map(|scid| graph.channel(*scid))
+                .map(|channel| match channel.capacity_sats {
+                    Some(capacity_sats) => capacity_sats * 1000,
+                    // Gossip from rapid sync has no capacity so go by the largest HTLC.
+                    None => [&channel.one_to_two, &channel.two_to_one]
+                        .iter()
+                        .filter_map(|update| update.as_ref().map(|u| u.htlc_maximum_msat))
+                        .max()
+                        .unwrap_or_default(),
+                })
+                .sum();
+            (*node_id, capacity_msat)
+        })
+        .collect();
+    capacities.sort_by_key(|(_, capacity_msat)| std::cmp::Reverse(*capacity_msat));
+    capacities
+        .into_iter()
+        .take(count)
+        .map(|(node_id, _)| node_id)
+        .collect()
+}
+
+#[test]
+fn test_probe_targets() {
+    use bitcoin::blockdata::constants::genesis_block;
+    use bitcoin::Network;
+    use lightning::ln::features::ChannelFeatures;
+    use lightning::ln::msgs::UnsignedChannelUpdate;
+    use logger::KndLogger;
+
+    KndLogger::init("test", log::LevelFilter::Info);
+    let genesis_hash = genesis_block(Network::Regtest).header.block_hash();
+    let graph = NetworkGraph::new(genesis_hash, KndLogger::global());
+    let us = test_utils::random_public_key();
+    let big = test_utils::random_public_key();
+    let small = test_utils::random_public_key();
+    let add_channel = |scid, node_one, node_two, htlc_maximum_msat| {
+        graph
+            .add_channel_from_partial_announcement(
+                scid,
+                1000,
+                ChannelFeatures::empty(),
+                node_one,
+                node_two,
+            )
+            .unwrap();
+        graph
+            .update_channel_unsigned(&UnsignedChannelUpdate {
+                chain_hash: genesis_hash,
+                short_channel_id: scid,
+                timestamp: 1000,
+                flags: 0,
+                cltv_expiry_delta: 40,
+                htlc_minimum_msat: 1,
+                htlc_maximum_msat,
+                fee_base_msat: 1000,
+                fee_proportional_millionths: 10,
+                excess_data: vec![],
+            })
+            .unwrap();
+    };
+    add_channel(1, us, big, 5_000_000);
+    add_channel(2, big, small, 1_000_000);
+
+    let our_node_id = NodeId::from_pubkey(&us);
+    assert_eq!(
+        vec![NodeId::from_pubkey(&big), NodeId::from_pubkey(&small)],
+        probe_targets(&graph.read_only(), &our_node_id, 5)
+    );
+    assert_eq!(
+        vec![NodeId::from_pubkey(&big)],
+        probe_targets(&graph.read_only(), &our_node_id, 1)
+    );
+}
@@ -1,6 +1,7 @@
 //! Prometheus http exporter
 
 use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
 
@@ -11,7 +12,9 @@ use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Method, Request, Response, Server, StatusCode};
 use log::info;
 use once_cell::sync::{Lazy, OnceCell};
-use prometheus::{self, register_gauge, Encoder, Gauge, TextEncoder};
+use prometheus::{
+    self, register_gauge, register_int_counter, Encoder, Gauge, IntCounter, TextEncoder,
+};
 
 use crate::api::LightningInterface;
 
@@ -48,6 +51,24 @@ static PEER_COUNT: Lazy<Gauge> = Lazy::new(|| {
 static WALLET_BALANCE: Lazy<Gauge> =
     Lazy::new(|| register_gauge!("wallet_balance", "The bitcoin wallet balance").unwrap());
 
+static PENDING_MONITOR_WRITES: Lazy<Gauge> = Lazy::new(|| {
+    register_gauge!(
+        "lightning_pending_monitor_writes",
+        "The number of channel monitor writes that have not been committed yet"
+    )
+    .unwrap()
+});
+
+static FAILED_MONITOR_WRITES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "lightning_failed_monitor_writes",
+        "The number of failed attempts to write a channel monitor"
+    )
+    .unwrap()
+});
+// The total the counter was last brought up to, as it can only be incremented.
+static FAILED_MONITOR_WRITES_SEEN: AtomicU64 = AtomicU64::new(0);
+
 async fn response_examples(
     lightning_metrics: Arc<dyn LightningInterface + Send + Sync>,
     req: Request<Body>,
@@ -61,6 +82,11 @@ async fn response_examples(
             CHANNEL_COUNT.set(lightning_metrics.graph_num_channels() as f64);
             PEER_COUNT.set(lightning_metrics.num_peers() as f64);
             WALLET_BALANCE.set(lightning_metrics.wallet_balance() as f64);
+            PENDING_MONITOR_WRITES.set(lightning_metrics.pending_monitor_writes() as f64);
+            let failed_monitor_writes = lightning_metrics.failed_monitor_writes();
+            let seen =
+                FAILED_MONITOR_WRITES_SEEN.fetch_max(failed_monitor_writes, Ordering::AcqRel);
+            FAILED_MONITOR_WRITES.inc_by(failed_monitor_writes.saturating_sub(seen));
             let metric_families = prometheus::gather();
             let mut buffer = vec![];
             let encoder = TextEncoder::new();
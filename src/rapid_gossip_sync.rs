@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use logger::KndLogger;
+
+use crate::controller::NetworkGraph;
+
+pub(crate) type RapidGossipSync =
+    lightning_rapid_gossip_sync::RapidGossipSync<Arc<NetworkGraph>, Arc<KndLogger>>;
+
+/// Where Rapid Gossip Sync snapshots come from.
+pub(crate) enum SnapshotSource {
+    Url(String),
+    File(String),
+}
+
+impl SnapshotSource {
+    pub fn new(source: &str) -> SnapshotSource {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            SnapshotSource::Url(source.trim_end_matches('/').to_string())
+        } else {
+            SnapshotSource::File(source.to_string())
+        }
+    }
+}
+
+/// Fetch the latest snapshot and apply it to the network graph. Returns the timestamp of the
+/// snapshot which the next one is requested from.
+pub(crate) async fn apply_snapshot(
+    rapid_gossip_sync: &RapidGossipSync,
+    source: &SnapshotSource,
+) -> Result<u32> {
+    let snapshot = match source {
+        SnapshotSource::Url(url) => {
+            let last_sync = rapid_gossip_sync
+                .network_graph()
+                .get_last_rapid_gossip_sync_timestamp()
+                .unwrap_or_default();
+            reqwest::get(snapshot_url(url, last_sync))
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await?
+                .to_vec()
+        }
+        SnapshotSource::File(path) => tokio::fs::read(path).await?,
+    };
+    rapid_gossip_sync
+        .update_network_graph(&snapshot)
+        .map_err(|e| anyhow!("Failed to apply gossip snapshot: {:?}", e))
+}
+
+fn snapshot_url(url: &str, last_sync: u32) -> String {
+    format!("{}/{}", url, last_sync)
+}
+
+#[test]
+fn test_snapshot_source() {
+    match SnapshotSource::new("https://rapidsync.lightningdevkit.org/snapshot/") {
+        SnapshotSource::Url(url) => assert_eq!(
+            "https://rapidsync.lightningdevkit.org/snapshot/1000",
+            snapshot_url(&url, 1000)
+        ),
+        SnapshotSource::File(_) => panic!("expected a url"),
+    }
+    assert!(matches!(
+        SnapshotSource::new("/var/lib/knd/snapshot.bin"),
+        SnapshotSource::File(_)
+    ));
+}
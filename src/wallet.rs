@@ -1,31 +1,42 @@
 use std::{
+    collections::HashMap,
     str::FromStr,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use bdk::{
     bitcoin::util::bip32::ExtendedPrivKey,
     blockchain::{rpc::Auth, ConfigurableBlockchain, RpcBlockchain, RpcConfig},
-    wallet::AddressInfo,
-    Balance, FeeRate, SignOptions, SyncOptions,
+    wallet::{AddressIndex, AddressInfo},
+    Balance, BlockTime, FeeRate, LocalUtxo, SignOptions, SyncOptions, TransactionDetails,
 };
 use bitcoin::{
-    util::bip32::{ChildNumber, DerivationPath},
-    Network, Script, Transaction,
+    hashes::Hash,
+    util::{
+        bip32::{ChildNumber, DerivationPath},
+        psbt::PartiallySignedTransaction,
+    },
+    Address, Network, Script, Transaction, Txid, WScriptHash,
 };
 use bitcoind::Client;
 use database::wallet_database::WalletDatabase;
-use lightning::chain::chaininterface::{ConfirmationTarget, FeeEstimator};
+use lightning::chain::chaininterface::{BroadcasterInterface, FeeEstimator};
 use log::{error, info};
 use settings::Settings;
 
-use crate::api::WalletInterface;
+use crate::api::{FeeRateSpec, FundingOptions, WalletInterface};
 
 pub struct Wallet {
     // bdk::Wallet uses a RefCell to hold the database which is not thread safe so we use a mutex here.
     wallet: Arc<Mutex<bdk::Wallet<WalletDatabase>>>,
     bitcoind_client: Arc<Client>,
+    network: Network,
+    // Set once the initial sync with bitcoind has completed.
+    synced: Arc<AtomicBool>,
 }
 
 impl WalletInterface for Wallet {
@@ -35,6 +46,48 @@ impl WalletInterface for Wallet {
             Err(_) => Ok(Balance::default()),
         }
     }
+
+    fn network(&self) -> Network {
+        self.network
+    }
+
+    fn new_address(&self) -> Result<AddressInfo> {
+        Ok(self.lock()?.get_address(AddressIndex::New)?)
+    }
+
+    fn withdraw(
+        &self,
+        address: Address,
+        amount: Option<u64>,
+        fee_rate: FeeRateSpec,
+    ) -> Result<Transaction> {
+        let wallet = self.lock()?;
+        let mut tx_builder = wallet.build_tx();
+        match amount {
+            Some(amount) => {
+                tx_builder.add_recipient(address.script_pubkey(), amount);
+            }
+            None => {
+                tx_builder.drain_wallet().drain_to(address.script_pubkey());
+            }
+        }
+        tx_builder.fee_rate(self.fee_rate(fee_rate)).enable_rbf();
+        let (mut psbt, _tx_details) = tx_builder.finish()?;
+        if !wallet.sign(&mut psbt, SignOptions::default())? {
+            bail!("Failed to sign withdrawal");
+        }
+        let tx = psbt.extract_tx();
+        self.bitcoind_client.broadcast_transaction(&tx);
+        Ok(tx)
+    }
+
+    fn list_utxos(&self) -> Result<Vec<(LocalUtxo, Option<BlockTime>)>> {
+        utxos_with_confirmation_time(&*self.lock()?)
+    }
+
+    fn list_transactions(&self) -> Result<Vec<TransactionDetails>> {
+        Ok(self.lock()?.list_transactions(false)?)
+    }
 }
 
 impl Wallet {
@@ -53,14 +106,14 @@ impl Wallet {
         };
 
         let base_path = DerivationPath::from_str(native_segwit_base_path)?;
-        let derivation_path = base_path.extend(&[ChildNumber::from_hardened_idx(coin_type)?]);
+        let derivation_path = base_path.extend([ChildNumber::from_hardened_idx(coin_type)?]);
         let receive_descriptor_template = bdk::descriptor!(wpkh((
             xprivkey,
-            derivation_path.extend(&[ChildNumber::Normal { index: 0 }])
+            derivation_path.extend([ChildNumber::Normal { index: 0 }])
         )))?;
         let change_descriptor_template = bdk::descriptor!(wpkh((
             xprivkey,
-            derivation_path.extend(&[ChildNumber::Normal { index: 1 }])
+            derivation_path.extend([ChildNumber::Normal { index: 1 }])
         )))?;
 
         let bdk_wallet = Arc::new(Mutex::new(bdk::Wallet::new(
@@ -86,6 +139,8 @@ impl Wallet {
 
         info!("Syncing wallet to blockchain.");
         let wallet_clone = bdk_wallet.clone();
+        let synced = Arc::new(AtomicBool::new(false));
+        let synced_clone = synced.clone();
         tokio::task::spawn_blocking(move || {
             // Don't want to block for a long time while the wallet is syncing so use try_lock everywhere else.
             if let Err(e) = wallet_clone
@@ -96,46 +151,128 @@ impl Wallet {
                 error!("Walled sync failed: {}", e);
             } else {
                 info!("Wallet sync complete.");
+                synced_clone.store(true, Ordering::Release);
             }
         });
 
         Ok(Wallet {
             wallet: bdk_wallet,
             bitcoind_client,
+            network: settings.bitcoin_network,
+            synced,
         })
     }
 
+    pub fn is_synced(&self) -> bool {
+        self.synced.load(Ordering::Acquire)
+    }
+
+    // The initial sync holds the lock for a long time so don't wait for it.
+    fn lock(&self) -> Result<MutexGuard<'_, bdk::Wallet<WalletDatabase>>> {
+        self.wallet
+            .try_lock()
+            .map_err(|_| anyhow!("Wallet is busy, try again later"))
+    }
+
+    fn fee_rate(&self, fee_rate: FeeRateSpec) -> FeeRate {
+        match fee_rate {
+            FeeRateSpec::Target(target) => FeeRate::from_sat_per_kwu(
+                self.bitcoind_client.get_est_sat_per_1000_weight(target) as f32,
+            ),
+            FeeRateSpec::PerKw(sats) => FeeRate::from_sat_per_kwu(sats as f32),
+            FeeRateSpec::PerKb(sats) => FeeRate::from_sat_per_kvb(sats as f32),
+        }
+    }
+
+    /// Fails if the coins allowed by the funding options cannot cover the channel and the fee.
+    pub fn check_funding(
+        &self,
+        channel_value_satoshis: u64,
+        options: &FundingOptions,
+        tip_height: u32,
+    ) -> Result<()> {
+        // Same size as the real funding output so the fee comes out the same.
+        let output_script = Script::new_v0_p2wsh(&WScriptHash::all_zeros());
+        let wallet = self.lock()?;
+        self.funding_psbt(
+            &wallet,
+            &output_script,
+            channel_value_satoshis,
+            options,
+            tip_height,
+        )?;
+        Ok(())
+    }
+
     pub fn fund_tx(
         &self,
         output_script: &Script,
         channel_value_satoshis: &u64,
+        options: &FundingOptions,
+        tip_height: u32,
     ) -> Result<Transaction> {
-        let wallet = self.wallet.try_lock().unwrap();
+        let wallet = self.lock()?;
 
-        let mut tx_builder = wallet.build_tx();
-        let fee_sats_per_1000_wu = self
-            .bitcoind_client
-            .get_est_sat_per_1000_weight(ConfirmationTarget::Normal);
+        let mut psbt = self.funding_psbt(
+            &wallet,
+            output_script,
+            *channel_value_satoshis,
+            options,
+            tip_height,
+        )?;
 
-        // TODO: is this the correct conversion??
-        let sat_per_vb = match fee_sats_per_1000_wu {
-            253 => 1.0,
-            _ => fee_sats_per_1000_wu as f32 / 250.0,
-        } as f32;
+        let _finalized = wallet.sign(&mut psbt, SignOptions::default())?;
 
-        let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
+        let funding_tx = psbt.extract_tx();
+        Ok(funding_tx)
+    }
+
+    fn funding_psbt(
+        &self,
+        wallet: &bdk::Wallet<WalletDatabase>,
+        output_script: &Script,
+        channel_value_satoshis: u64,
+        options: &FundingOptions,
+        tip_height: u32,
+    ) -> Result<PartiallySignedTransaction> {
+        let confirmations = |confirmation_time: &Option<BlockTime>| match confirmation_time {
+            Some(time) => tip_height.saturating_sub(time.height) + 1,
+            None => 0,
+        };
+        let utxos = utxos_with_confirmation_time(wallet)?;
 
+        let mut tx_builder = wallet.build_tx();
+        if options.utxos.is_empty() {
+            tx_builder.unspendable(
+                utxos
+                    .iter()
+                    .filter(|(_, time)| confirmations(time) < options.min_conf)
+                    .map(|(utxo, _)| utxo.outpoint)
+                    .collect(),
+            );
+        } else {
+            for outpoint in &options.utxos {
+                match utxos.iter().find(|(utxo, _)| utxo.outpoint == *outpoint) {
+                    Some((_, time)) if confirmations(time) < options.min_conf => bail!(
+                        "Utxo {} has fewer than {} confirmations",
+                        outpoint,
+                        options.min_conf
+                    ),
+                    Some(_) => {}
+                    None => bail!("Utxo {} is not in the wallet or already spent", outpoint),
+                }
+            }
+            tx_builder
+                .add_utxos(&options.utxos)?
+                .manually_selected_only();
+        }
         tx_builder
-            .add_recipient(output_script.clone(), *channel_value_satoshis)
-            .fee_rate(fee_rate)
+            .add_recipient(output_script.clone(), channel_value_satoshis)
+            .fee_rate(self.fee_rate(options.fee_rate))
             .enable_rbf();
 
-        let (mut psbt, _tx_details) = tx_builder.finish()?;
-
-        let _finalized = wallet.sign(&mut psbt, SignOptions::default())?;
-
-        let funding_tx = psbt.extract_tx();
-        Ok(funding_tx)
+        let (psbt, _tx_details) = tx_builder.finish()?;
+        Ok(psbt)
     }
 
     pub fn get_new_address(&self) -> Result<AddressInfo> {
@@ -147,3 +284,21 @@ impl Wallet {
         Ok(address)
     }
 }
+
+fn utxos_with_confirmation_time(
+    wallet: &bdk::Wallet<WalletDatabase>,
+) -> Result<Vec<(LocalUtxo, Option<BlockTime>)>> {
+    let confirmations: HashMap<Txid, Option<BlockTime>> = wallet
+        .list_transactions(false)?
+        .into_iter()
+        .map(|tx| (tx.txid, tx.confirmation_time))
+        .collect();
+    Ok(wallet
+        .list_unspent()?
+        .into_iter()
+        .map(|utxo| {
+            let confirmation_time = confirmations.get(&utxo.outpoint.txid).cloned().flatten();
+            (utxo, confirmation_time)
+        })
+        .collect())
+}
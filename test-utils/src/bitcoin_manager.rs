@@ -80,8 +80,8 @@ impl BitcoinManager {
             pid = pid.trim().to_string();
             Command::new("kill").arg("-9").arg(pid).output().unwrap();
         }
-        std::fs::remove_dir_all(&self.data_dir()).unwrap_or_default();
-        std::fs::create_dir_all(&self.data_dir()).unwrap();
+        std::fs::remove_dir_all(self.data_dir()).unwrap_or_default();
+        std::fs::create_dir_all(self.data_dir()).unwrap();
     }
 
     fn data_dir(&self) -> String {
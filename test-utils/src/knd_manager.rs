@@ -69,10 +69,10 @@ impl KndManager {
         set_var("KND_EXPORTER_ADDRESS", &exporter_address);
         set_var("KND_REST_API_ADDRESS", &rest_api_address);
         set_var("KND_BITCOIN_NETWORK", &bitcoin.network);
-        set_var("KND_BITCOIN_COOKIE_PATH", &bitcoin.cookie_path());
+        set_var("KND_BITCOIN_COOKIE_PATH", bitcoin.cookie_path());
         set_var("KND_BITCOIN_RPC_HOST", "127.0.0.1");
-        set_var("KND_BITCOIN_RPC_PORT", &bitcoin.rpc_port.to_string());
-        set_var("KND_DATABASE_PORT", &cockroach.port.to_string());
+        set_var("KND_BITCOIN_RPC_PORT", bitcoin.rpc_port.to_string());
+        set_var("KND_DATABASE_PORT", cockroach.port.to_string());
 
         let client = https_client();
 
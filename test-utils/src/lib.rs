@@ -15,6 +15,12 @@ pub struct TestSettingsBuilder {
     settings: Settings,
 }
 
+impl Default for TestSettingsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TestSettingsBuilder {
     pub fn new() -> TestSettingsBuilder {
         let mut settings = Settings::parse_from::<Vec<OsStr>, OsStr>(vec![]);
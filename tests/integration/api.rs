@@ -2,10 +2,15 @@ use std::thread::spawn;
 use std::{fs, sync::Arc};
 
 use axum::http::HeaderValue;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{Secp256k1, SecretKey};
+use bitcoin::{Address, Network};
 use futures::FutureExt;
 use hex::ToHex;
 use hyper::header::CONTENT_TYPE;
 use hyper::Method;
+use lightning::ln::PaymentSecret;
+use lightning_invoice::{Currency, InvoiceBuilder};
 use lightning_knd::api::start_rest_api;
 use lightning_knd::api::MacaroonAuth;
 use logger::KndLogger;
@@ -15,7 +20,14 @@ use reqwest::StatusCode;
 use settings::Settings;
 use test_utils::{https_client, random_public_key, TestSettingsBuilder};
 
-use api::{routes, Balance, Channel, FundChannel, FundChannelResponse, GetInfo};
+use api::{
+    routes, Balance, Channel, ChannelClose, ChannelCloseResponse, ChannelDetail, ChannelFee,
+    ChannelIds, ChannelLiquidity, ConnectPeer, ConnectPeerResponse, FundChannel,
+    FundChannelResponse, GenerateInvoice, GenerateInvoiceResponse, GetInfo, InterceptScid, Invoice,
+    Keysend, LiquidityRange, ListForwardsResponse, NetworkChannel, NetworkNode, NewAddressResponse,
+    PayInvoice, Payment, PaymentResponse, Peer, Probe, ProbeResponse, Route, RouteHint,
+    SetChannelFee, Utxo, WalletTransaction, Withdraw, WithdrawResponse,
+};
 use tokio::runtime::Runtime;
 
 use crate::mock_lightning::MockLightning;
@@ -52,18 +64,186 @@ generate!(
     Method::GET,
     routes::GET_BALANCE
 );
+generate!(
+    test_newaddr_unauthorized,
+    unauthorized_request,
+    Method::GET,
+    routes::NEW_ADDR
+);
+generate!(
+    test_withdraw_unauthorized,
+    unauthorized_request,
+    Method::POST,
+    routes::WITHDRAW
+);
+generate!(
+    test_listutxos_unauthorized,
+    unauthorized_request,
+    Method::GET,
+    routes::LIST_UTXOS
+);
+generate!(
+    test_listtransactions_unauthorized,
+    unauthorized_request,
+    Method::GET,
+    routes::LIST_TRANSACTIONS
+);
 generate!(
     test_listchannels_unauthorized,
     unauthorized_request,
     Method::GET,
     routes::LIST_CHANNELS
 );
+generate!(
+    test_getchannel_unauthorized,
+    unauthorized_request,
+    Method::GET,
+    routes::GET_CHANNEL
+);
+generate!(
+    test_listforwards_unauthorized,
+    unauthorized_request,
+    Method::GET,
+    routes::LIST_FORWARDS
+);
+generate!(
+    test_setchannelfee_unauthorized,
+    unauthorized_request,
+    Method::POST,
+    routes::SET_CHANNEL_FEE
+);
 generate!(
     test_openchannel_unauthorized,
     unauthorized_request,
     Method::POST,
     routes::OPEN_CHANNEL
 );
+generate!(
+    test_connectpeer_unauthorized,
+    unauthorized_request,
+    Method::POST,
+    routes::CONNECT_PEER
+);
+generate!(
+    test_listpeers_unauthorized,
+    unauthorized_request,
+    Method::GET,
+    routes::LIST_PEERS
+);
+generate!(
+    test_disconnectpeer_unauthorized,
+    unauthorized_request,
+    Method::DELETE,
+    routes::DISCONNECT_PEER
+);
+generate!(
+    test_probe_unauthorized,
+    unauthorized_request,
+    Method::POST,
+    routes::PROBE
+);
+generate!(
+    test_interceptscid_unauthorized,
+    unauthorized_request,
+    Method::POST,
+    routes::INTERCEPT_SCID
+);
+generate!(
+    test_scorerchannel_unauthorized,
+    unauthorized_request,
+    Method::GET,
+    routes::SCORER_CHANNEL
+);
+generate!(
+    test_bannode_unauthorized,
+    unauthorized_request,
+    Method::POST,
+    routes::BAN_NODE
+);
+generate!(
+    test_unbannode_unauthorized,
+    unauthorized_request,
+    Method::POST,
+    routes::UNBAN_NODE
+);
+generate!(
+    test_resetscorer_unauthorized,
+    unauthorized_request,
+    Method::POST,
+    routes::RESET_SCORER
+);
+generate!(
+    test_listnodes_unauthorized,
+    unauthorized_request,
+    Method::GET,
+    routes::LIST_NODES
+);
+generate!(
+    test_getnode_unauthorized,
+    unauthorized_request,
+    Method::GET,
+    routes::GET_NODE
+);
+generate!(
+    test_listnetworkchannels_unauthorized,
+    unauthorized_request,
+    Method::GET,
+    routes::LIST_NETWORK_CHANNELS
+);
+generate!(
+    test_getnetworkchannel_unauthorized,
+    unauthorized_request,
+    Method::GET,
+    routes::GET_NETWORK_CHANNEL
+);
+generate!(
+    test_getroute_unauthorized,
+    unauthorized_request,
+    Method::GET,
+    routes::GET_ROUTE
+);
+generate!(
+    test_closechannel_unauthorized,
+    unauthorized_request,
+    Method::POST,
+    routes::CLOSE_CHANNEL
+);
+generate!(
+    test_generateinvoice_unauthorized,
+    unauthorized_request,
+    Method::POST,
+    routes::GENERATE_INVOICE
+);
+generate!(
+    test_listinvoices_unauthorized,
+    unauthorized_request,
+    Method::GET,
+    routes::LIST_INVOICES
+);
+generate!(
+    test_payinvoice_unauthorized,
+    unauthorized_request,
+    Method::POST,
+    routes::PAY_INVOICE
+);
+generate!(
+    test_keysend_unauthorized,
+    unauthorized_request,
+    Method::POST,
+    routes::KEYSEND
+);
+generate!(
+    test_listpayments_unauthorized,
+    unauthorized_request,
+    Method::GET,
+    routes::LIST_PAYMENTS
+);
+generate!(
+    test_getpayment_unauthorized,
+    unauthorized_request,
+    Method::GET,
+    routes::GET_PAYMENT
+);
 
 #[tokio::test(flavor = "multi_thread")]
 async fn test_not_found() {
@@ -100,6 +280,7 @@ async fn test_getinfo_readonly() {
         .unwrap();
     let info: GetInfo = serde_json::from_str(&result).unwrap();
     assert_eq!(LIGHTNING.num_peers, info.num_peers);
+    assert_eq!(LIGHTNING.synced_to_chain, info.synced_to_chain);
 }
 
 #[tokio::test(flavor = "multi_thread")]
@@ -113,13 +294,119 @@ async fn test_getbalance_readonly() {
     assert_eq!(5, balance.unconf_balance);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_newaddr_readonly() {
+    let result = send(readonly_request(Method::GET, routes::NEW_ADDR))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_newaddr_admin() {
+    let result = send(admin_request(Method::GET, routes::NEW_ADDR))
+        .await
+        .unwrap();
+    let response: NewAddressResponse = serde_json::from_str(&result).unwrap();
+    assert!(response.address.starts_with("bcrt1"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_withdraw_readonly() {
+    let body = serde_json::to_string(&withdraw_request()).unwrap();
+    let result = send(readonly_request(Method::POST, routes::WITHDRAW).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_withdraw_admin() {
+    let body = serde_json::to_string(&withdraw_request()).unwrap();
+    let result = send(admin_request(Method::POST, routes::WITHDRAW).body(body))
+        .await
+        .unwrap();
+    let response: WithdrawResponse = serde_json::from_str(&result).unwrap();
+    assert_eq!(
+        "fba98a9a61ef62c081b31769f66a81f1640b4f94d48b550a550034cb4990eded",
+        response.txid
+    );
+
+    // The minimum relay fee is enough to sweep the wallet.
+    let mut request = withdraw_request();
+    request.fee_rate = Some("1000perkb".to_string());
+    let body = serde_json::to_string(&request).unwrap();
+    send(admin_request(Method::POST, routes::WITHDRAW).body(body))
+        .await
+        .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_withdraw_bad_request() {
+    let mut request = withdraw_request();
+    request.satoshis = "everything".to_string();
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::WITHDRAW).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result);
+
+    let mut request = withdraw_request();
+    request.fee_rate = Some("100perkw".to_string());
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::WITHDRAW).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result);
+
+    let mut request = withdraw_request();
+    request.address = Address::p2wpkh(
+        &bitcoin::PublicKey::new(random_public_key()),
+        Network::Bitcoin,
+    )
+    .unwrap()
+    .to_string();
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::WITHDRAW).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_listutxos_readonly() {
+    let result = send(readonly_request(Method::GET, routes::LIST_UTXOS))
+        .await
+        .unwrap();
+    let utxos: Vec<Utxo> = serde_json::from_str(&result).unwrap();
+    let utxo = utxos.first().unwrap();
+    assert_eq!(1, utxo.output);
+    assert_eq!(1000, utxo.value);
+    assert!(utxo.address.as_ref().unwrap().starts_with("bcrt1"));
+    assert_eq!("confirmed", utxo.status);
+    assert_eq!(Some(100), utxo.blockheight);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_listtransactions_readonly() {
+    let result = send(readonly_request(Method::GET, routes::LIST_TRANSACTIONS))
+        .await
+        .unwrap();
+    let transactions: Vec<WalletTransaction> = serde_json::from_str(&result).unwrap();
+    let transaction = transactions.first().unwrap();
+    assert_eq!(1000, transaction.received);
+    assert_eq!(0, transaction.sent);
+    assert_eq!(Some(10), transaction.fee);
+    assert_eq!(None, transaction.blockheight);
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_listchannels_readonly() {
     let result = send(readonly_request(Method::GET, routes::LIST_CHANNELS))
         .await
         .unwrap();
     let channels: Vec<Channel> = serde_json::from_str(&result).unwrap();
-    let channel = channels.get(0).unwrap();
+    let channel = channels.first().unwrap();
     assert_eq!(
         "0202755b475334bd9a56a317fd23dfe264b193bcbd7322faa3e974031704068266",
         channel.id
@@ -132,16 +419,101 @@ async fn test_listchannels_readonly() {
         channel.funding_txid
     );
     assert_eq!("false", channel.private);
-    assert_eq!("", channel.msatoshi_to_us);
-    assert_eq!("1000000", channel.msatoshi_total);
-    assert_eq!("", channel.msatoshi_to_them);
+    assert_eq!("10001", channel.msatoshi_to_us);
+    assert_eq!("1000000000", channel.msatoshi_total);
+    assert_eq!("5200000", channel.msatoshi_to_them);
     assert_eq!("5000", channel.their_channel_reserve_satoshis);
     assert_eq!("10000", channel.our_channel_reserve_satoshis);
     assert_eq!("100000", channel.spendable_msatoshi);
+    assert_eq!("200000", channel.receivable_msatoshi);
     assert_eq!(1, channel.direction);
     assert_eq!("test_node                       ", channel.alias);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_getchannel_readonly() {
+    let result = send(readonly_request(
+        Method::GET,
+        &routes::GET_CHANNEL.replace(":id", &[1u8; 32].encode_hex::<String>()),
+    ))
+    .await
+    .unwrap();
+    let channel: ChannelDetail = serde_json::from_str(&result).unwrap();
+    assert_eq!("10001", channel.channel.msatoshi_to_us);
+    assert_eq!(Some(1000), channel.fee_base_msat);
+    assert_eq!(Some(100), channel.fee_proportional_millionths);
+    assert_eq!(Some(72), channel.cltv_expiry_delta);
+    assert_eq!(Some(10), channel.confirmations);
+    assert_eq!(Some(3), channel.confirmations_required);
+    assert_eq!(
+        Some("0000000000000000000000000000000000000000000000000000000000000000:2".to_string()),
+        channel.funding_outpoint
+    );
+    assert_eq!("", channel.features);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_listforwards_readonly() {
+    let result = send(readonly_request(Method::GET, routes::LIST_FORWARDS))
+        .await
+        .unwrap();
+    let response: ListForwardsResponse = serde_json::from_str(&result).unwrap();
+    assert_eq!(2, response.forwards.len());
+    let forward = response.forwards.first().unwrap();
+    assert_eq!(Some([2u8; 32].encode_hex::<String>()), forward.in_channel);
+    assert_eq!(Some([1u8; 32].encode_hex::<String>()), forward.out_channel);
+    assert_eq!(Some(1020), forward.amount_in_msat);
+    assert_eq!(Some(1000), forward.amount_out_msat);
+    assert_eq!(Some(20), forward.fee_msat);
+    assert_eq!(1000, forward.timestamp);
+    assert_eq!(2, response.fee_totals.len());
+    let total = &response.fee_totals[0];
+    assert_eq!([1u8; 32].encode_hex::<String>(), total.channel_id);
+    assert_eq!(0, total.incoming.forwards);
+    assert_eq!(2, total.outgoing.forwards);
+    assert_eq!(2000, total.outgoing.amount_msat);
+    assert_eq!(30, total.outgoing.fee_msat);
+    let total = &response.fee_totals[1];
+    assert_eq!([2u8; 32].encode_hex::<String>(), total.channel_id);
+    assert_eq!(2, total.incoming.forwards);
+    assert_eq!(2030, total.incoming.amount_msat);
+    assert_eq!(30, total.incoming.fee_msat);
+    assert_eq!(0, total.outgoing.forwards);
+
+    let result = send(readonly_request(
+        Method::GET,
+        &format!("{}?start=600", routes::LIST_FORWARDS),
+    ))
+    .await
+    .unwrap();
+    let response: ListForwardsResponse = serde_json::from_str(&result).unwrap();
+    assert_eq!(1, response.forwards.len());
+    assert_eq!(20, response.fee_totals[0].outgoing.fee_msat);
+    assert_eq!(20, response.fee_totals[1].incoming.fee_msat);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_getchannel_not_found() {
+    let result = send(readonly_request(
+        Method::GET,
+        &routes::GET_CHANNEL.replace(":id", &[9u8; 32].encode_hex::<String>()),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::NOT_FOUND, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_getchannel_bad_id() {
+    let result = send(readonly_request(
+        Method::GET,
+        &routes::GET_CHANNEL.replace(":id", "abcd"),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_openchannel_readonly() {
     let request = fund_channel_request();
@@ -152,6 +524,67 @@ async fn test_openchannel_readonly() {
     assert_eq!(StatusCode::UNAUTHORIZED, result)
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_openchannel_bad_fee_rate() {
+    let mut request = fund_channel_request();
+    request.fee_rate = "4".to_string();
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::OPEN_CHANNEL).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_openchannel_bad_utxo() {
+    let mut request = fund_channel_request();
+    request.utxos = vec!["not_a_txid:0".to_string()];
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::OPEN_CHANNEL).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_openchannel_bad_announce() {
+    let mut request = fund_channel_request();
+    request.announce = "yes".to_string();
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::OPEN_CHANNEL).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_openchannel_close_to() {
+    let mut request = fund_channel_request();
+    request.close_to = Address::p2wpkh(
+        &bitcoin::PublicKey::new(random_public_key()),
+        Network::Bitcoin,
+    )
+    .unwrap()
+    .to_string();
+    let body = serde_json::to_string(&request).unwrap();
+    send(admin_request(Method::POST, routes::OPEN_CHANNEL).body(body))
+        .await
+        .unwrap();
+
+    let mut request = fund_channel_request();
+    request.close_to = Address::p2wpkh(
+        &bitcoin::PublicKey::new(random_public_key()),
+        Network::Testnet,
+    )
+    .unwrap()
+    .to_string();
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::OPEN_CHANNEL).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_openchannel_admin() {
     let request = fund_channel_request();
@@ -170,11 +603,915 @@ async fn test_openchannel_admin() {
     );
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_setchannelfee_readonly() {
+    let request = set_channel_fee_request(ChannelIds::One("all".to_string()));
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(readonly_request(Method::POST, routes::SET_CHANNEL_FEE).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_setchannelfee_admin() {
+    for id in [
+        ChannelIds::One("all".to_string()),
+        ChannelIds::One([1u8; 32].encode_hex()),
+        ChannelIds::Many(vec![[1u8; 32].encode_hex()]),
+    ] {
+        let request = set_channel_fee_request(id);
+        let body = serde_json::to_string(&request).unwrap();
+        let result = send(admin_request(Method::POST, routes::SET_CHANNEL_FEE).body(body))
+            .await
+            .unwrap();
+        let fees: Vec<ChannelFee> = serde_json::from_str(&result).unwrap();
+        let fee = fees.first().unwrap();
+        assert_eq!([1u8; 32].encode_hex::<String>(), fee.channel_id);
+        assert_eq!(5, fee.base);
+        assert_eq!(100, fee.ppm);
+        assert_eq!(72, fee.cltv_expiry_delta);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_setchannelfee_bad_request() {
+    let request = set_channel_fee_request(ChannelIds::Many(vec!["abcd".to_string()]));
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::SET_CHANNEL_FEE).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result);
+
+    let mut request = set_channel_fee_request(ChannelIds::One("all".to_string()));
+    request.cltv_expiry_delta = Some(10);
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::SET_CHANNEL_FEE).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result);
+
+    let request = set_channel_fee_request(ChannelIds::One([9u8; 32].encode_hex()));
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::SET_CHANNEL_FEE).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::NOT_FOUND, result);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_closechannel_readonly() {
+    let request = close_channel_request();
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(readonly_request(Method::POST, routes::CLOSE_CHANNEL).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_closechannel_admin() {
+    let request = close_channel_request();
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::CLOSE_CHANNEL).body(body))
+        .await
+        .unwrap();
+    let response: ChannelCloseResponse = serde_json::from_str(&result).unwrap();
+    assert_eq!(
+        Some("fba98a9a61ef62c081b31769f66a81f1640b4f94d48b550a550034cb4990eded".to_string()),
+        response.txid
+    );
+    assert_eq!("mutual", response.close_type);
+    assert_eq!("broadcast", response.status);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_closechannel_pending() {
+    let request = ChannelClose {
+        channel_id: [2u8; 32].encode_hex(),
+        force: false,
+    };
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::CLOSE_CHANNEL).body(body))
+        .await
+        .unwrap();
+    let response: ChannelCloseResponse = serde_json::from_str(&result).unwrap();
+    assert_eq!(request.channel_id, response.channel_id);
+    assert_eq!("pending", response.status);
+    assert!(response.tx.is_none());
+    assert!(response.txid.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_closechannel_bad_channel_id() {
+    let request = ChannelClose {
+        channel_id: "0101".to_string(),
+        force: true,
+    };
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::CLOSE_CHANNEL).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_generateinvoice_readonly() {
+    let request = generate_invoice_request();
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(readonly_request(Method::POST, routes::GENERATE_INVOICE).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_generateinvoice_admin() {
+    let request = generate_invoice_request();
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::GENERATE_INVOICE).body(body))
+        .await
+        .unwrap();
+    let response: GenerateInvoiceResponse = serde_json::from_str(&result).unwrap();
+    assert_eq!(
+        "0303030303030303030303030303030303030303030303030303030303030303",
+        response.payment_hash
+    );
+    assert_eq!(600, response.expires_at);
+    assert!(response.bolt11.starts_with("lnbcrt"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_listinvoices_readonly() {
+    let result = send(readonly_request(Method::GET, routes::LIST_INVOICES))
+        .await
+        .unwrap();
+    let invoices: Vec<Invoice> = serde_json::from_str(&result).unwrap();
+    let invoice = invoices.first().unwrap();
+    assert_eq!(
+        "0303030303030303030303030303030303030303030303030303030303030303",
+        invoice.payment_hash
+    );
+    assert_eq!("paid", invoice.status);
+    assert_eq!(Some(10000), invoice.msatoshi);
+    assert_eq!(Some(10000), invoice.msatoshi_received);
+    assert_eq!(1000, invoice.expires_at);
+    assert_eq!(Some(500), invoice.paid_at);
+    assert_eq!(
+        Some("0505050505050505050505050505050505050505050505050505050505050505".to_string()),
+        invoice.payment_preimage
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_listinvoices_bad_payment_hash() {
+    let result = send(readonly_request(
+        Method::GET,
+        &format!("{}?payment_hash=xyz", routes::LIST_INVOICES),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_payinvoice_readonly() {
+    let request = pay_invoice_request();
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(readonly_request(Method::POST, routes::PAY_INVOICE).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_payinvoice_admin() {
+    let request = pay_invoice_request();
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::PAY_INVOICE).body(body))
+        .await
+        .unwrap();
+    let response: PaymentResponse = serde_json::from_str(&result).unwrap();
+    assert_eq!(
+        "0303030303030303030303030303030303030303030303030303030303030303",
+        response.payment_hash
+    );
+    assert_eq!("succeeded", response.status);
+    assert_eq!(
+        Some("0505050505050505050505050505050505050505050505050505050505050505".to_string()),
+        response.payment_preimage
+    );
+    assert_eq!(Some(10), response.fee_msat);
+    assert_eq!(Some(2), response.hops);
+    assert!(response.failure.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_payinvoice_pending() {
+    let mut request = pay_invoice_request();
+    request.amount = Some(10000);
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::PAY_INVOICE).body(body))
+        .await
+        .unwrap();
+    let response: PaymentResponse = serde_json::from_str(&result).unwrap();
+    assert_eq!("pending", response.status);
+    assert!(response.payment_preimage.is_none());
+    assert!(response.failure.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_payinvoice_bad_invoice() {
+    let request = PayInvoice {
+        invoice: "lnbc1xyz".to_string(),
+        amount: None,
+    };
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::PAY_INVOICE).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_keysend_readonly() {
+    let body = serde_json::to_string(&keysend_request()).unwrap();
+    let result = send(readonly_request(Method::POST, routes::KEYSEND).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_keysend_admin() {
+    let body = serde_json::to_string(&keysend_request()).unwrap();
+    let result = send(admin_request(Method::POST, routes::KEYSEND).body(body))
+        .await
+        .unwrap();
+    let response: PaymentResponse = serde_json::from_str(&result).unwrap();
+    assert_eq!(
+        "0303030303030303030303030303030303030303030303030303030303030303",
+        response.payment_hash
+    );
+    assert_eq!("failed", response.status);
+    assert!(response.payment_preimage.is_none());
+    let failure = response.failure.unwrap();
+    assert_eq!("Payment rejected by destination", failure.reason);
+    assert!(failure.permanent);
+    assert_eq!(Some(1), failure.short_channel_id);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_keysend_bad_pubkey() {
+    let request = Keysend {
+        pubkey: "02abc".to_string(),
+        amount: 1000,
+    };
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::KEYSEND).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_keysend_custom_records() {
+    let body = serde_json::json!({
+        "pubkey": random_public_key().to_string(),
+        "amount": 1000,
+        "custom_records": { "34349334": "68656c6c6f" }
+    })
+    .to_string();
+    let result = send(admin_request(Method::POST, routes::KEYSEND).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_listpayments_readonly() {
+    let result = send(readonly_request(
+        Method::GET,
+        &format!(
+            "{}?direction=outbound&status=succeeded",
+            routes::LIST_PAYMENTS
+        ),
+    ))
+    .await
+    .unwrap();
+    let payments: Vec<Payment> = serde_json::from_str(&result).unwrap();
+    assert_eq!(1, payments.len());
+    let payment = payments.first().unwrap();
+    assert_eq!(
+        "0303030303030303030303030303030303030303030303030303030303030303",
+        payment.payment_hash
+    );
+    assert_eq!("outbound", payment.direction);
+    assert_eq!("succeeded", payment.status);
+    assert_eq!(Some(10000), payment.msatoshi);
+    assert_eq!(Some(10), payment.fee_msat);
+    assert_eq!(
+        Some("0505050505050505050505050505050505050505050505050505050505050505".to_string()),
+        payment.payment_preimage
+    );
+    assert_eq!(500, payment.created_at);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_listpayments_filtered() {
+    let result = send(readonly_request(
+        Method::GET,
+        &format!("{}?direction=inbound", routes::LIST_PAYMENTS),
+    ))
+    .await
+    .unwrap();
+    let payments: Vec<Payment> = serde_json::from_str(&result).unwrap();
+    assert!(payments.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_listpayments_bad_status() {
+    let result = send(readonly_request(
+        Method::GET,
+        &format!("{}?status=lost", routes::LIST_PAYMENTS),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_getpayment_readonly() {
+    let result = send(readonly_request(
+        Method::GET,
+        &routes::GET_PAYMENT.replace(":payment_hash", &[3u8; 32].encode_hex::<String>()),
+    ))
+    .await
+    .unwrap();
+    let payment: Payment = serde_json::from_str(&result).unwrap();
+    assert_eq!("outbound", payment.direction);
+    assert_eq!("succeeded", payment.status);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_getpayment_not_found() {
+    let result = send(readonly_request(
+        Method::GET,
+        &routes::GET_PAYMENT.replace(":payment_hash", &[9u8; 32].encode_hex::<String>()),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::NOT_FOUND, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_getpayment_bad_hash() {
+    let result = send(readonly_request(
+        Method::GET,
+        &routes::GET_PAYMENT.replace(":payment_hash", "abcd"),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_probe_readonly() {
+    let result = send(readonly_request(Method::POST, routes::PROBE).json(&probe_request(1000)))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_probe_admin() {
+    let result = send(admin_request(Method::POST, routes::PROBE).json(&probe_request(1000)))
+        .await
+        .unwrap();
+    let response: ProbeResponse = serde_json::from_str(&result).unwrap();
+    assert!(response.success);
+    assert_eq!(2, response.hops.len());
+    assert_eq!(COUNTERPARTY, response.hops[0].id);
+    assert!(response.failed_hop.is_none());
+
+    let result = send(admin_request(Method::POST, routes::PROBE).json(&probe_request(2_000_000)))
+        .await
+        .unwrap();
+    let response: ProbeResponse = serde_json::from_str(&result).unwrap();
+    assert!(!response.success);
+    assert_eq!(34234125, response.failed_hop.unwrap().short_channel_id);
+
+    let result =
+        send(admin_request(Method::POST, routes::PROBE).json(&probe_request(2_000_000_000)))
+            .await
+            .unwrap_err();
+    assert_eq!(StatusCode::NOT_FOUND, result);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_probe_invoice() {
+    let result = send(admin_request(Method::POST, routes::PROBE).json(&Probe {
+        pubkey: None,
+        invoice: Some(pay_invoice_request().invoice),
+        amount: None,
+    }))
+    .await
+    .unwrap();
+    let response: ProbeResponse = serde_json::from_str(&result).unwrap();
+    assert!(response.success);
+    let last_hop = response.hops.last().unwrap();
+    assert_eq!(10000, last_hop.fee_msat);
+    assert_eq!(144, last_hop.cltv_expiry_delta);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_probe_bad_request() {
+    let mut no_amount = probe_request(1000);
+    no_amount.amount = None;
+    let mut both = probe_request(1000);
+    both.invoice = Some(pay_invoice_request().invoice);
+    for probe in [no_amount, both] {
+        let result = send(admin_request(Method::POST, routes::PROBE).json(&probe))
+            .await
+            .unwrap_err();
+        assert_eq!(StatusCode::BAD_REQUEST, result)
+    }
+}
+
+fn probe_request(amount: u64) -> Probe {
+    Probe {
+        pubkey: Some(random_public_key().to_string()),
+        invoice: None,
+        amount: Some(amount),
+    }
+}
+
+fn keysend_request() -> Keysend {
+    Keysend {
+        pubkey: random_public_key().to_string(),
+        amount: 1000,
+    }
+}
+
+fn pay_invoice_request() -> PayInvoice {
+    let secp_ctx = Secp256k1::new();
+    let secret_key = SecretKey::from_slice(&[42u8; 32]).unwrap();
+    let invoice = InvoiceBuilder::new(Currency::Bitcoin)
+        .description("test".to_string())
+        .payment_hash(sha256::Hash::from_slice(&[3u8; 32]).unwrap())
+        .payment_secret(PaymentSecret([4u8; 32]))
+        .current_timestamp()
+        .min_final_cltv_expiry(144)
+        .amount_milli_satoshis(10000)
+        .build_signed(|hash| secp_ctx.sign_ecdsa_recoverable(hash, &secret_key))
+        .unwrap();
+    PayInvoice {
+        invoice: invoice.to_string(),
+        amount: None,
+    }
+}
+
+fn generate_invoice_request() -> GenerateInvoice {
+    GenerateInvoice {
+        amount: Some(10000),
+        description: "test".to_string(),
+        expiry: Some(600),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_connectpeer_readonly() {
+    let body = serde_json::to_string(&connect_peer_request()).unwrap();
+    let result = send(readonly_request(Method::POST, routes::CONNECT_PEER).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_connectpeer_admin() {
+    let request = connect_peer_request();
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::CONNECT_PEER).body(body))
+        .await
+        .unwrap();
+    let response: ConnectPeerResponse = serde_json::from_str(&result).unwrap();
+    assert!(request.id.starts_with(&response.id));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_connectpeer_bad_id() {
+    let request = ConnectPeer {
+        id: random_public_key().to_string(),
+    };
+    let body = serde_json::to_string(&request).unwrap();
+    let result = send(admin_request(Method::POST, routes::CONNECT_PEER).body(body))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_listpeers_readonly() {
+    let result = send(readonly_request(Method::GET, routes::LIST_PEERS))
+        .await
+        .unwrap();
+    let peers: Vec<Peer> = serde_json::from_str(&result).unwrap();
+    let peer = peers.first().unwrap();
+    assert_eq!(
+        "0202755b475334bd9a56a317fd23dfe264b193bcbd7322faa3e974031704068266",
+        peer.id
+    );
+    assert!(peer.connected);
+    assert_eq!(Some("127.0.0.1:9735".to_string()), peer.netaddr);
+    assert_eq!(Some("0100".to_string()), peer.features);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_disconnectpeer_readonly() {
+    let result = send(readonly_request(
+        Method::DELETE,
+        &routes::DISCONNECT_PEER.replace(":id", &random_public_key().to_string()),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_disconnectpeer_admin() {
+    let route = format!(
+        "{}?forget=true",
+        routes::DISCONNECT_PEER.replace(":id", &random_public_key().to_string())
+    );
+    send(admin_request(Method::DELETE, &route)).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_disconnectpeer_bad_id() {
+    let result = send(admin_request(
+        Method::DELETE,
+        &routes::DISCONNECT_PEER.replace(":id", "abcd"),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_listnodes_readonly() {
+    let result = send(readonly_request(Method::GET, routes::LIST_NODES))
+        .await
+        .unwrap();
+    let nodes: Vec<NetworkNode> = serde_json::from_str(&result).unwrap();
+    let node = nodes.first().unwrap();
+    assert_eq!(COUNTERPARTY, node.id);
+    assert_eq!(Some("030201".to_string()), node.color);
+    assert_eq!(Some(1000), node.last_update);
+
+    let result = send(readonly_request(
+        Method::GET,
+        &format!("{}?after={}", routes::LIST_NODES, COUNTERPARTY),
+    ))
+    .await
+    .unwrap();
+    let nodes: Vec<NetworkNode> = serde_json::from_str(&result).unwrap();
+    assert!(nodes.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_getnode_readonly() {
+    let result = send(readonly_request(
+        Method::GET,
+        &routes::GET_NODE.replace(":id", COUNTERPARTY),
+    ))
+    .await
+    .unwrap();
+    let node: NetworkNode = serde_json::from_str(&result).unwrap();
+    assert_eq!(COUNTERPARTY, node.id);
+    assert_eq!(Some("".to_string()), node.features);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_getnode_bad_id() {
+    let result = send(readonly_request(
+        Method::GET,
+        &routes::GET_NODE.replace(":id", "abcd"),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_listnetworkchannels_readonly() {
+    let result = send(readonly_request(Method::GET, routes::LIST_NETWORK_CHANNELS))
+        .await
+        .unwrap();
+    let channels: Vec<NetworkChannel> = serde_json::from_str(&result).unwrap();
+    let channel = channels.first().unwrap();
+    assert_eq!(34234125, channel.short_channel_id);
+    assert_eq!(COUNTERPARTY, channel.node_one);
+    let policy = channel.one_to_two.as_ref().unwrap();
+    assert_eq!(1000, policy.base_fee_msat);
+    assert_eq!(10, policy.fee_proportional_millionths);
+    assert_eq!(40, policy.cltv_expiry_delta);
+    assert!(channel.two_to_one.is_none());
+
+    let result = send(readonly_request(
+        Method::GET,
+        &format!("{}?after=34234125", routes::LIST_NETWORK_CHANNELS),
+    ))
+    .await
+    .unwrap();
+    let channels: Vec<NetworkChannel> = serde_json::from_str(&result).unwrap();
+    assert!(channels.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_getnetworkchannel_readonly() {
+    let result = send(readonly_request(
+        Method::GET,
+        &routes::GET_NETWORK_CHANNEL.replace(":short_channel_id", "34234125"),
+    ))
+    .await
+    .unwrap();
+    let channel: NetworkChannel = serde_json::from_str(&result).unwrap();
+    assert_eq!(34234125, channel.short_channel_id);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_getnetworkchannel_not_found() {
+    let result = send(readonly_request(
+        Method::GET,
+        &routes::GET_NETWORK_CHANNEL.replace(":short_channel_id", "1"),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::NOT_FOUND, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_getnetworkchannel_bad_id() {
+    let result = send(readonly_request(
+        Method::GET,
+        &routes::GET_NETWORK_CHANNEL.replace(":short_channel_id", "abcd"),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_getroute_readonly() {
+    let payee = random_public_key().to_string();
+    let result = send(readonly_request(
+        Method::GET,
+        &format!(
+            "{}?final_cltv=30",
+            routes::GET_ROUTE
+                .replace(":id", &payee)
+                .replace(":msatoshi", "50000")
+        ),
+    ))
+    .await
+    .unwrap();
+    let route: Route = serde_json::from_str(&result).unwrap();
+    assert_eq!(1010, route.fee_msat);
+    assert_eq!(50000, route.amount_msat);
+    let path = route.paths.first().unwrap();
+    assert_eq!(1010, path.fee_msat);
+    assert_eq!(102, path.cltv_expiry_delta);
+    assert_eq!(COUNTERPARTY, path.hops[0].id);
+    assert_eq!(payee, path.hops[1].id);
+    assert_eq!(30, path.hops[1].cltv_expiry_delta);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_getroute_bad_request() {
+    let result = send(readonly_request(
+        Method::GET,
+        &routes::GET_ROUTE
+            .replace(":id", "abcd")
+            .replace(":msatoshi", "50000"),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result);
+
+    let result = send(readonly_request(
+        Method::GET,
+        &routes::GET_ROUTE
+            .replace(":id", COUNTERPARTY)
+            .replace(":msatoshi", "lots"),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_interceptscid_readonly() {
+    let result = send(readonly_request(Method::POST, routes::INTERCEPT_SCID).json(
+        &InterceptScid {
+            id: random_public_key().to_string(),
+        },
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_interceptscid_admin() {
+    let result = send(
+        admin_request(Method::POST, routes::INTERCEPT_SCID).json(&InterceptScid {
+            id: random_public_key().to_string(),
+        }),
+    )
+    .await
+    .unwrap();
+    let hint: RouteHint = serde_json::from_str(&result).unwrap();
+    assert_eq!(COUNTERPARTY, hint.node_id);
+    assert_eq!(123456789, hint.short_channel_id);
+    assert_eq!(1000, hint.fee_base_msat);
+    assert_eq!(100, hint.fee_proportional_millionths);
+    assert_eq!(72, hint.cltv_expiry_delta);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_interceptscid_bad_id() {
+    let result = send(
+        admin_request(Method::POST, routes::INTERCEPT_SCID).json(&InterceptScid {
+            id: "abcd".to_string(),
+        }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_scorerchannel_readonly() {
+    let result = send(readonly_request(
+        Method::GET,
+        &routes::SCORER_CHANNEL.replace(":short_channel_id", "34234125"),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_scorerchannel_admin() {
+    let result = send(admin_request(
+        Method::GET,
+        &routes::SCORER_CHANNEL.replace(":short_channel_id", "34234125"),
+    ))
+    .await
+    .unwrap();
+    let liquidity: ChannelLiquidity = serde_json::from_str(&result).unwrap();
+    assert_eq!(34234125, liquidity.short_channel_id);
+    let (from_counterparty, to_counterparty) = if liquidity.node_one == COUNTERPARTY {
+        (liquidity.one_to_two, liquidity.two_to_one)
+    } else {
+        (liquidity.two_to_one, liquidity.one_to_two)
+    };
+    assert_eq!(
+        Some(LiquidityRange {
+            min_msat: 0,
+            max_msat: 400_000
+        }),
+        from_counterparty
+    );
+    assert_eq!(None, to_counterparty);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_scorerchannel_not_found() {
+    let result = send(admin_request(
+        Method::GET,
+        &routes::SCORER_CHANNEL.replace(":short_channel_id", "1"),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::NOT_FOUND, result);
+
+    let result = send(admin_request(
+        Method::GET,
+        &routes::SCORER_CHANNEL.replace(":short_channel_id", "abc"),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_bannode_readonly() {
+    let result = send(readonly_request(
+        Method::POST,
+        &routes::BAN_NODE.replace(":id", &random_public_key().to_string()),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_bannode_admin() {
+    let id = random_public_key().to_string();
+    send(admin_request(
+        Method::POST,
+        &routes::BAN_NODE.replace(":id", &id),
+    ))
+    .await
+    .unwrap();
+    send(admin_request(
+        Method::POST,
+        &routes::UNBAN_NODE.replace(":id", &id),
+    ))
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_bannode_bad_id() {
+    let result = send(admin_request(
+        Method::POST,
+        &routes::BAN_NODE.replace(":id", "abcd"),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result);
+
+    let result = send(admin_request(
+        Method::POST,
+        &routes::UNBAN_NODE.replace(":id", "abcd"),
+    ))
+    .await
+    .unwrap_err();
+    assert_eq!(StatusCode::BAD_REQUEST, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_resetscorer_readonly() {
+    let result = send(readonly_request(Method::POST, routes::RESET_SCORER))
+        .await
+        .unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, result)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_resetscorer_admin() {
+    send(admin_request(Method::POST, routes::RESET_SCORER))
+        .await
+        .unwrap();
+}
+
+// The counterparty of the mock channel which is also the node in the mock network graph.
+const COUNTERPARTY: &str = "0202755b475334bd9a56a317fd23dfe264b193bcbd7322faa3e974031704068266";
+
+fn withdraw_request() -> Withdraw {
+    Withdraw {
+        address: Address::p2wpkh(
+            &bitcoin::PublicKey::new(random_public_key()),
+            Network::Regtest,
+        )
+        .unwrap()
+        .to_string(),
+        satoshis: "all".to_string(),
+        fee_rate: Some("urgent".to_string()),
+    }
+}
+
+fn connect_peer_request() -> ConnectPeer {
+    ConnectPeer {
+        id: format!("{}@127.0.0.1:9735", random_public_key()),
+    }
+}
+
+fn close_channel_request() -> ChannelClose {
+    ChannelClose {
+        channel_id: [1u8; 32].encode_hex(),
+        force: false,
+    }
+}
+
+fn set_channel_fee_request(id: ChannelIds) -> SetChannelFee {
+    SetChannelFee {
+        id,
+        base: Some(5),
+        ppm: None,
+        cltv_expiry_delta: None,
+        max_dust_htlc_exposure_msat: None,
+    }
+}
+
 fn fund_channel_request() -> FundChannel {
     FundChannel {
         id: random_public_key().serialize().encode_hex(),
         satoshis: "21000000".to_string(),
-        fee_rate: "4".to_string(),
+        fee_rate: "1000perkw".to_string(),
         announce: "true".to_string(),
         push_msat: "10000".to_string(),
         close_to: "".to_string(),
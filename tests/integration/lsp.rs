@@ -0,0 +1,264 @@
+use std::time::Duration;
+
+use lightning::chain::keysinterface::KeysInterface;
+use lightning::ln::channelmanager::{self, PaymentId, MIN_CLTV_EXPIRY_DELTA};
+use lightning::ln::functional_test_utils::*;
+use lightning::ln::msgs::ChannelMessageHandler;
+use lightning::routing::gossip::RoutingFees;
+use lightning::routing::router::{
+    find_route, PaymentParameters, RouteHint, RouteHintHop, RouteParameters,
+};
+use lightning::util::events::{Event, MessageSendEvent, MessageSendEventsProvider};
+use lightning::util::test_utils::TestScorer;
+use lightning::{
+    check_added_monitors, commitment_signed_dance, expect_htlc_handling_failed_destinations,
+    expect_payment_claimable, expect_pending_htlcs_forwardable,
+    expect_pending_htlcs_forwardable_conditions, expect_pending_htlcs_forwardable_ignore,
+    get_event_msg, get_htlc_update_msgs, get_revoke_commit_msgs,
+};
+use lightning_knd::lsp::Interceptor;
+
+// Node 0 pays node 2 through an intercept scid of node 1, which has no channel to node 2 yet.
+#[test]
+fn test_intercept_open_forward() {
+    let chanmon_cfgs = create_chanmon_cfgs(3);
+    let node_cfgs = create_node_cfgs(3, &chanmon_cfgs);
+    let mut lsp_config = test_default_channel_config();
+    lsp_config.accept_intercept_htlcs = true;
+    let mut client_config = test_default_channel_config();
+    client_config.manually_accept_inbound_channels = true;
+    let node_chanmgrs = create_node_chanmgrs(
+        3,
+        &node_cfgs,
+        &[None, Some(lsp_config), Some(client_config)],
+    );
+    let nodes = create_network(3, &node_cfgs, &node_chanmgrs);
+    let lsp_id = nodes[1].node.get_our_node_id();
+    let client_id = nodes[2].node.get_our_node_id();
+    create_announced_chan_between_nodes(
+        &nodes,
+        0,
+        1,
+        channelmanager::provided_init_features(),
+        channelmanager::provided_init_features(),
+    );
+    let interceptor = Interceptor::new(nodes[1].node, Duration::from_secs(60));
+
+    let amount_msat = 100_000;
+    let intercept_scid = nodes[1].node.get_intercept_scid();
+    let payment_params = PaymentParameters::from_node_id(client_id)
+        .with_route_hints(vec![RouteHint(vec![RouteHintHop {
+            src_node_id: lsp_id,
+            short_channel_id: intercept_scid,
+            fees: RoutingFees {
+                base_msat: 1000,
+                proportional_millionths: 0,
+            },
+            cltv_expiry_delta: MIN_CLTV_EXPIRY_DELTA,
+            htlc_minimum_msat: None,
+            htlc_maximum_msat: None,
+        }])])
+        .with_features(channelmanager::provided_invoice_features());
+    let route_params = RouteParameters {
+        payment_params,
+        final_value_msat: amount_msat,
+        final_cltv_expiry_delta: TEST_FINAL_CLTV,
+    };
+    let route = find_route(
+        &nodes[0].node.get_our_node_id(),
+        &route_params,
+        nodes[0].network_graph,
+        None,
+        nodes[0].logger,
+        &TestScorer::with_penalty(0),
+        &chanmon_cfgs[0].keys_manager.get_secure_random_bytes(),
+    )
+    .unwrap();
+    let (payment_hash, payment_secret) = nodes[2]
+        .node
+        .create_inbound_payment(Some(amount_msat), 3600)
+        .unwrap();
+    nodes[0]
+        .node
+        .send_payment(
+            &route,
+            payment_hash,
+            &Some(payment_secret),
+            PaymentId(payment_hash.0),
+        )
+        .unwrap();
+    check_added_monitors!(nodes[0], 1);
+    let update_add = get_htlc_update_msgs!(nodes[0], lsp_id);
+    nodes[1].node.handle_update_add_htlc(
+        &nodes[0].node.get_our_node_id(),
+        &update_add.update_add_htlcs[0],
+    );
+    commitment_signed_dance!(
+        nodes[1],
+        nodes[0],
+        &update_add.commitment_signed,
+        false,
+        true
+    );
+
+    let events = nodes[1].node.get_and_clear_pending_events();
+    assert_eq!(events.len(), 1);
+    match events[0] {
+        Event::HTLCIntercepted {
+            intercept_id,
+            requested_next_hop_scid,
+            payment_hash,
+            expected_outbound_amount_msat,
+            ..
+        } => {
+            assert_eq!(intercept_scid, requested_next_hop_scid);
+            assert!(interceptor
+                .intercept(
+                    intercept_id,
+                    client_id,
+                    payment_hash,
+                    expected_outbound_amount_msat,
+                )
+                .is_some());
+        }
+        _ => panic!("Unexpected event"),
+    }
+
+    // The client accepts the channel with zero confirmations.
+    let open_channel = get_event_msg!(nodes[1], MessageSendEvent::SendOpenChannel, client_id);
+    // Not announced.
+    assert_eq!(0, open_channel.channel_flags & 1);
+    nodes[2].node.handle_open_channel(
+        &lsp_id,
+        channelmanager::provided_init_features(),
+        &open_channel,
+    );
+    let events = nodes[2].node.get_and_clear_pending_events();
+    assert_eq!(events.len(), 1);
+    match events[0] {
+        Event::OpenChannelRequest {
+            temporary_channel_id,
+            ..
+        } => nodes[2]
+            .node
+            .accept_inbound_channel_from_trusted_peer_0conf(&temporary_channel_id, &lsp_id, 0)
+            .unwrap(),
+        _ => panic!("Unexpected event"),
+    }
+    let accept_channel = get_event_msg!(nodes[2], MessageSendEvent::SendAcceptChannel, lsp_id);
+    assert_eq!(0, accept_channel.minimum_depth);
+    nodes[1].node.handle_accept_channel(
+        &client_id,
+        channelmanager::provided_init_features(),
+        &accept_channel,
+    );
+
+    let events = nodes[1].node.get_and_clear_pending_events();
+    assert_eq!(events.len(), 1);
+    let (temporary_channel_id, funding_tx) = match &events[0] {
+        Event::FundingGenerationReady {
+            temporary_channel_id,
+            channel_value_satoshis,
+            output_script,
+            ..
+        } => (
+            *temporary_channel_id,
+            bitcoin::Transaction {
+                version: 2,
+                lock_time: bitcoin::PackedLockTime::ZERO,
+                input: vec![],
+                output: vec![bitcoin::TxOut {
+                    value: *channel_value_satoshis,
+                    script_pubkey: output_script.clone(),
+                }],
+            },
+        ),
+        _ => panic!("Unexpected event"),
+    };
+    nodes[1]
+        .node
+        .funding_transaction_generated(&temporary_channel_id, &client_id, funding_tx)
+        .unwrap();
+    let funding_created = get_event_msg!(nodes[1], MessageSendEvent::SendFundingCreated, client_id);
+    nodes[2]
+        .node
+        .handle_funding_created(&lsp_id, &funding_created);
+    check_added_monitors!(nodes[2], 1);
+    let msg_events = nodes[2].node.get_and_clear_pending_msg_events();
+    assert_eq!(msg_events.len(), 2);
+    for msg_event in msg_events {
+        match msg_event {
+            MessageSendEvent::SendFundingSigned { msg, .. } => {
+                nodes[1].node.handle_funding_signed(&client_id, &msg);
+                check_added_monitors!(nodes[1], 1);
+            }
+            MessageSendEvent::SendChannelReady { msg, .. } => {
+                nodes[1].node.handle_channel_ready(&client_id, &msg)
+            }
+            _ => panic!("Unexpected event"),
+        }
+    }
+    for msg_event in nodes[1].node.get_and_clear_pending_msg_events() {
+        match msg_event {
+            MessageSendEvent::SendChannelReady { msg, .. } => {
+                nodes[2].node.handle_channel_ready(&lsp_id, &msg)
+            }
+            MessageSendEvent::SendChannelUpdate { msg, .. } => {
+                nodes[2].node.handle_channel_update(&lsp_id, &msg)
+            }
+            _ => panic!("Unexpected event"),
+        }
+    }
+    let client_update = get_event_msg!(nodes[2], MessageSendEvent::SendChannelUpdate, lsp_id);
+    nodes[1]
+        .node
+        .handle_channel_update(&client_id, &client_update);
+    nodes[1]
+        .tx_broadcaster
+        .txn_broadcasted
+        .lock()
+        .unwrap()
+        .clear();
+    expect_channel_ready_event(&nodes[2], &lsp_id);
+
+    // The channel is ready before the funding transaction confirms.
+    let events = nodes[1].node.get_and_clear_pending_events();
+    assert_eq!(events.len(), 1);
+    match events[0] {
+        Event::ChannelReady {
+            channel_id,
+            user_channel_id,
+            counterparty_node_id,
+            ..
+        } => interceptor.channel_ready(user_channel_id, &channel_id, counterparty_node_id),
+        _ => panic!("Unexpected event"),
+    }
+
+    expect_pending_htlcs_forwardable!(nodes[1]);
+    check_added_monitors!(nodes[1], 1);
+    let update_add = get_htlc_update_msgs!(nodes[1], client_id);
+    nodes[2]
+        .node
+        .handle_update_add_htlc(&lsp_id, &update_add.update_add_htlcs[0]);
+    commitment_signed_dance!(
+        nodes[2],
+        nodes[1],
+        &update_add.commitment_signed,
+        false,
+        true
+    );
+    expect_pending_htlcs_forwardable!(nodes[2]);
+    let payment_preimage = nodes[2]
+        .node
+        .get_payment_preimage(payment_hash, payment_secret)
+        .unwrap();
+    expect_payment_claimable!(
+        nodes[2],
+        payment_hash,
+        payment_secret,
+        amount_msat,
+        Some(payment_preimage),
+        client_id
+    );
+    claim_payment(&nodes[0], &[&nodes[1], &nodes[2]], payment_preimage);
+}
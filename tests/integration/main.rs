@@ -1,6 +1,7 @@
 use tokio::signal::unix::SignalKind;
 
 pub mod api;
+mod lsp;
 mod mock_lightning;
 mod mock_wallet;
 pub mod prometheus;
@@ -1,21 +1,43 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
 use anyhow::Result;
 use async_trait::async_trait;
-use bitcoin::{consensus::deserialize, hashes::Hash, secp256k1::PublicKey, Network, Txid};
+use bitcoin::{
+    blockdata::constants::genesis_block, consensus::deserialize, hashes::Hash,
+    secp256k1::PublicKey, Network, Txid,
+};
+use database::forward::Forward;
+use database::invoice::{Invoice, InvoiceStatus};
+use database::payment::{Payment, PaymentDirection, PaymentStatus};
 use hex::FromHex;
 use lightning::{
     chain::transaction::OutPoint,
     ln::{
         channelmanager::{ChannelCounterparty, ChannelDetails},
-        features::{InitFeatures, NodeFeatures},
+        features::{ChannelFeatures, InitFeatures, NodeFeatures},
+        msgs::UnsignedChannelUpdate,
+        script::ShutdownScript,
+        PaymentHash, PaymentPreimage, PaymentSecret,
+    },
+    routing::{
+        gossip::RoutingFees,
+        gossip::{ChannelInfo, NetworkGraph, NodeAlias, NodeAnnouncementInfo, NodeId, NodeInfo},
+        router::{PaymentParameters, Route, RouteHintHop, RouteHop},
     },
-    routing::gossip::{NodeAlias, NodeAnnouncementInfo, NodeInfo},
-    util::config::UserConfig,
+    util::config::ChannelConfig,
 };
-use lightning_knd::api::{LightningInterface, OpenChannelResult};
+use lightning_knd::api::{
+    ChannelConfigUpdate, CloseChannelResult, FundingOptions, LightningInterface, OpenChannelResult,
+    PaymentOutcome, Peer, ProbeOutcome,
+};
+use logger::KndLogger;
 use test_utils::random_public_key;
 
 pub struct MockLightning {
     pub num_peers: usize,
+    pub synced_to_chain: bool,
     pub num_nodes: usize,
     pub num_channels: usize,
     pub wallet_balance: u64,
@@ -62,10 +84,16 @@ impl Default for MockLightning {
             is_public: true,
             inbound_htlc_minimum_msat: Some(300),
             inbound_htlc_maximum_msat: Some(300000),
-            config: None,
+            config: Some(ChannelConfig {
+                forwarding_fee_base_msat: 1000,
+                forwarding_fee_proportional_millionths: 100,
+                cltv_expiry_delta: 72,
+                ..Default::default()
+            }),
         };
         Self {
             num_peers: 5,
+            synced_to_chain: true,
             num_nodes: 6,
             num_channels: 7,
             wallet_balance: 8,
@@ -98,6 +126,10 @@ impl LightningInterface for MockLightning {
     fn network(&self) -> bitcoin::Network {
         Network::Bitcoin
     }
+    fn synced_to_chain(&self) -> bool {
+        self.synced_to_chain
+    }
+
     fn num_active_channels(&self) -> usize {
         0
     }
@@ -117,6 +149,14 @@ impl LightningInterface for MockLightning {
         self.wallet_balance
     }
 
+    fn pending_monitor_writes(&self) -> usize {
+        0
+    }
+
+    fn failed_monitor_writes(&self) -> u64 {
+        0
+    }
+
     fn version(&self) -> String {
         "v0.1".to_string()
     }
@@ -140,12 +180,91 @@ impl LightningInterface for MockLightning {
         })
     }
 
+    fn graph_nodes(&self, after: Option<NodeId>, limit: usize) -> Vec<(NodeId, NodeInfo)> {
+        let node_id = NodeId::from_pubkey(&self.channels[0].counterparty.node_id);
+        let node = self
+            .get_node(self.channels[0].counterparty.node_id)
+            .unwrap();
+        vec![(node_id, node)]
+            .into_iter()
+            .filter(|(id, _)| after.is_none_or(|after| *id > after))
+            .take(limit)
+            .collect()
+    }
+
+    fn graph_channels(&self, after: Option<u64>, limit: usize) -> Vec<(u64, ChannelInfo)> {
+        let graph = network_graph();
+        let channels = graph
+            .read_only()
+            .channels()
+            .iter()
+            .filter(|(scid, _)| after.is_none_or(|after| **scid > after))
+            .take(limit)
+            .map(|(scid, channel)| (*scid, channel.clone()))
+            .collect();
+        channels
+    }
+
+    fn graph_channel(&self, short_channel_id: u64) -> Option<ChannelInfo> {
+        let graph = network_graph();
+        let channel = graph.read_only().channel(short_channel_id).cloned();
+        channel
+    }
+
+    fn find_route(
+        &self,
+        payee: PublicKey,
+        amount_msat: u64,
+        final_cltv_expiry_delta: u32,
+    ) -> Result<Route> {
+        let hop = |pubkey, short_channel_id, fee_msat, cltv_expiry_delta| RouteHop {
+            pubkey,
+            node_features: NodeFeatures::empty(),
+            short_channel_id,
+            channel_features: ChannelFeatures::empty(),
+            fee_msat,
+            cltv_expiry_delta,
+        };
+        Ok(Route {
+            paths: vec![vec![
+                hop(self.channels[0].counterparty.node_id, 34234124, 1010, 72),
+                hop(payee, 34234125, amount_msat, final_cltv_expiry_delta),
+            ]],
+            payment_params: None,
+        })
+    }
+
+    async fn connect_peer(&self, _public_key: PublicKey, _peer_address: SocketAddr) -> Result<()> {
+        Ok(())
+    }
+
+    async fn list_peers(&self) -> Result<Vec<Peer>> {
+        let mut node_features = NodeFeatures::empty();
+        node_features.set_variable_length_onion_required();
+        Ok(vec![Peer {
+            public_key: PublicKey::from_slice(&[
+                2, 2, 117, 91, 71, 83, 52, 189, 154, 86, 163, 23, 253, 35, 223, 226, 100, 177, 147,
+                188, 189, 115, 34, 250, 163, 233, 116, 3, 23, 4, 6, 130, 102,
+            ])
+            .unwrap(),
+            socket_addr: Some("127.0.0.1:9735".parse().unwrap()),
+            connected: true,
+            node_features: Some(node_features),
+        }])
+    }
+
+    async fn disconnect_peer(&self, _public_key: PublicKey, _forget: bool) -> Result<()> {
+        Ok(())
+    }
+
     async fn open_channel(
         &self,
         _their_network_key: PublicKey,
         _channel_value_satoshis: u64,
         _push_msat: u64,
-        _override_config: Option<UserConfig>,
+        _funding: FundingOptions,
+        _announce: bool,
+        _close_to: Option<ShutdownScript>,
     ) -> Result<OpenChannelResult> {
         let transaction =
             deserialize::<bitcoin::Transaction>(&Vec::<u8>::from_hex(TEST_TX).unwrap()).unwrap();
@@ -156,11 +275,258 @@ impl LightningInterface for MockLightning {
             channel_id: [1u8; 32],
         })
     }
+
+    fn set_channel_fee(
+        &self,
+        channel_ids: Option<Vec<[u8; 32]>>,
+        update: ChannelConfigUpdate,
+    ) -> Result<Vec<([u8; 32], ChannelConfig)>> {
+        Ok(self
+            .channels
+            .iter()
+            .filter(|c| {
+                channel_ids
+                    .as_ref()
+                    .is_none_or(|ids| ids.contains(&c.channel_id))
+            })
+            .map(|c| {
+                let mut config = c.config.unwrap_or_default();
+                update.apply(&mut config);
+                (c.channel_id, config)
+            })
+            .collect())
+    }
+
+    async fn close_channel(
+        &self,
+        channel_id: &[u8; 32],
+        _force: bool,
+    ) -> Result<CloseChannelResult> {
+        // The peer of channel 2 does not respond.
+        let transaction = if *channel_id == [2u8; 32] {
+            None
+        } else {
+            Some(
+                deserialize::<bitcoin::Transaction>(&Vec::<u8>::from_hex(TEST_TX).unwrap())
+                    .unwrap(),
+            )
+        };
+        Ok(CloseChannelResult {
+            channel_id: *channel_id,
+            transaction,
+        })
+    }
+
+    async fn create_invoice(
+        &self,
+        amount_msat: Option<u64>,
+        description: String,
+        expiry_secs: u32,
+    ) -> Result<Invoice> {
+        Ok(Invoice {
+            payment_hash: PaymentHash([3u8; 32]),
+            payment_secret: PaymentSecret([4u8; 32]),
+            bolt11: TEST_INVOICE.to_string(),
+            amount_msat,
+            description,
+            expires_at: SystemTime::UNIX_EPOCH + Duration::from_secs(expiry_secs as u64),
+            status: InvoiceStatus::Unpaid,
+            preimage: None,
+            amount_received_msat: None,
+            paid_at: None,
+        })
+    }
+
+    async fn list_invoices(&self, _payment_hash: Option<PaymentHash>) -> Result<Vec<Invoice>> {
+        Ok(vec![Invoice {
+            payment_hash: PaymentHash([3u8; 32]),
+            payment_secret: PaymentSecret([4u8; 32]),
+            bolt11: TEST_INVOICE.to_string(),
+            amount_msat: Some(10000),
+            description: "test".to_string(),
+            expires_at: SystemTime::UNIX_EPOCH + Duration::from_secs(1000),
+            status: InvoiceStatus::Paid,
+            preimage: Some(PaymentPreimage([5u8; 32])),
+            amount_received_msat: Some(10000),
+            paid_at: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(500)),
+        }])
+    }
+
+    async fn pay_invoice(
+        &self,
+        _invoice: lightning_invoice::Invoice,
+        amount_msat: Option<u64>,
+    ) -> Result<PaymentOutcome> {
+        // Stands in for a payment that is still in flight when we stop waiting.
+        if amount_msat.is_some() {
+            return Ok(PaymentOutcome::Pending);
+        }
+        Ok(PaymentOutcome::Succeeded {
+            preimage: PaymentPreimage([5u8; 32]),
+            fee_paid_msat: Some(10),
+            hops: 2,
+        })
+    }
+
+    async fn list_forwards(
+        &self,
+        start: Option<SystemTime>,
+        end: Option<SystemTime>,
+    ) -> Result<Vec<Forward>> {
+        let forward = |fee, secs| {
+            let mut forward = Forward::new(Some([2u8; 32]), Some([1u8; 32]), Some(fee), false);
+            forward.amount_in_msat = Some(1000 + fee);
+            forward.amount_out_msat = Some(1000);
+            forward.timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+            forward
+        };
+        Ok(vec![forward(20, 1000), forward(10, 500)]
+            .into_iter()
+            .filter(|f| start.is_none_or(|start| f.timestamp >= start))
+            .filter(|f| end.is_none_or(|end| f.timestamp < end))
+            .collect())
+    }
+
+    async fn list_payments(
+        &self,
+        payment_hash: Option<PaymentHash>,
+        direction: Option<PaymentDirection>,
+        status: Option<PaymentStatus>,
+    ) -> Result<Vec<Payment>> {
+        let mut payment = Payment::new(
+            PaymentHash([3u8; 32]),
+            PaymentDirection::Outbound,
+            PaymentStatus::Succeeded,
+            Some(10000),
+        );
+        payment.preimage = Some(PaymentPreimage([5u8; 32]));
+        payment.fee_msat = Some(10);
+        payment.timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(500);
+        Ok(vec![payment]
+            .into_iter()
+            .filter(|p| payment_hash.is_none_or(|x| x == p.payment_hash))
+            .filter(|p| direction.is_none_or(|x| x == p.direction))
+            .filter(|p| status.is_none_or(|x| x == p.status))
+            .collect())
+    }
+
+    async fn keysend(
+        &self,
+        _payee_pubkey: PublicKey,
+        _amount_msat: u64,
+    ) -> Result<(PaymentHash, PaymentOutcome)> {
+        Ok((
+            PaymentHash([3u8; 32]),
+            PaymentOutcome::Failed {
+                reason: "Payment rejected by destination".to_string(),
+                permanent: true,
+                short_channel_id: Some(1),
+            },
+        ))
+    }
+
+    async fn probe(
+        &self,
+        payment_params: PaymentParameters,
+        amount_msat: u64,
+        final_cltv_expiry_delta: u32,
+    ) -> Result<ProbeOutcome> {
+        if amount_msat > 1_000_000_000 {
+            return Ok(ProbeOutcome::NoRoute {
+                reason: "Not enough liquidity".to_string(),
+            });
+        }
+        let path = self
+            .find_route(
+                payment_params.payee_pubkey,
+                amount_msat,
+                final_cltv_expiry_delta,
+            )?
+            .paths
+            .remove(0);
+        // Pretend the last channel can't carry large amounts.
+        if amount_msat > 1_000_000 {
+            Ok(ProbeOutcome::Failed {
+                path,
+                short_channel_id: Some(34234125),
+            })
+        } else {
+            Ok(ProbeOutcome::Succeeded { path })
+        }
+    }
+
+    fn estimated_channel_liquidity_range(
+        &self,
+        short_channel_id: u64,
+        target: &NodeId,
+    ) -> Option<(u64, u64)> {
+        // Only learned in the direction away from the counterparty.
+        let counterparty = NodeId::from_pubkey(&self.channels[0].counterparty.node_id);
+        (short_channel_id == 34234125 && *target != counterparty).then_some((0, 400_000))
+    }
+
+    async fn ban_node(&self, _node_id: PublicKey) -> Result<()> {
+        Ok(())
+    }
+
+    async fn unban_node(&self, _node_id: PublicKey) -> Result<()> {
+        Ok(())
+    }
+
+    async fn reset_scorer(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn create_intercept_scid(&self, _node_id: PublicKey) -> Result<RouteHintHop> {
+        Ok(RouteHintHop {
+            src_node_id: self.channels[0].counterparty.node_id,
+            short_channel_id: 123456789,
+            fees: RoutingFees {
+                base_msat: 1000,
+                proportional_millionths: 100,
+            },
+            cltv_expiry_delta: 72,
+            htlc_minimum_msat: None,
+            htlc_maximum_msat: None,
+        })
+    }
 }
 
-const TEST_TX: &str = "0200000003c26f3eb7932f7acddc5ddd26602b77e7516079b03090a16e2c2f54\
+pub const TEST_TX: &str = "0200000003c26f3eb7932f7acddc5ddd26602b77e7516079b03090a16e2c2f54\
                                     85d1fd600f0100000000ffffffffc26f3eb7932f7acddc5ddd26602b77e75160\
                                     79b03090a16e2c2f5485d1fd600f0000000000ffffffff571fb3e02278217852\
                                     dd5d299947e2b7354a639adc32ec1fa7b82cfb5dec530e0500000000ffffffff\
                                     03e80300000000000002aaeee80300000000000001aa200300000000000001ff\
                                     00000000";
+
+const TEST_INVOICE: &str = "lnbcrt100n1p3h6lr7pp5qvpsxqcrqvpsxqcrqvpsxqcrqvpsxqcrqvpsxqcrqvpsxqcrqvpsqdqqcqzpgsp5qszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqs9qyyssq";
+
+// A graph with a single channel from the counterparty of our channel, added without an announcement.
+fn network_graph() -> NetworkGraph<Arc<KndLogger>> {
+    let genesis_hash = genesis_block(Network::Regtest).header.block_hash();
+    let graph = NetworkGraph::new(genesis_hash, KndLogger::global());
+    graph
+        .add_channel_from_partial_announcement(
+            34234125,
+            1000,
+            ChannelFeatures::empty(),
+            MockLightning::default().channels[0].counterparty.node_id,
+            random_public_key(),
+        )
+        .unwrap();
+    graph
+        .update_channel_unsigned(&UnsignedChannelUpdate {
+            chain_hash: genesis_hash,
+            short_channel_id: 34234125,
+            timestamp: 1000,
+            flags: 0,
+            cltv_expiry_delta: 40,
+            htlc_minimum_msat: 1,
+            htlc_maximum_msat: 100_000_000,
+            fee_base_msat: 1000,
+            fee_proportional_millionths: 10,
+            excess_data: vec![],
+        })
+        .unwrap();
+    graph
+}
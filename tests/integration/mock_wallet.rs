@@ -1,15 +1,77 @@
 use anyhow::Result;
-use bdk::Balance;
-use lightning_knd::api::WalletInterface;
+use bdk::{wallet::AddressInfo, Balance, BlockTime, KeychainKind, LocalUtxo, TransactionDetails};
+use bitcoin::{
+    consensus::deserialize, hashes::Hash, Address, Network, OutPoint, Transaction, TxOut, Txid,
+};
+use hex::FromHex;
+use lightning_knd::api::{FeeRateSpec, WalletInterface};
+use test_utils::random_public_key;
+
+use crate::mock_lightning::TEST_TX;
 
 pub struct MockWallet {
     balance: Balance,
+    address: Address,
 }
 
 impl WalletInterface for MockWallet {
     fn balance(&self) -> Result<bdk::Balance> {
         Ok(self.balance.clone())
     }
+
+    fn network(&self) -> Network {
+        Network::Regtest
+    }
+
+    fn new_address(&self) -> Result<AddressInfo> {
+        Ok(AddressInfo {
+            index: 0,
+            address: self.address.clone(),
+            keychain: KeychainKind::External,
+        })
+    }
+
+    fn withdraw(
+        &self,
+        _address: Address,
+        _amount: Option<u64>,
+        _fee_rate: FeeRateSpec,
+    ) -> Result<Transaction> {
+        Ok(deserialize::<Transaction>(&Vec::<u8>::from_hex(TEST_TX).unwrap()).unwrap())
+    }
+
+    fn list_utxos(&self) -> Result<Vec<(LocalUtxo, Option<BlockTime>)>> {
+        let utxo = LocalUtxo {
+            outpoint: OutPoint {
+                txid: Txid::all_zeros(),
+                vout: 1,
+            },
+            txout: TxOut {
+                value: 1000,
+                script_pubkey: self.address.script_pubkey(),
+            },
+            keychain: KeychainKind::External,
+            is_spent: false,
+        };
+        Ok(vec![(
+            utxo,
+            Some(BlockTime {
+                height: 100,
+                timestamp: 1000,
+            }),
+        )])
+    }
+
+    fn list_transactions(&self) -> Result<Vec<TransactionDetails>> {
+        Ok(vec![TransactionDetails {
+            transaction: None,
+            txid: Txid::all_zeros(),
+            received: 1000,
+            sent: 0,
+            fee: Some(10),
+            confirmation_time: None,
+        }])
+    }
 }
 
 impl Default for MockWallet {
@@ -21,6 +83,11 @@ impl Default for MockWallet {
                 untrusted_pending: 3,
                 confirmed: 4,
             },
+            address: Address::p2wpkh(
+                &bitcoin::PublicKey::new(random_public_key()),
+                Network::Regtest,
+            )
+            .unwrap(),
         }
     }
 }
@@ -15,6 +15,7 @@ pub async fn test_prometheus() {
         num_nodes: 10,
         num_channels: 20,
         num_peers: 5,
+        synced_to_chain: true,
         wallet_balance: 500000,
         channels: vec![],
     });
@@ -48,6 +49,9 @@ pub async fn test_prometheus() {
         get_metric(&result, "wallet_balance"),
         metrics.wallet_balance as f64
     );
+    assert_eq!(get_metric(&result, "lightning_pending_monitor_writes"), 0.0);
+    assert_eq!(get_metric(&result, "lightning_failed_monitor_writes"), 0.0);
+    assert!(result.contains("# TYPE lightning_failed_monitor_writes counter"));
 
     let not_found = call_exporter(&address, "wrong").await.unwrap();
     assert_eq!(not_found, "Not Found");
@@ -66,7 +70,7 @@ fn get_metric(metrics: &str, name: &str) -> f64 {
         .find(|x| x.starts_with(name))
         .unwrap()
         .split(' ')
-        .last()
+        .next_back()
         .unwrap()
         .parse::<f64>()
         .unwrap()